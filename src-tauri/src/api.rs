@@ -0,0 +1,577 @@
+use crate::jobs::{JobInfo, JobManager, PauseMode};
+use crate::profiles::ProfileStore;
+use axum::extract::{Path, Query, Request, State};
+use axum::http::{header, StatusCode};
+use axum::middleware::{self, Next};
+use axum::response::sse::{Event, KeepAlive, Sse};
+use axum::response::{IntoResponse, Response};
+use axum::routing::{get, post};
+use axum::{Json, Router};
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
+use chacha20poly1305::aead::{rand_core::RngCore, OsRng};
+use serde::{Deserialize, Serialize};
+use std::convert::Infallible;
+use std::fs;
+use std::io::Write;
+use std::net::Ipv4Addr;
+use std::sync::Mutex;
+use tauri::Manager;
+use tokio::sync::watch;
+use tokio_stream::wrappers::{BroadcastStream, WatchStream};
+use tokio_stream::{Stream, StreamExt};
+
+// 設定ディレクトリに保存するアクセストークンのファイル
+const TOKEN_FILE: &str = "api-token";
+const TOKEN_BYTES: usize = 32;
+// 任意のコマンドやプログラムを実行できるため、APIやプロファイルからは受け付けないオプション
+// （別名も含む。設定ファイルやプラグインは中に同じオプションを書けるため対象にする）
+const FORBIDDEN_OPTIONS: &[&str] = &[
+    "--exec",
+    "--exec-before-download",
+    "--netrc-cmd",
+    "--downloader",
+    "--external-downloader",
+    "--downloader-args",
+    "--external-downloader-args",
+    "--ffmpeg-location",
+    "--config-locations",
+    "--config-location",
+    "--plugin-dirs",
+    "--use-postprocessor",
+    "--postprocessor-args",
+    "--ppa",
+    "--update",
+    "--update-to",
+];
+// 受け付けない短いオプション（-U: --update）
+const FORBIDDEN_SHORT_OPTIONS: &[char] = &['U'];
+// 値を取る短いオプション（まとめて指定した場合、これ以降は値として扱われる）
+const VALUE_SHORT_OPTIONS: &[char] = &[
+    'a', 'o', 'f', 'r', 'P', 'u', 'p', 'S', 'I', 'N', 'R', 't', '2',
+];
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct ApiConfig {
+    pub enabled: bool,
+    // 127.0.0.1でだけ待ち受ける
+    pub port: u16,
+}
+
+impl Default for ApiConfig {
+    fn default() -> Self {
+        ApiConfig {
+            enabled: false,
+            port: 38417,
+        }
+    }
+}
+
+impl ApiConfig {
+    pub fn validate(&self) -> Result<(), String> {
+        if self.port < 1024 {
+            return Err("api.port must be between 1024 and 65535".to_string());
+        }
+        Ok(())
+    }
+}
+
+// 設定画面で表示するAPIの状態（トークンは他のツールに設定するため表示する）
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ApiStatus {
+    pub running: bool,
+    pub url: Option<String>,
+    pub token: Option<String>,
+    // 起動に失敗した理由（ポートが使用中など）
+    pub error: Option<String>,
+}
+
+struct RunningServer {
+    port: u16,
+    task: tauri::async_runtime::JoinHandle<()>,
+    // 停止時に接続中のServer-Sent Eventsも終了させる
+    shutdown: watch::Sender<bool>,
+}
+
+// ローカルHTTP APIのサーバー（UIと同じJobManagerでジョブを登録・中止する）
+#[derive(Default)]
+pub struct ApiServer {
+    running: Mutex<Option<RunningServer>>,
+    error: Mutex<Option<String>>,
+}
+
+impl ApiServer {
+    // 設定に合わせてサーバーを開始・停止（実行中なら開始し直す）
+    pub fn apply(&self, app: &tauri::AppHandle, config: &ApiConfig) {
+        let mut running = self.running.lock().unwrap();
+        if let Some(server) = running.take() {
+            let _ = server.shutdown.send(true);
+            server.task.abort();
+            log::info!("Stopped local API server on port {}", server.port);
+        }
+        *self.error.lock().unwrap() = None;
+        if !config.enabled {
+            return;
+        }
+        match start(app, config.port) {
+            Ok(server) => {
+                log::info!("Started local API server on port {}", server.port);
+                *running = Some(server);
+            }
+            Err(e) => {
+                log::error!("Could not start local API server: {}", e);
+                *self.error.lock().unwrap() = Some(e);
+            }
+        }
+    }
+
+    fn status(&self) -> ApiStatus {
+        let port = self
+            .running
+            .lock()
+            .unwrap()
+            .as_ref()
+            .map(|server| server.port);
+        ApiStatus {
+            running: port.is_some(),
+            url: port.map(|port| format!("http://127.0.0.1:{}", port)),
+            token: port.and_then(|_| load_or_create_token().ok()),
+            error: self.error.lock().unwrap().clone(),
+        }
+    }
+}
+
+#[derive(Clone)]
+struct ApiState {
+    app: tauri::AppHandle,
+    port: u16,
+    token: String,
+    shutdown: watch::Receiver<bool>,
+}
+
+fn start(app: &tauri::AppHandle, port: u16) -> Result<RunningServer, String> {
+    let token = load_or_create_token()?;
+    // ポートが使用中なら設定の保存時に分かるよう、待ち受けは同期的に開始する
+    let listener = std::net::TcpListener::bind((Ipv4Addr::LOCALHOST, port))
+        .and_then(|listener| listener.set_nonblocking(true).map(|_| listener))
+        .map_err(|e| format!("Could not listen on 127.0.0.1:{}: {}", port, e))?;
+    let (shutdown, shutdown_receiver) = watch::channel(false);
+    let state = ApiState {
+        app: app.clone(),
+        port,
+        token,
+        shutdown: shutdown_receiver,
+    };
+
+    let router = Router::new()
+        .route("/api/jobs", get(list_jobs).post(enqueue))
+        .route("/api/jobs/{id}", get(get_job))
+        .route("/api/jobs/{id}/cancel", post(cancel_job))
+        .route("/api/jobs/{id}/pause", post(pause_job))
+        .route("/api/jobs/{id}/resume", post(resume_job))
+        .route("/api/events", get(events))
+        .layer(middleware::from_fn_with_state(state.clone(), authorize))
+        .with_state(state);
+    let task = tauri::async_runtime::spawn(async move {
+        let listener = match tokio::net::TcpListener::from_std(listener) {
+            Ok(listener) => listener,
+            Err(e) => {
+                log::error!("Could not start local API server: {}", e);
+                return;
+            }
+        };
+        if let Err(e) = axum::serve(listener, router).await {
+            log::error!("Local API server stopped: {}", e);
+        }
+    });
+    Ok(RunningServer {
+        port,
+        task,
+        shutdown,
+    })
+}
+
+fn token_path() -> Result<std::path::PathBuf, String> {
+    Ok(crate::paths::get()?.config_dir.join(TOKEN_FILE))
+}
+
+fn load_or_create_token() -> Result<String, String> {
+    match fs::read_to_string(token_path()?) {
+        Ok(token) if !token.trim().is_empty() => Ok(token.trim().to_string()),
+        _ => create_token(),
+    }
+}
+
+// 新しいトークンを生成して保存（以前のトークンは使えなくなる）
+fn create_token() -> Result<String, String> {
+    let mut bytes = [0u8; TOKEN_BYTES];
+    OsRng.fill_bytes(&mut bytes);
+    let token = URL_SAFE_NO_PAD.encode(bytes);
+    let mut file = crate::cookies::create_private_file(&token_path()?)?;
+    file.write_all(token.as_bytes()).map_err(|e| {
+        log::error!("Failed to write API token: {}", e);
+        format!("Failed to write API token: {}", e)
+    })?;
+    Ok(token)
+}
+
+// --exec=cmd の形式や、yt-dlpが受け付ける長いオプションの省略形、まとめて指定した短いオプションも対象にする
+// （--netrc は省略形ではなく別のオプション）
+pub(crate) fn is_forbidden_option(arg: &str) -> bool {
+    if let Some(long) = arg.strip_prefix("--") {
+        let name = long.split('=').next().unwrap_or_default();
+        return !name.is_empty()
+            && name != "netrc"
+            && FORBIDDEN_OPTIONS
+                .iter()
+                .any(|option| option[2..].starts_with(name));
+    }
+    let Some(short) = arg.strip_prefix('-') else {
+        return false;
+    };
+    for ch in short.chars() {
+        if FORBIDDEN_SHORT_OPTIONS.contains(&ch) {
+            return true;
+        }
+        if VALUE_SHORT_OPTIONS.contains(&ch) || !ch.is_ascii_alphanumeric() {
+            break;
+        }
+    }
+    false
+}
+
+// オプションの文字列を分割し、URLを加えたyt-dlpの引数にする
+fn enqueue_args(options: &str, urls: &[String]) -> Result<Vec<String>, String> {
+    let mut args = shlex::split(options)
+        .ok_or_else(|| "Invalid command line syntax - failed to parse arguments".to_string())?;
+    if let Some(option) = args.iter().find(|arg| is_forbidden_option(arg)) {
+        return Err(format!(
+            "Option {} is not allowed through the local API",
+            option
+        ));
+    }
+    args.extend(urls.iter().cloned());
+    Ok(args)
+}
+
+fn error_response(status: StatusCode, message: &str) -> Response {
+    (status, Json(serde_json::json!({ "error": message }))).into_response()
+}
+
+// Authorization: Bearer <token>、またはクエリのtoken（ヘッダーを指定できないEventSource用）
+async fn authorize(State(state): State<ApiState>, request: Request, next: Next) -> Response {
+    if let Err((status, message)) = check_authorization(&request, state.port, &state.token) {
+        return error_response(status, message);
+    }
+    next.run(request).await
+}
+
+fn check_authorization(
+    request: &Request,
+    port: u16,
+    expected: &str,
+) -> Result<(), (StatusCode, &'static str)> {
+    // DNSリバインディングで他のサイトから呼ばれないよう、Hostがローカルホストの場合だけ受け付ける
+    let host = request
+        .headers()
+        .get(header::HOST)
+        .and_then(|value| value.to_str().ok())
+        .unwrap_or_default();
+    if host != format!("127.0.0.1:{}", port) && host != format!("localhost:{}", port) {
+        return Err((StatusCode::FORBIDDEN, "Invalid host"));
+    }
+
+    let token = request
+        .headers()
+        .get(header::AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Bearer "))
+        .map(str::to_string)
+        .or_else(|| {
+            url::form_urlencoded::parse(request.uri().query().unwrap_or_default().as_bytes())
+                .find(|(key, _)| key == "token")
+                .map(|(_, value)| value.into_owned())
+        });
+    if !token.is_some_and(|token| constant_time_eq(token.as_bytes(), expected.as_bytes())) {
+        return Err((StatusCode::UNAUTHORIZED, "Invalid or missing token"));
+    }
+    Ok(())
+}
+
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0, |diff, (x, y)| diff | (x ^ y)) == 0
+}
+
+async fn list_jobs(State(state): State<ApiState>) -> Json<Vec<JobInfo>> {
+    Json(state.app.state::<JobManager>().list())
+}
+
+async fn get_job(State(state): State<ApiState>, Path(id): Path<String>) -> Response {
+    match state.app.state::<JobManager>().get(&id) {
+        Some(job) => Json(job).into_response(),
+        None => error_response(StatusCode::NOT_FOUND, &format!("Job not found: {}", id)),
+    }
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct EnqueueRequest {
+    urls: Vec<String>,
+    profile: Option<String>,
+    // run_yt_dlpと同じ形式のyt-dlpのオプション
+    #[serde(default)]
+    options: String,
+}
+
+// URLをジョブとして登録（結果を待たずにジョブIDを返す）
+async fn enqueue(State(state): State<ApiState>, Json(request): Json<EnqueueRequest>) -> Response {
+    log::info!(
+        "Local API enqueue with {} URLs, profile: {:?}",
+        request.urls.len(),
+        request.profile
+    );
+    let parsed = crate::urls::parse_url_list(&request.urls.join("\n"));
+    if parsed.urls.is_empty() {
+        return error_response(StatusCode::BAD_REQUEST, "No valid URLs provided");
+    }
+    if let Some(profile) = &request.profile {
+        if state.app.state::<ProfileStore>().get(profile).is_none() {
+            return error_response(
+                StatusCode::BAD_REQUEST,
+                &format!("Profile not found: {}", profile),
+            );
+        }
+    }
+    let args = match enqueue_args(&request.options, &parsed.urls) {
+        Ok(args) => args,
+        Err(e) => return error_response(StatusCode::BAD_REQUEST, &e),
+    };
+
+    match crate::engine::spawn_job(&state.app, args, request.profile) {
+        Ok(id) => (
+            StatusCode::ACCEPTED,
+            Json(serde_json::json!({ "id": id, "urls": parsed.urls })),
+        )
+            .into_response(),
+        Err(e) => error_response(StatusCode::INTERNAL_SERVER_ERROR, &e),
+    }
+}
+
+async fn cancel_job(State(state): State<ApiState>, Path(id): Path<String>) -> Response {
+    log::info!("Local API cancel job {}", id);
+    let jobs = state.app.state::<JobManager>();
+    match jobs.cancel(&id) {
+        Ok(()) => StatusCode::ACCEPTED.into_response(),
+        Err(e) if jobs.get(&id).is_none() => error_response(StatusCode::NOT_FOUND, &e),
+        Err(e) => error_response(StatusCode::CONFLICT, &e),
+    }
+}
+
+#[derive(Debug, Default, Deserialize)]
+struct PauseRequest {
+    mode: Option<PauseMode>,
+}
+
+// 一時停止（本文のmodeの指定がなければUnixではプロセスを止め、それ以外では終了させる）
+async fn pause_job(
+    State(state): State<ApiState>,
+    Path(id): Path<String>,
+    request: Option<Json<PauseRequest>>,
+) -> Response {
+    let mode = request.and_then(|Json(request)| request.mode);
+    log::info!("Local API pause job {}, mode: {:?}", id, mode);
+    let jobs = state.app.state::<JobManager>();
+    match jobs.pause(&id, mode.unwrap_or_default()) {
+        Ok(()) => StatusCode::ACCEPTED.into_response(),
+        Err(e) if jobs.get(&id).is_none() => error_response(StatusCode::NOT_FOUND, &e),
+        Err(e) => error_response(StatusCode::CONFLICT, &e),
+    }
+}
+
+async fn resume_job(State(state): State<ApiState>, Path(id): Path<String>) -> Response {
+    log::info!("Local API resume job {}", id);
+    let jobs = state.app.state::<JobManager>();
+    match jobs.resume(&id) {
+        Ok(()) => StatusCode::ACCEPTED.into_response(),
+        Err(e) if jobs.get(&id).is_none() => error_response(StatusCode::NOT_FOUND, &e),
+        Err(e) => error_response(StatusCode::CONFLICT, &e),
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct EventsQuery {
+    // 指定があればそのジョブのイベントだけを送る
+    job: Option<String>,
+}
+
+// ジョブのイベント（job-updated、yt-dlpの出力行など）をServer-Sent Eventsで配信
+async fn events(
+    State(state): State<ApiState>,
+    Query(query): Query<EventsQuery>,
+) -> Sse<impl Stream<Item = Result<Event, Infallible>>> {
+    let receiver = state.app.state::<JobManager>().events.subscribe();
+    // 受け取りきれずに破棄されたイベントは飛ばし、サーバーの停止で終了する
+    let events = BroadcastStream::new(receiver).map(|event| Some(event.ok()));
+    let stop = WatchStream::new(state.shutdown)
+        .filter(|stopped| *stopped)
+        .map(|_| None);
+    let stream = events
+        .merge(stop)
+        .take_while(Option::is_some)
+        .filter_map(move |event| {
+            let event = event.flatten()?;
+            if query.job.as_ref().is_some_and(|job| *job != event.job_id) {
+                return None;
+            }
+            Event::default()
+                .event(&event.event)
+                .json_data(&event)
+                .ok()
+                .map(Ok)
+        });
+    Sse::new(stream).keep_alive(KeepAlive::default())
+}
+
+#[tauri::command]
+pub async fn get_api_status(server: tauri::State<'_, ApiServer>) -> Result<ApiStatus, String> {
+    Ok(server.status())
+}
+
+// トークンを再生成し、実行中なら新しいトークンで開始し直す
+#[tauri::command]
+pub async fn regenerate_api_token(
+    app: tauri::AppHandle,
+    server: tauri::State<'_, ApiServer>,
+    store: tauri::State<'_, crate::settings::SettingsStore>,
+) -> Result<ApiStatus, String> {
+    log::info!("Invoked regenerate_api_token");
+    create_token()?;
+    server.apply(&app, &store.get().api);
+    Ok(server.status())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::body::Body;
+
+    const PORT: u16 = 8765;
+    const TOKEN: &str = "secret-token";
+
+    fn request(host: &str, uri: &str, authorization: Option<&str>) -> Request {
+        let mut builder = Request::builder().uri(uri).header(header::HOST, host);
+        if let Some(authorization) = authorization {
+            builder = builder.header(header::AUTHORIZATION, authorization);
+        }
+        builder.body(Body::empty()).unwrap()
+    }
+
+    fn status(request: &Request) -> StatusCode {
+        match check_authorization(request, PORT, TOKEN) {
+            Ok(()) => StatusCode::OK,
+            Err((status, _)) => status,
+        }
+    }
+
+    #[test]
+    fn authorization_checks_the_host() {
+        let bearer = Some("Bearer secret-token");
+        assert_eq!(
+            status(&request("127.0.0.1:8765", "/api/jobs", bearer)),
+            StatusCode::OK
+        );
+        assert_eq!(
+            status(&request("localhost:8765", "/api/jobs", bearer)),
+            StatusCode::OK
+        );
+        assert_eq!(
+            status(&request("evil.example:8765", "/api/jobs", bearer)),
+            StatusCode::FORBIDDEN
+        );
+        assert_eq!(
+            status(&request("127.0.0.1:9999", "/api/jobs", bearer)),
+            StatusCode::FORBIDDEN
+        );
+    }
+
+    #[test]
+    fn authorization_checks_the_token() {
+        let host = "127.0.0.1:8765";
+        assert_eq!(
+            status(&request(host, "/api/jobs", None)),
+            StatusCode::UNAUTHORIZED
+        );
+        assert_eq!(
+            status(&request(host, "/api/jobs", Some("Bearer wrong-token"))),
+            StatusCode::UNAUTHORIZED
+        );
+        assert_eq!(
+            status(&request(host, "/api/jobs", Some("secret-token"))),
+            StatusCode::UNAUTHORIZED
+        );
+        assert_eq!(
+            status(&request(host, "/api/events?token=secret-token", None)),
+            StatusCode::OK
+        );
+        assert_eq!(
+            status(&request(host, "/api/events?token=secret", None)),
+            StatusCode::UNAUTHORIZED
+        );
+    }
+
+    #[test]
+    fn forbidden_options_are_detected() {
+        for arg in [
+            "--exec",
+            "--exec=rm -rf ~",
+            "--exec-before-download",
+            "--netrc-cmd",
+            "--netrc-c",
+            "--downloader=/tmp/bin",
+            "--external-downloader",
+            "--downloader-args",
+            "--ffmpeg-location",
+            "--config-locations=evil.conf",
+            "--plugin-dirs",
+            "--use-postprocessor",
+            "--ppa",
+            "--update-to",
+            "-U",
+            "-iU",
+        ] {
+            assert!(is_forbidden_option(arg), "{}", arg);
+        }
+        for arg in [
+            "--no-exec",
+            "--netrc",
+            "--",
+            "-x",
+            "-fUHD",
+            "-o%(title)s [%(id)s].%(ext)s",
+            "https://example.com/--exec",
+        ] {
+            assert!(!is_forbidden_option(arg), "{}", arg);
+        }
+    }
+
+    #[test]
+    fn enqueue_rejects_forbidden_options() {
+        let urls = vec!["https://example.com/watch?v=1".to_string()];
+        assert_eq!(
+            enqueue_args("-x --audio-format mp3", &urls).unwrap(),
+            vec![
+                "-x",
+                "--audio-format",
+                "mp3",
+                "https://example.com/watch?v=1"
+            ]
+        );
+        assert!(enqueue_args("--exec 'touch /tmp/x'", &urls)
+            .unwrap_err()
+            .contains("--exec"));
+        assert!(enqueue_args("--downloader /tmp/evil", &urls).is_err());
+        assert!(enqueue_args("--config-locations evil.conf", &urls).is_err());
+        assert!(enqueue_args("-x 'unclosed", &urls).is_err());
+    }
+}
@@ -0,0 +1,366 @@
+use crate::jobs::JobManager;
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use std::fs;
+use std::path::{Path, PathBuf};
+use url::Url;
+
+// プロファイル未指定時に使うアーカイブ名
+pub const DEFAULT_PROFILE: &str = "default";
+
+// アーカイブの1エントリ（yt-dlpの--download-archiveと同じ「extractor id」形式）
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ArchiveEntry {
+    pub extractor: String,
+    pub id: String,
+}
+
+impl ArchiveEntry {
+    fn parse(line: &str) -> Option<Self> {
+        let mut parts = line.split_whitespace();
+        let extractor = parts.next()?;
+        let id = parts.next()?;
+        Some(ArchiveEntry {
+            extractor: extractor.to_lowercase(),
+            id: id.to_string(),
+        })
+    }
+
+    fn to_line(&self) -> String {
+        format!("{} {}", self.extractor, self.id)
+    }
+}
+
+// 実行前チェックの結果
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ArchiveCheck {
+    pub profile: String,
+    pub total: usize,
+    pub skipped: usize,
+    // URLから動画IDを判別できずチェックできなかった件数
+    pub unknown: usize,
+    pub skipped_urls: Vec<String>,
+}
+
+// アーカイブのエントリ一覧（検索文字列で絞り込み可能）
+#[tauri::command]
+pub async fn list_archive_entries(
+    profile: Option<String>,
+    search: Option<String>,
+) -> Result<Vec<ArchiveEntry>, String> {
+    log::info!(
+        "Invoked list_archive_entries with profile: {:?}, search: {:?}",
+        profile,
+        search
+    );
+    let entries = load_entries(&archive_path(profile.as_deref())?)?;
+
+    let search = search.map(|s| s.trim().to_lowercase()).unwrap_or_default();
+    if search.is_empty() {
+        return Ok(entries);
+    }
+    Ok(entries
+        .into_iter()
+        .filter(|entry| entry.to_line().to_lowercase().contains(&search))
+        .collect())
+}
+
+// 外部のアーカイブファイルを取り込み（重複は除外）、追加件数をリターン
+#[tauri::command]
+pub async fn import_archive(
+    profile: Option<String>,
+    path: String,
+    jobs: tauri::State<'_, JobManager>,
+) -> Result<usize, String> {
+    log::info!(
+        "Invoked import_archive with profile: {:?}, path: {:?}",
+        profile,
+        path
+    );
+    ensure_not_in_use(&jobs, profile.as_deref())?;
+    let archive = archive_path(profile.as_deref())?;
+    let mut entries = load_entries(&archive)?;
+    let mut known: HashSet<ArchiveEntry> = entries.iter().cloned().collect();
+
+    let imported = load_entries(Path::new(&path))?;
+    let mut added = 0;
+    for entry in imported {
+        if known.insert(entry.clone()) {
+            entries.push(entry);
+            added += 1;
+        }
+    }
+
+    save_entries(&archive, &entries)?;
+    log::info!("Imported {} archive entries into {:?}", added, archive);
+    Ok(added)
+}
+
+// アーカイブを指定パスに書き出し、件数をリターン
+#[tauri::command]
+pub async fn export_archive(profile: Option<String>, path: String) -> Result<usize, String> {
+    log::info!(
+        "Invoked export_archive with profile: {:?}, path: {:?}",
+        profile,
+        path
+    );
+    let entries = load_entries(&archive_path(profile.as_deref())?)?;
+    save_entries(Path::new(&path), &entries)?;
+    Ok(entries.len())
+}
+
+// 指定したエントリをアーカイブから削除し、削除件数をリターン
+#[tauri::command]
+pub async fn delete_archive_entries(
+    profile: Option<String>,
+    entries: Vec<ArchiveEntry>,
+    jobs: tauri::State<'_, JobManager>,
+) -> Result<usize, String> {
+    log::info!(
+        "Invoked delete_archive_entries with profile: {:?}, entries: {:?}",
+        profile,
+        entries
+    );
+    ensure_not_in_use(&jobs, profile.as_deref())?;
+    let archive = archive_path(profile.as_deref())?;
+    let targets: HashSet<ArchiveEntry> = entries
+        .into_iter()
+        .map(|entry| ArchiveEntry {
+            extractor: entry.extractor.to_lowercase(),
+            id: entry.id,
+        })
+        .collect();
+
+    let current = load_entries(&archive)?;
+    let before = current.len();
+    let remaining: Vec<ArchiveEntry> = current
+        .into_iter()
+        .filter(|entry| !targets.contains(entry))
+        .collect();
+
+    save_entries(&archive, &remaining)?;
+    Ok(before - remaining.len())
+}
+
+// URLリストをアーカイブと照合し、スキップされる件数を確認
+#[tauri::command]
+pub async fn check_archive(profile: Option<String>, urls: String) -> Result<ArchiveCheck, String> {
+    log::info!("Invoked check_archive with profile: {:?}", profile);
+    let urls: Vec<String> = urls
+        .lines()
+        .map(|line| line.trim())
+        .filter(|line| !line.is_empty())
+        .map(|line| line.to_string())
+        .collect();
+    check_urls(profile.as_deref(), &urls)
+}
+
+// URLリストとアーカイブの照合
+pub fn check_urls(profile: Option<&str>, urls: &[String]) -> Result<ArchiveCheck, String> {
+    let entries = archived_entries(profile)?;
+
+    let mut check = ArchiveCheck {
+        profile: profile_name(profile),
+        total: urls.len(),
+        skipped: 0,
+        unknown: 0,
+        skipped_urls: Vec::new(),
+    };
+    for url in urls {
+        match archive_entry_for_url(url) {
+            Some(entry) if entries.contains(&entry) => {
+                check.skipped += 1;
+                check.skipped_urls.push(url.clone());
+            }
+            Some(_) => {}
+            None => check.unknown += 1,
+        }
+    }
+    Ok(check)
+}
+
+// プロファイルのアーカイブに記録済みのエントリ
+pub fn archived_entries(profile: Option<&str>) -> Result<HashSet<ArchiveEntry>, String> {
+    Ok(load_entries(&archive_path(profile)?)?.into_iter().collect())
+}
+
+// プロファイルごとのアーカイブファイルのパス
+pub fn archive_path(profile: Option<&str>) -> Result<PathBuf, String> {
+    let dir = crate::paths::data_dir()?.join("archives");
+    fs::create_dir_all(&dir).map_err(|e| {
+        log::error!("Could not create archives directory: {}", e);
+        format!("Could not create archives directory: {}", e)
+    })?;
+    let path = dir.join(format!("{}.txt", file_name(profile)));
+
+    // 以前の名前（使えない文字を'_'に置き換えただけ）のファイルがあれば引き継ぐ
+    // 他のプロファイルと共有していた可能性があるため、移動せずコピーする
+    let legacy = dir.join(format!("{}.txt", legacy_file_name(profile)));
+    if legacy != path && !path.exists() && legacy.exists() {
+        match fs::copy(&legacy, &path) {
+            Ok(_) => log::info!("Copied archive {:?} to {:?}", legacy, path),
+            Err(e) => log::warn!("Could not copy archive {:?}: {}", legacy, e),
+        }
+    }
+    Ok(path)
+}
+
+// 表示用のプロファイル名
+fn profile_name(profile: Option<&str>) -> String {
+    profile
+        .map(|p| p.trim())
+        .filter(|p| !p.is_empty())
+        .unwrap_or(DEFAULT_PROFILE)
+        .to_string()
+}
+
+// プロファイル名をファイル名に変換
+// 英小文字・数字・'-'・'_'以外はUTF-8のバイトごとに%XXにして、
+// 別のプロファイル（"a b"と"a_b"、大文字小文字を区別しないファイルシステムでの"A"と"a"）が同じファイルにならないようにする
+fn file_name(profile: Option<&str>) -> String {
+    let mut name = String::new();
+    for c in profile_name(profile).chars() {
+        if c.is_ascii_lowercase() || c.is_ascii_digit() || c == '-' || c == '_' {
+            name.push(c);
+        } else {
+            let mut buf = [0u8; 4];
+            for byte in c.encode_utf8(&mut buf).bytes() {
+                name.push_str(&format!("%{:02X}", byte));
+            }
+        }
+    }
+    name
+}
+
+// 以前のバージョンのファイル名
+fn legacy_file_name(profile: Option<&str>) -> String {
+    profile_name(profile)
+        .chars()
+        .map(|c| {
+            if c.is_alphanumeric() || c == '-' || c == '_' {
+                c
+            } else {
+                '_'
+            }
+        })
+        .collect()
+}
+
+// 実行中・待機中のジョブが同じアーカイブを使っている場合は編集しない
+// （yt-dlpが追記している途中で書き換えると記録が失われる）
+fn ensure_not_in_use(jobs: &JobManager, profile: Option<&str>) -> Result<(), String> {
+    let name = file_name(profile);
+    let in_use = jobs
+        .list()
+        .iter()
+        .any(|job| !job.status.is_finished() && file_name(job.profile.as_deref()) == name);
+    if in_use {
+        return Err(format!(
+            "The archive of profile {:?} is in use by a running job",
+            profile_name(profile)
+        ));
+    }
+    Ok(())
+}
+
+fn load_entries(path: &Path) -> Result<Vec<ArchiveEntry>, String> {
+    let content = match fs::read_to_string(path) {
+        Ok(content) => content,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(e) => {
+            log::error!("Could not read archive file {:?}: {}", path, e);
+            return Err(format!("Could not read archive file {:?}: {}", path, e));
+        }
+    };
+    Ok(content.lines().filter_map(ArchiveEntry::parse).collect())
+}
+
+// 途中で失敗しても元のファイルが壊れないよう、一時ファイルに書いてから置き換える
+fn save_entries(path: &Path, entries: &[ArchiveEntry]) -> Result<(), String> {
+    let mut tmp_path = path.as_os_str().to_owned();
+    tmp_path.push(".tmp");
+    let tmp_path = PathBuf::from(tmp_path);
+
+    let content: String = entries
+        .iter()
+        .map(|entry| format!("{}\n", entry.to_line()))
+        .collect();
+    fs::write(&tmp_path, content).map_err(|e| {
+        log::error!("Failed to write archive file {:?}: {}", tmp_path, e);
+        format!("Failed to write archive file {:?}: {}", tmp_path, e)
+    })?;
+    fs::rename(&tmp_path, path).map_err(|e| {
+        log::error!("Failed to save archive file {:?}: {}", path, e);
+        format!("Failed to save archive file {:?}: {}", path, e)
+    })
+}
+
+// URLからアーカイブのエントリを推定（ネットワークを使わず判別できるサイトのみ）
+pub fn archive_entry_for_url(url: &str) -> Option<ArchiveEntry> {
+    let parsed = Url::parse(url.trim()).ok()?;
+    let host = parsed.host_str()?.trim_start_matches("www.").to_lowercase();
+    let segments: Vec<&str> = parsed
+        .path_segments()
+        .map(|s| s.filter(|seg| !seg.is_empty()).collect())
+        .unwrap_or_default();
+    let entry = |extractor: &str, id: &str| {
+        Some(ArchiveEntry {
+            extractor: extractor.to_string(),
+            id: id.to_string(),
+        })
+    };
+
+    match host.as_str() {
+        "youtube.com" | "m.youtube.com" | "music.youtube.com" => match segments.as_slice() {
+            ["watch"] => {
+                let id = parsed
+                    .query_pairs()
+                    .find(|(key, _)| key == "v")
+                    .map(|(_, value)| value.to_string())?;
+                entry("youtube", &id)
+            }
+            ["shorts" | "embed" | "live" | "v", id, ..] => entry("youtube", id),
+            _ => None,
+        },
+        "youtu.be" => entry("youtube", segments.first()?),
+        "vimeo.com" => match segments.as_slice() {
+            [id, ..] if id.chars().all(|c| c.is_ascii_digit()) => entry("vimeo", id),
+            _ => None,
+        },
+        "nicovideo.jp" | "sp.nicovideo.jp" => match segments.as_slice() {
+            ["watch", id, ..] => entry("niconico", id),
+            _ => None,
+        },
+        "nico.ms" => entry("niconico", segments.first()?),
+        "dailymotion.com" => match segments.as_slice() {
+            ["video", id, ..] => entry("dailymotion", id),
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn file_names_do_not_collide() {
+        assert_eq!(file_name(None), "default");
+        assert_eq!(file_name(Some("  ")), "default");
+        assert_eq!(file_name(Some("music-hq_2")), "music-hq_2");
+        assert_eq!(file_name(Some("a b")), "a%20b");
+        assert_eq!(file_name(Some("a_b")), "a_b");
+        assert_eq!(file_name(Some("A")), "%41");
+        assert_eq!(file_name(Some("a%20b")), "a%2520b");
+        assert_eq!(file_name(Some("音楽")), "%E9%9F%B3%E6%A5%BD");
+    }
+
+    #[test]
+    fn legacy_file_names_replace_symbols() {
+        assert_eq!(legacy_file_name(Some("a b")), "a_b");
+        assert_eq!(legacy_file_name(Some("Music")), "Music");
+    }
+}
@@ -0,0 +1,248 @@
+use crate::scheduler::{offset, parse_hhmm};
+use serde::{Deserialize, Serialize};
+use std::sync::{Arc, Mutex};
+use time::OffsetDateTime;
+
+// 配分がこの比率以上変わったときだけ実行中のプロセスを再起動する（再起動のたびに接続し直すため）
+const RESTART_RATIO: f64 = 1.25;
+
+// 時間帯ごとの上限
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BandwidthWindow {
+    // start〜end（HH:MM）。endがstartより前なら日付をまたぐ
+    pub start: String,
+    pub end: String,
+    // 全ジョブ合計の上限（KiB/s、0なら無制限）
+    pub limit_kib: u64,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct BandwidthConfig {
+    // 全ジョブ合計の上限（KiB/s、0なら無制限）。実行中のプロセス数で等分して--limit-rateで指定する
+    pub limit_kib: u64,
+    // 時間帯ごとの上書き（最初に一致したものを使う）
+    pub schedule: Vec<BandwidthWindow>,
+    // 時間帯はフロントエンドから渡されたUTCからのオフセット（分）の現地時刻として扱う
+    pub utc_offset_minutes: i32,
+}
+
+impl BandwidthConfig {
+    pub fn validate(&self) -> Result<(), String> {
+        offset(self.utc_offset_minutes)?;
+        for window in &self.schedule {
+            if parse_hhmm(&window.start)? == parse_hhmm(&window.end)? {
+                return Err("Bandwidth schedule start and end must differ".to_string());
+            }
+        }
+        Ok(())
+    }
+
+    // 指定した時刻の全体の上限（無制限ならNone）
+    fn limit_at(&self, now: OffsetDateTime) -> Option<u64> {
+        let time = offset(self.utc_offset_minutes)
+            .map(|offset| now.to_offset(offset).time())
+            .ok()?;
+        let limit_kib = self
+            .schedule
+            .iter()
+            .find(
+                |window| match (parse_hhmm(&window.start), parse_hhmm(&window.end)) {
+                    (Ok(start), Ok(end)) if start < end => start <= time && time < end,
+                    (Ok(start), Ok(end)) => start <= time || time < end,
+                    _ => false,
+                },
+            )
+            .map(|window| window.limit_kib)
+            .unwrap_or(self.limit_kib);
+        (limit_kib > 0).then_some(limit_kib)
+    }
+}
+
+// 全ジョブで共有する帯域の上限を、実行中のyt-dlpプロセスに配分する
+#[derive(Default)]
+pub struct BandwidthBudget {
+    config: Mutex<BandwidthConfig>,
+    // 上限の配分を受けている実行中のプロセス数
+    active: Mutex<usize>,
+}
+
+// 配分を受けている間の登録（dropで解除）
+pub struct BandwidthSlot {
+    budget: Arc<BandwidthBudget>,
+}
+
+impl Drop for BandwidthSlot {
+    fn drop(&mut self) {
+        let mut active = self.budget.active.lock().unwrap();
+        *active = active.saturating_sub(1);
+    }
+}
+
+impl BandwidthBudget {
+    pub fn set_config(&self, config: BandwidthConfig) {
+        *self.config.lock().unwrap() = config;
+    }
+
+    // プロセスを配分の対象として登録
+    pub fn register(self: &Arc<Self>) -> BandwidthSlot {
+        *self.active.lock().unwrap() += 1;
+        BandwidthSlot {
+            budget: self.clone(),
+        }
+    }
+
+    // 現在のプロセスごとの上限（KiB/s、無制限ならNone）
+    // 開始・終了したプロセスや時間帯の切り替わりで変わるため、実行中も確認する
+    pub fn share(&self) -> Option<u64> {
+        let limit_kib = self
+            .config
+            .lock()
+            .unwrap()
+            .limit_at(OffsetDateTime::now_utc())?;
+        Some(share_of(limit_kib, *self.active.lock().unwrap()))
+    }
+}
+
+// 全体の上限を実行中のプロセス数で等分（1KiB/s未満にはしない）
+fn share_of(limit_kib: u64, active: usize) -> u64 {
+    (limit_kib / active.max(1) as u64).max(1)
+}
+
+// 実行中のプロセスの上限を新しい配分に合わせるために再起動するかどうか
+// 小さな変化では再起動せず、上限の有無が変わった場合は必ず再起動する
+pub fn should_restart(current: Option<u64>, share: Option<u64>) -> bool {
+    match (current, share) {
+        (Some(current), Some(share)) => {
+            current.max(share) as f64 / current.min(share) as f64 >= RESTART_RATIO
+        }
+        (current, share) => current != share,
+    }
+}
+
+// ユーザーが-r・--limit-rate（別名--rate-limit）を指定している場合は配分の対象にしない
+pub fn has_rate_option(args: &[String]) -> bool {
+    args.iter().any(|arg| {
+        let arg = arg.as_str();
+        matches!(arg, "-r" | "--limit-rate" | "--rate-limit")
+            || arg.starts_with("--limit-rate=")
+            || arg.starts_with("--rate-limit=")
+            || arg
+                .strip_prefix("-r")
+                .is_some_and(|value| value.starts_with(|c: char| c.is_ascii_digit()))
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use time::{Date, Month};
+
+    fn utc(hour: u8, minute: u8) -> OffsetDateTime {
+        Date::from_calendar_date(2026, Month::January, 1)
+            .unwrap()
+            .with_hms(hour, minute, 0)
+            .unwrap()
+            .assume_utc()
+    }
+
+    fn args(args: &[&str]) -> Vec<String> {
+        args.iter().map(|arg| arg.to_string()).collect()
+    }
+
+    #[test]
+    fn share_is_split_between_registered_processes() {
+        let budget = Arc::new(BandwidthBudget::default());
+        assert_eq!(budget.share(), None);
+
+        budget.set_config(BandwidthConfig {
+            limit_kib: 1000,
+            ..Default::default()
+        });
+        assert_eq!(budget.share(), Some(1000));
+        let first = budget.register();
+        assert_eq!(budget.share(), Some(1000));
+        let second = budget.register();
+        let third = budget.register();
+        assert_eq!(budget.share(), Some(333));
+        drop(second);
+        assert_eq!(budget.share(), Some(500));
+        drop(first);
+        drop(third);
+        assert_eq!(budget.share(), Some(1000));
+    }
+
+    #[test]
+    fn share_is_at_least_one_kib() {
+        assert_eq!(share_of(3, 4), 1);
+        assert_eq!(share_of(0, 0), 1);
+        assert_eq!(share_of(1000, 0), 1000);
+    }
+
+    #[test]
+    fn limit_follows_schedule_windows() {
+        let config = BandwidthConfig {
+            limit_kib: 1000,
+            schedule: vec![
+                BandwidthWindow {
+                    start: "09:00".to_string(),
+                    end: "18:00".to_string(),
+                    limit_kib: 200,
+                },
+                // 日付をまたぐ時間帯は無制限
+                BandwidthWindow {
+                    start: "23:00".to_string(),
+                    end: "06:00".to_string(),
+                    limit_kib: 0,
+                },
+            ],
+            utc_offset_minutes: 540,
+        };
+        // UTC+9の9:00〜18:00
+        assert_eq!(config.limit_at(utc(0, 0)), Some(200));
+        assert_eq!(config.limit_at(utc(8, 59)), Some(200));
+        assert_eq!(config.limit_at(utc(9, 0)), Some(1000));
+        // UTC+9の23:00〜翌6:00
+        assert_eq!(config.limit_at(utc(14, 0)), None);
+        assert_eq!(config.limit_at(utc(20, 59)), None);
+        assert_eq!(config.limit_at(utc(21, 0)), Some(1000));
+    }
+
+    #[test]
+    fn restarts_only_for_large_changes() {
+        assert!(!should_restart(Some(1000), Some(1000)));
+        assert!(!should_restart(Some(240), Some(200)));
+        assert!(should_restart(Some(250), Some(200)));
+        assert!(!should_restart(Some(200), Some(240)));
+        assert!(should_restart(Some(333), Some(250)));
+        assert!(should_restart(Some(500), Some(1000)));
+        assert!(should_restart(None, Some(500)));
+        assert!(should_restart(Some(500), None));
+        assert!(!should_restart(None, None));
+    }
+
+    #[test]
+    fn detects_user_rate_options() {
+        for rate in [
+            &["-r", "1M"][..],
+            &["-r500K"],
+            &["-r1.5M"],
+            &["--limit-rate", "1M"],
+            &["--limit-rate=1M"],
+            &["--rate-limit", "1M"],
+            &["--rate-limit=1M"],
+        ] {
+            assert!(has_rate_option(&args(rate)), "{:?}", rate);
+        }
+        for other in [
+            &["--recode-video", "mp4"][..],
+            &["-rf"],
+            &["--restrict-filenames"],
+            &["--limit-rates"],
+            &["--remux-video", "mkv"],
+        ] {
+            assert!(!has_rate_option(&args(other)), "{:?}", other);
+        }
+    }
+}
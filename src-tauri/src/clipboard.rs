@@ -0,0 +1,241 @@
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tauri::Emitter;
+
+// 正規表現として扱うパターンの接頭辞（それ以外はドメイン名）
+const REGEX_PREFIX: &str = "re:";
+// 保留中の一覧に残すURLの上限
+const MAX_PENDING_URLS: usize = 1000;
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct ClipboardConfig {
+    pub enabled: bool,
+    // 取り込むURLのドメイン（サブドメインも一致）、または「re:」で始まる正規表現
+    pub patterns: Vec<String>,
+    pub poll_interval_ms: u64,
+}
+
+impl Default for ClipboardConfig {
+    fn default() -> Self {
+        ClipboardConfig {
+            enabled: false,
+            patterns: [
+                "youtube.com",
+                "youtu.be",
+                "nicovideo.jp",
+                "nico.ms",
+                "vimeo.com",
+                "dailymotion.com",
+                "twitch.tv",
+                "twitter.com",
+                "x.com",
+                "tiktok.com",
+                "bilibili.com",
+                "soundcloud.com",
+            ]
+            .iter()
+            .map(|pattern| pattern.to_string())
+            .collect(),
+            poll_interval_ms: 1000,
+        }
+    }
+}
+
+impl ClipboardConfig {
+    pub fn validate(&self) -> Result<(), String> {
+        if self.poll_interval_ms < 100 {
+            return Err("clipboard.pollIntervalMs must be at least 100".to_string());
+        }
+        self.matcher().map(|_| ())
+    }
+
+    fn matcher(&self) -> Result<UrlMatcher, String> {
+        let mut matcher = UrlMatcher::default();
+        for pattern in &self.patterns {
+            let pattern = pattern.trim();
+            if pattern.is_empty() {
+                continue;
+            }
+            match pattern.strip_prefix(REGEX_PREFIX) {
+                Some(regex) => matcher.regexes.push(
+                    regex::Regex::new(regex)
+                        .map_err(|e| format!("Invalid clipboard pattern {:?}: {}", pattern, e))?,
+                ),
+                None => matcher.domains.push(
+                    pattern
+                        .trim_start_matches("*.")
+                        .trim_start_matches("www.")
+                        .to_lowercase(),
+                ),
+            }
+        }
+        Ok(matcher)
+    }
+}
+
+#[derive(Default)]
+struct UrlMatcher {
+    domains: Vec<String>,
+    regexes: Vec<regex::Regex>,
+}
+
+impl UrlMatcher {
+    fn matches(&self, url: &str) -> bool {
+        let host = url::Url::parse(url)
+            .ok()
+            .and_then(|url| url.host_str().map(str::to_lowercase))
+            .unwrap_or_default();
+        self.domains
+            .iter()
+            .any(|domain| host == *domain || host.ends_with(&format!(".{}", domain)))
+            || self.regexes.iter().any(|regex| regex.is_match(url))
+    }
+}
+
+// url-capturedイベントで送信する内容
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CapturedUrl {
+    pub url: String,
+    pub pending_count: usize,
+}
+
+#[derive(Default)]
+struct PendingUrls {
+    urls: Vec<String>,
+    keys: HashSet<String>,
+}
+
+// クリップボードの監視と、取り込んだURLの保留中の一覧
+#[derive(Default)]
+pub struct ClipboardWatcher {
+    pending: Arc<Mutex<PendingUrls>>,
+    // 実行中の監視スレッドの停止フラグ
+    stop: Mutex<Option<Arc<AtomicBool>>>,
+}
+
+impl ClipboardWatcher {
+    // 設定に合わせて監視を開始・停止（実行中なら新しい設定で開始し直す）
+    pub fn apply(&self, app: &tauri::AppHandle, config: &ClipboardConfig) {
+        if let Some(stop) = self.stop.lock().unwrap().take() {
+            stop.store(true, Ordering::Relaxed);
+            log::info!("Stopped clipboard watcher");
+        }
+        if !config.enabled {
+            return;
+        }
+        let matcher = match config.matcher() {
+            Ok(matcher) => matcher,
+            Err(e) => {
+                log::error!("Could not start clipboard watcher: {}", e);
+                return;
+            }
+        };
+
+        let stop = Arc::new(AtomicBool::new(false));
+        *self.stop.lock().unwrap() = Some(stop.clone());
+        let pending = self.pending.clone();
+        let app = app.clone();
+        let interval = Duration::from_millis(config.poll_interval_ms);
+        // クリップボードのAPIは同期的なため専用のスレッドで確認する
+        std::thread::spawn(move || {
+            let mut clipboard = match arboard::Clipboard::new() {
+                Ok(clipboard) => clipboard,
+                Err(e) => {
+                    log::error!("Could not access clipboard: {}", e);
+                    return;
+                }
+            };
+            log::info!("Started clipboard watcher");
+            // 開始前からクリップボードにあった内容は取り込まない
+            let mut last_text = clipboard.get_text().unwrap_or_default();
+            while !stop.load(Ordering::Relaxed) {
+                std::thread::sleep(interval);
+                // テキスト以外（画像など）や空の場合はエラーになるため無視
+                let Ok(text) = clipboard.get_text() else {
+                    continue;
+                };
+                if text == last_text {
+                    continue;
+                }
+                for url in crate::urls::parse_url_list(&text).urls {
+                    if !matcher.matches(&url) {
+                        continue;
+                    }
+                    let pending_count = {
+                        let mut pending = pending.lock().unwrap();
+                        if pending.urls.len() >= MAX_PENDING_URLS
+                            || !pending.keys.insert(crate::urls::dedupe_key(&url))
+                        {
+                            continue;
+                        }
+                        pending.urls.push(url.clone());
+                        pending.urls.len()
+                    };
+                    log::info!("Captured URL from clipboard: {}", url);
+                    let _ = app.emit("url-captured", CapturedUrl { url, pending_count });
+                }
+                last_text = text;
+            }
+        });
+    }
+
+    fn pending_urls(&self) -> Vec<String> {
+        self.pending.lock().unwrap().urls.clone()
+    }
+
+    // 指定したURL（指定がなければ全て）を保留中の一覧から削除
+    fn remove(&self, urls: Option<&[String]>) -> Vec<String> {
+        let mut pending = self.pending.lock().unwrap();
+        match urls {
+            Some(urls) => {
+                let keys: HashSet<String> = urls
+                    .iter()
+                    .map(|url| crate::urls::dedupe_key(url))
+                    .collect();
+                pending
+                    .urls
+                    .retain(|url| !keys.contains(&crate::urls::dedupe_key(url)));
+                pending.keys.retain(|key| !keys.contains(key));
+            }
+            None => *pending = PendingUrls::default(),
+        }
+        pending.urls.clone()
+    }
+}
+
+// クリップボードの監視のオン・オフ（設定にも保存）
+#[tauri::command]
+pub async fn set_clipboard_watch(
+    enabled: bool,
+    app: tauri::AppHandle,
+    store: tauri::State<'_, crate::settings::SettingsStore>,
+    watcher: tauri::State<'_, ClipboardWatcher>,
+) -> Result<crate::settings::Settings, String> {
+    log::info!("Invoked set_clipboard_watch with enabled: {}", enabled);
+    let updated = store.update(|settings| settings.clipboard.enabled = enabled)?;
+    watcher.apply(&app, &updated.clipboard);
+    Ok(updated)
+}
+
+// クリップボードから取り込んで保留中のURL（取り込んだ順）
+#[tauri::command]
+pub async fn get_pending_urls(
+    watcher: tauri::State<'_, ClipboardWatcher>,
+) -> Result<Vec<String>, String> {
+    Ok(watcher.pending_urls())
+}
+
+// 保留中のURLを削除（urlsの指定がなければ全て）し、残りをリターン
+#[tauri::command]
+pub async fn clear_pending_urls(
+    urls: Option<Vec<String>>,
+    watcher: tauri::State<'_, ClipboardWatcher>,
+) -> Result<Vec<String>, String> {
+    log::info!("Invoked clear_pending_urls with urls: {:?}", urls);
+    Ok(watcher.remove(urls.as_deref()))
+}
@@ -0,0 +1,229 @@
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::fs;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use time::{format_description::well_known::Rfc3339, OffsetDateTime};
+
+const NETSCAPE_HEADER: &str = "# Netscape HTTP Cookie File";
+
+// 保存済みのCookieセット（サイトごとに1つ）
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CookieSet {
+    pub site: String,
+    pub cookie_count: usize,
+    pub domains: Vec<String>,
+    // yt-dlpが実行後にCookieを書き戻すため、取り込み日時ではなく最終更新日時
+    pub updated_at: Option<String>,
+}
+
+// Netscape形式のcookies.txtを取り込み、サイトごとのCookieストアに保存
+// siteを省略した場合はCookieのドメインから判定する
+#[tauri::command]
+pub async fn import_cookies(path: String, site: Option<String>) -> Result<CookieSet, String> {
+    log::info!(
+        "Invoked import_cookies with path: {:?}, site: {:?}",
+        path,
+        site
+    );
+    let content = fs::read_to_string(&path).map_err(|e| {
+        log::error!("Could not read cookies file: {}", e);
+        format!("Could not read cookies file: {}", e)
+    })?;
+
+    let lines = parse_cookie_lines(&content);
+    if lines.is_empty() {
+        log::error!("No cookies found in {:?}", path);
+        return Err(format!(
+            "No cookies found in {:?} (expected Netscape cookies.txt format)",
+            path
+        ));
+    }
+
+    let site = match site.as_deref().map(str::trim).filter(|s| !s.is_empty()) {
+        Some(site) => normalize_site(site),
+        None => most_common_site(&lines).ok_or_else(|| {
+            log::error!("Could not determine the site of the cookies");
+            "Could not determine the site of the cookies".to_string()
+        })?,
+    };
+
+    let file_path = cookie_file(&site)?;
+    let mut file = create_private_file(&file_path)?;
+    writeln!(file, "{}", NETSCAPE_HEADER)
+        .and_then(|_| lines.iter().try_for_each(|line| writeln!(file, "{}", line)))
+        .map_err(|e| {
+            log::error!("Failed to write cookies file: {}", e);
+            format!("Failed to write cookies file: {}", e)
+        })?;
+
+    log::info!("Imported {} cookies for {}", lines.len(), site);
+    read_cookie_set(&site, &file_path)
+}
+
+// 保存済みのCookieセット一覧
+#[tauri::command]
+pub async fn list_cookie_sets() -> Result<Vec<CookieSet>, String> {
+    let dir = cookies_dir()?;
+    let entries = fs::read_dir(&dir).map_err(|e| {
+        log::error!("Could not read cookies directory: {}", e);
+        format!("Could not read cookies directory: {}", e)
+    })?;
+
+    let mut sets = Vec::new();
+    for entry in entries.filter_map(|entry| entry.ok()) {
+        let path = entry.path();
+        if path.extension().and_then(|ext| ext.to_str()) != Some("txt") {
+            continue;
+        }
+        if let Some(site) = path.file_stem().and_then(|stem| stem.to_str()) {
+            sets.push(read_cookie_set(site, &path)?);
+        }
+    }
+    sets.sort_by(|a, b| a.site.cmp(&b.site));
+    Ok(sets)
+}
+
+#[tauri::command]
+pub async fn delete_cookie_set(site: String) -> Result<(), String> {
+    log::info!("Invoked delete_cookie_set with site: {:?}", site);
+    let path = cookie_file(&normalize_site(&site))?;
+    fs::remove_file(&path).map_err(|e| {
+        log::error!("Could not delete cookies for {}: {}", site, e);
+        format!("Could not delete cookies for {}: {}", site, e)
+    })
+}
+
+// ドメインに対応するCookieファイル（yt-dlpの--cookiesに渡す）
+pub fn cookies_for_domain(domain: &str) -> Option<PathBuf> {
+    let path = cookie_file(&normalize_site(domain)).ok()?;
+    path.is_file().then_some(path)
+}
+
+// Cookieファイルを所有者のみ読み書きできる権限にする
+// （yt-dlpが実行後にCookieを書き戻すため、実行のたびに呼び出す）
+pub fn restrict_permissions(path: &Path) {
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        if let Err(e) = fs::set_permissions(path, fs::Permissions::from_mode(0o600)) {
+            log::warn!("Failed to restrict permissions of {:?}: {}", path, e);
+        }
+    }
+    #[cfg(not(unix))]
+    let _ = path;
+}
+
+fn cookies_dir() -> Result<PathBuf, String> {
+    let dir = crate::paths::data_dir()?.join("cookies");
+    fs::create_dir_all(&dir).map_err(|e| {
+        log::error!("Could not create cookies directory: {}", e);
+        format!("Could not create cookies directory: {}", e)
+    })?;
+
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        fs::set_permissions(&dir, fs::Permissions::from_mode(0o700)).map_err(|e| {
+            log::error!("Failed to restrict permissions of cookies directory: {}", e);
+            format!("Failed to restrict permissions of cookies directory: {}", e)
+        })?;
+    }
+
+    Ok(dir)
+}
+
+fn cookie_file(site: &str) -> Result<PathBuf, String> {
+    if site.is_empty() || site.contains(['/', '\\']) || site.starts_with('.') {
+        return Err(format!("Invalid cookie site name: {:?}", site));
+    }
+    Ok(cookies_dir()?.join(format!("{}.txt", site)))
+}
+
+// 所有者のみ読み書きできる権限でファイルを作成
+pub fn create_private_file(path: &Path) -> Result<fs::File, String> {
+    let mut options = fs::OpenOptions::new();
+    options.write(true).create(true).truncate(true);
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::OpenOptionsExt;
+        options.mode(0o600);
+    }
+
+    let file = options.open(path).map_err(|e| {
+        log::error!("Could not create {}: {}", path.display(), e);
+        format!("Could not create {}: {}", path.display(), e)
+    })?;
+    restrict_permissions(path);
+    Ok(file)
+}
+
+// Netscape形式のCookie行（タブ区切り7項目）だけを取り出す
+fn parse_cookie_lines(content: &str) -> Vec<&str> {
+    content
+        .lines()
+        .map(|line| line.trim_end_matches('\r'))
+        .filter(|line| !line.trim().is_empty())
+        .filter(|line| !line.starts_with('#') || line.starts_with("#HttpOnly_"))
+        .filter(|line| line.split('\t').count() == 7)
+        .collect()
+}
+
+fn cookie_domain(line: &str) -> &str {
+    let domain = line.split('\t').next().unwrap_or_default();
+    domain
+        .trim_start_matches("#HttpOnly_")
+        .trim_start_matches('.')
+}
+
+// Cookieのドメインで最も多いサイト
+fn most_common_site(lines: &[&str]) -> Option<String> {
+    let mut counts: BTreeMap<String, usize> = BTreeMap::new();
+    for line in lines {
+        *counts
+            .entry(normalize_site(cookie_domain(line)))
+            .or_default() += 1;
+    }
+    counts
+        .into_iter()
+        .max_by_key(|(_, count)| *count)
+        .map(|(site, _)| site)
+}
+
+// youtube.com、https://www.youtube.com/などをyoutube.comにそろえる
+pub fn normalize_site(site: &str) -> String {
+    let site = site.trim().trim_start_matches('.');
+    if site.contains("://") {
+        crate::throttle::domain_of(site)
+    } else {
+        crate::throttle::domain_of(&format!("https://{}", site))
+    }
+}
+
+fn read_cookie_set(site: &str, path: &Path) -> Result<CookieSet, String> {
+    let content = fs::read_to_string(path).map_err(|e| {
+        log::error!("Could not read cookies for {}: {}", site, e);
+        format!("Could not read cookies for {}: {}", site, e)
+    })?;
+    let lines = parse_cookie_lines(&content);
+
+    let mut domains: Vec<String> = lines
+        .iter()
+        .map(|line| cookie_domain(line).to_string())
+        .collect();
+    domains.sort();
+    domains.dedup();
+
+    let updated_at = fs::metadata(path)
+        .and_then(|meta| meta.modified())
+        .ok()
+        .and_then(|modified| OffsetDateTime::from(modified).format(&Rfc3339).ok());
+
+    Ok(CookieSet {
+        site: site.to_string(),
+        cookie_count: lines.len(),
+        domains,
+        updated_at,
+    })
+}
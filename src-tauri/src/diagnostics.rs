@@ -0,0 +1,429 @@
+use serde::{Deserialize, Serialize};
+
+// yt-dlpのエラーの種類
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum ErrorKind {
+    BotCheck,
+    RateLimited,
+    FormatUnavailable,
+    LoginRequired,
+    Private,
+    GeoBlocked,
+    Unavailable,
+    Forbidden,
+    Timeout,
+    NetworkError,
+    FragmentError,
+    ExtractorOutdated,
+    FfmpegMissing,
+    DiskFull,
+    InvalidUrl,
+}
+
+// 対処方法
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum Remediation {
+    UseCookies,
+    SlowDown,
+    UpdateYtDlp,
+    ChangeFormat,
+    UseProxy,
+    CheckUrl,
+    Retry,
+    SetFfmpegLocation,
+    FreeDiskSpace,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum Severity {
+    Error,
+    Warning,
+}
+
+// yt-dlp-diagnosticイベントで送信する診断結果
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Diagnostic {
+    pub kind: ErrorKind,
+    pub severity: Severity,
+    pub remediations: Vec<Remediation>,
+    pub hint: String,
+    pub line: String,
+}
+
+// メッセージに含まれる文字列（小文字）とエラーの種類の対応（上から順に判定）
+const PATTERNS: &[(&str, ErrorKind)] = &[
+    ("confirm you're not a bot", ErrorKind::BotCheck),
+    ("confirm you\u{2019}re not a bot", ErrorKind::BotCheck),
+    ("http error 429", ErrorKind::RateLimited),
+    ("too many requests", ErrorKind::RateLimited),
+    ("rate-limit", ErrorKind::RateLimited),
+    ("rate limit", ErrorKind::RateLimited),
+    (
+        "requested format is not available",
+        ErrorKind::FormatUnavailable,
+    ),
+    ("private video", ErrorKind::Private),
+    ("video is private", ErrorKind::Private),
+    ("not available in your country", ErrorKind::GeoBlocked),
+    ("geo restrict", ErrorKind::GeoBlocked),
+    ("geo-restrict", ErrorKind::GeoBlocked),
+    ("confirm your age", ErrorKind::LoginRequired),
+    ("registered users", ErrorKind::LoginRequired),
+    ("members-only", ErrorKind::LoginRequired),
+    ("join this channel", ErrorKind::LoginRequired),
+    ("login required", ErrorKind::LoginRequired),
+    ("account credentials", ErrorKind::LoginRequired),
+    ("use --cookies", ErrorKind::LoginRequired),
+    ("no space left on device", ErrorKind::DiskFull),
+    ("errno 28", ErrorKind::DiskFull),
+    ("ffmpeg not found", ErrorKind::FfmpegMissing),
+    ("ffprobe and ffmpeg not found", ErrorKind::FfmpegMissing),
+    ("ffmpeg is not installed", ErrorKind::FfmpegMissing),
+    ("fragment", ErrorKind::FragmentError),
+    ("did not get any data blocks", ErrorKind::FragmentError),
+    ("timed out", ErrorKind::Timeout),
+    ("timeout", ErrorKind::Timeout),
+    ("connection reset", ErrorKind::NetworkError),
+    ("connection refused", ErrorKind::NetworkError),
+    (
+        "temporary failure in name resolution",
+        ErrorKind::NetworkError,
+    ),
+    ("getaddrinfo failed", ErrorKind::NetworkError),
+    ("network is unreachable", ErrorKind::NetworkError),
+    ("http error 403", ErrorKind::Forbidden),
+    ("video unavailable", ErrorKind::Unavailable),
+    ("has been removed", ErrorKind::Unavailable),
+    ("does not exist", ErrorKind::Unavailable),
+    ("http error 404", ErrorKind::Unavailable),
+    ("is not a valid url", ErrorKind::InvalidUrl),
+    ("unsupported url", ErrorKind::InvalidUrl),
+    ("signature extraction failed", ErrorKind::ExtractorOutdated),
+    ("nsig extraction failed", ErrorKind::ExtractorOutdated),
+    ("unable to extract", ErrorKind::ExtractorOutdated),
+    ("update to the latest version", ErrorKind::ExtractorOutdated),
+];
+
+impl ErrorKind {
+    pub fn remediations(self) -> Vec<Remediation> {
+        match self {
+            ErrorKind::BotCheck => vec![Remediation::UseCookies, Remediation::SlowDown],
+            ErrorKind::RateLimited => vec![Remediation::SlowDown, Remediation::Retry],
+            ErrorKind::FormatUnavailable => {
+                vec![Remediation::ChangeFormat, Remediation::UpdateYtDlp]
+            }
+            ErrorKind::LoginRequired | ErrorKind::Private => vec![Remediation::UseCookies],
+            ErrorKind::GeoBlocked => vec![Remediation::UseProxy],
+            ErrorKind::Unavailable | ErrorKind::InvalidUrl => vec![Remediation::CheckUrl],
+            ErrorKind::Forbidden => vec![Remediation::UpdateYtDlp, Remediation::UseCookies],
+            ErrorKind::Timeout | ErrorKind::NetworkError | ErrorKind::FragmentError => {
+                vec![Remediation::Retry, Remediation::SlowDown]
+            }
+            ErrorKind::ExtractorOutdated => vec![Remediation::UpdateYtDlp],
+            ErrorKind::FfmpegMissing => vec![Remediation::SetFfmpegLocation],
+            ErrorKind::DiskFull => vec![Remediation::FreeDiskSpace],
+        }
+    }
+
+    pub fn hint(self) -> &'static str {
+        match self {
+            ErrorKind::BotCheck => {
+                "The site suspects automated access. Import cookies from a logged-in browser and increase the sleep intervals."
+            }
+            ErrorKind::RateLimited => {
+                "Too many requests were sent. Wait a while and retry with larger --sleep-requests / --sleep-interval values."
+            }
+            ErrorKind::FormatUnavailable => {
+                "The selected format does not exist for this video. Use a more permissive -f selector such as \"bv*+ba/b\", or update yt-dlp."
+            }
+            ErrorKind::LoginRequired => {
+                "This video requires a logged-in account. Provide cookies for the site."
+            }
+            ErrorKind::Private => {
+                "This video is private. Only cookies from an account with access can download it."
+            }
+            ErrorKind::GeoBlocked => {
+                "This video is not available in your region. Try a proxy located in an allowed region."
+            }
+            ErrorKind::Unavailable => "The video has been removed or does not exist. Check the URL.",
+            ErrorKind::Forbidden => {
+                "The server refused the request (HTTP 403). Update yt-dlp; if it persists, provide cookies."
+            }
+            ErrorKind::Timeout => "The connection timed out. Retry later or slow down requests.",
+            ErrorKind::NetworkError => "A network error occurred. Check your connection and retry.",
+            ErrorKind::FragmentError => {
+                "Some fragments failed to download. Retry, or raise --fragment-retries."
+            }
+            ErrorKind::ExtractorOutdated => {
+                "The site has probably changed. Update yt-dlp to the latest version."
+            }
+            ErrorKind::FfmpegMissing => {
+                "ffmpeg is required for this operation. Set the ffmpeg directory in the settings."
+            }
+            ErrorKind::DiskFull => "The disk is full. Free up space or choose another output folder.",
+            ErrorKind::InvalidUrl => "The URL is not supported by yt-dlp. Check the URL.",
+        }
+    }
+}
+
+// yt-dlpの出力1行を分類（ERROR/WARNING行とダウンロード中の「Got error」行が対象）
+pub fn classify_line(line: &str) -> Option<Diagnostic> {
+    let trimmed = line.trim();
+    let (severity, message) = if let Some(rest) = trimmed.strip_prefix("ERROR:") {
+        (Severity::Error, rest)
+    } else if let Some(rest) = trimmed.strip_prefix("WARNING:") {
+        (Severity::Warning, rest)
+    } else if trimmed.contains("Got error:") {
+        (Severity::Warning, trimmed)
+    } else {
+        return None;
+    };
+
+    let kind = classify_message(message)?;
+    Some(Diagnostic {
+        kind,
+        severity,
+        remediations: kind.remediations(),
+        hint: kind.hint().to_string(),
+        line: trimmed.to_string(),
+    })
+}
+
+// エラーメッセージからエラーの種類を判別
+pub fn classify_message(message: &str) -> Option<ErrorKind> {
+    let message = message.to_lowercase();
+    PATTERNS
+        .iter()
+        .find(|(pattern, _)| message.contains(pattern))
+        .map(|(_, kind)| *kind)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // yt-dlpが実際に出力する行と、期待するエラーの種類
+    const FIXTURES: &[(&str, ErrorKind)] = &[
+        (
+            "ERROR: [youtube] dQw4w9WgXcQ: Sign in to confirm you're not a bot. Use --cookies-from-browser or --cookies for the authentication. See  https://github.com/yt-dlp/yt-dlp/wiki/FAQ#how-do-i-pass-cookies-to-yt-dlp  for how to manually pass cookies. Also see  https://github.com/yt-dlp/yt-dlp/wiki/Extractors#exporting-youtube-cookies  for tips on effectively exporting YouTube cookies",
+            ErrorKind::BotCheck,
+        ),
+        (
+            "ERROR: [youtube] dQw4w9WgXcQ: Sign in to confirm you\u{2019}re not a bot. This helps protect our community. Learn more",
+            ErrorKind::BotCheck,
+        ),
+        (
+            "ERROR: unable to download video data: HTTP Error 429: Too Many Requests",
+            ErrorKind::RateLimited,
+        ),
+        (
+            "ERROR: [twitch:stream] somechannel: Too many requests, please try again later",
+            ErrorKind::RateLimited,
+        ),
+        (
+            "ERROR: [Instagram] CxYz123: Requested content is not available, rate-limit reached or login required. Use --cookies, --cookies-from-browser, --username and --password, --netrc-cmd, or --netrc (instagram) to provide account credentials",
+            ErrorKind::RateLimited,
+        ),
+        (
+            "ERROR: [vimeo] 76979871: You have hit the rate limit for this resource, please wait",
+            ErrorKind::RateLimited,
+        ),
+        (
+            "ERROR: [youtube] dQw4w9WgXcQ: Requested format is not available. Use --list-formats for a list of available formats",
+            ErrorKind::FormatUnavailable,
+        ),
+        (
+            "ERROR: [youtube] dQw4w9WgXcQ: Private video. Sign in if you've been granted access to this video. Use --cookies-from-browser or --cookies for the authentication.",
+            ErrorKind::Private,
+        ),
+        (
+            "ERROR: [vimeo] 76979871: This video is private",
+            ErrorKind::Private,
+        ),
+        (
+            "ERROR: [BBC] p01234: This video is not available in your country.",
+            ErrorKind::GeoBlocked,
+        ),
+        (
+            "ERROR: [NRK] MUHH48000314: This video is not available from your location due to geo restriction",
+            ErrorKind::GeoBlocked,
+        ),
+        (
+            "ERROR: [Dailymotion] x8abcd: Video geo-restricted by the owner",
+            ErrorKind::GeoBlocked,
+        ),
+        (
+            "ERROR: [youtube] dQw4w9WgXcQ: Sign in to confirm your age. This video may be inappropriate for some users. Use --cookies-from-browser or --cookies for the authentication.",
+            ErrorKind::LoginRequired,
+        ),
+        (
+            "ERROR: [niconico] sm9: This video is only available for registered users",
+            ErrorKind::LoginRequired,
+        ),
+        (
+            "ERROR: [youtube] dQw4w9WgXcQ: This video is available to this channel's members on level: Member (or any higher level). Join this channel to get access to members-only content like this video, and other exclusive perks.",
+            ErrorKind::LoginRequired,
+        ),
+        (
+            "ERROR: [youtube] dQw4w9WgXcQ: Join this channel to get access to this video",
+            ErrorKind::LoginRequired,
+        ),
+        (
+            "ERROR: [vimeo] 76979871: Login required to access this video",
+            ErrorKind::LoginRequired,
+        ),
+        (
+            "ERROR: [twitter] 1234567890: Use --username and --password, --netrc-cmd, or --netrc (twitter) to provide account credentials",
+            ErrorKind::LoginRequired,
+        ),
+        (
+            "ERROR: [twitter] 1234567890: NSFW tweet requires authentication. Use --cookies, --cookies-from-browser, --username and --password, --netrc-cmd, or --netrc (twitter) to provide authentication",
+            ErrorKind::LoginRequired,
+        ),
+        (
+            "ERROR: Unable to write to file: [Errno 28] No space left on device",
+            ErrorKind::DiskFull,
+        ),
+        (
+            "ERROR: unable to write data: [Errno 28] There is not enough space on the disk",
+            ErrorKind::DiskFull,
+        ),
+        (
+            "WARNING: ffmpeg not found. The downloaded format may not be the best available. Installing ffmpeg is strongly recommended: https://github.com/yt-dlp/yt-dlp#dependencies",
+            ErrorKind::FfmpegMissing,
+        ),
+        (
+            "ERROR: Postprocessing: ffprobe and ffmpeg not found. Please install or provide the path using --ffmpeg-location",
+            ErrorKind::FfmpegMissing,
+        ),
+        (
+            "ERROR: You have requested merging of multiple formats but ffmpeg is not installed. Aborting due to --abort-on-error",
+            ErrorKind::FfmpegMissing,
+        ),
+        (
+            "[download] Got error: HTTP Error 503: Service Unavailable. Retrying fragment 12 (1/10)...",
+            ErrorKind::FragmentError,
+        ),
+        (
+            "ERROR: Did not get any data blocks",
+            ErrorKind::FragmentError,
+        ),
+        (
+            "ERROR: [youtube] dQw4w9WgXcQ: Unable to download API page: <urlopen error _ssl.c:990: The handshake operation timed out>",
+            ErrorKind::Timeout,
+        ),
+        (
+            "ERROR: unable to download video data: ('Connection aborted.', TimeoutError('read timeout'))",
+            ErrorKind::Timeout,
+        ),
+        (
+            "ERROR: unable to download video data: [Errno 104] Connection reset by peer",
+            ErrorKind::NetworkError,
+        ),
+        (
+            "ERROR: [generic] Unable to download webpage: <urlopen error [Errno 111] Connection refused>",
+            ErrorKind::NetworkError,
+        ),
+        (
+            "ERROR: [generic] Unable to download webpage: <urlopen error [Errno -3] Temporary failure in name resolution>",
+            ErrorKind::NetworkError,
+        ),
+        (
+            "ERROR: [generic] Unable to download webpage: <urlopen error [Errno 11001] getaddrinfo failed>",
+            ErrorKind::NetworkError,
+        ),
+        (
+            "ERROR: [generic] Unable to download webpage: <urlopen error [Errno 101] Network is unreachable>",
+            ErrorKind::NetworkError,
+        ),
+        (
+            "ERROR: unable to download video data: HTTP Error 403: Forbidden",
+            ErrorKind::Forbidden,
+        ),
+        (
+            "ERROR: [youtube] dQw4w9WgXcQ: Video unavailable. This video has been removed by the uploader",
+            ErrorKind::Unavailable,
+        ),
+        (
+            "ERROR: [vimeo] 76979871: This video has been removed due to a copyright claim",
+            ErrorKind::Unavailable,
+        ),
+        (
+            "ERROR: [niconico] sm0: The video does not exist",
+            ErrorKind::Unavailable,
+        ),
+        (
+            "ERROR: [generic] Unable to download webpage: HTTP Error 404: Not Found (caused by <HTTPError 404: Not Found>)",
+            ErrorKind::Unavailable,
+        ),
+        (
+            "ERROR: 'notaurl' is not a valid URL. Set --default-search \"ytsearch\" (or run  yt-dlp \"ytsearch:notaurl\" ) to search YouTube",
+            ErrorKind::InvalidUrl,
+        ),
+        (
+            "ERROR: Unsupported URL: https://example.com/page",
+            ErrorKind::InvalidUrl,
+        ),
+        (
+            "WARNING: [youtube] dQw4w9WgXcQ: Signature extraction failed: Some formats may be missing",
+            ErrorKind::ExtractorOutdated,
+        ),
+        (
+            "WARNING: [youtube] dQw4w9WgXcQ: nsig extraction failed: You may experience throttling for some formats",
+            ErrorKind::ExtractorOutdated,
+        ),
+        (
+            "ERROR: [youtube] dQw4w9WgXcQ: Unable to extract uploader id; please report this issue on  https://github.com/yt-dlp/yt-dlp/issues?q= , filling out the appropriate issue template. Confirm you are on the latest version using  yt-dlp -U",
+            ErrorKind::ExtractorOutdated,
+        ),
+        (
+            "WARNING: [youtube] dQw4w9WgXcQ: The extractor may be outdated. Please update to the latest version of yt-dlp",
+            ErrorKind::ExtractorOutdated,
+        ),
+    ];
+
+    #[test]
+    fn classifies_fixtures() {
+        for (line, kind) in FIXTURES {
+            let diagnostic =
+                classify_line(line).unwrap_or_else(|| panic!("{:?} was not classified", line));
+            assert_eq!(diagnostic.kind, *kind, "{:?}", line);
+            let severity = if line.starts_with("ERROR:") {
+                Severity::Error
+            } else {
+                Severity::Warning
+            };
+            assert_eq!(diagnostic.severity, severity, "{:?}", line);
+            assert_eq!(diagnostic.remediations, kind.remediations());
+        }
+    }
+
+    #[test]
+    fn every_pattern_has_a_fixture() {
+        for (pattern, kind) in PATTERNS {
+            assert!(
+                FIXTURES
+                    .iter()
+                    .any(|(line, k)| k == kind && line.to_lowercase().contains(pattern)),
+                "No fixture for {:?}",
+                pattern
+            );
+        }
+    }
+
+    #[test]
+    fn ignores_other_lines() {
+        for line in [
+            "[download]  45.0% of   10.00MiB at    1.00MiB/s ETA 00:05",
+            "[youtube] dQw4w9WgXcQ: Downloading webpage",
+            "[hlsnative] Total fragments: 120",
+            "ERROR: an error that is not known yet",
+        ] {
+            assert!(classify_line(line).is_none(), "{:?}", line);
+        }
+    }
+}
@@ -0,0 +1,384 @@
+use crate::engine::Context;
+use crate::{diagnostics, throttle};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+const MB: u64 = 1024 * 1024;
+
+// --pathsで指定できる種類（home以外は保存先ではない）
+const PATH_TYPES: &[&str] = &[
+    "home",
+    "temp",
+    "subtitle",
+    "thumbnail",
+    "description",
+    "annotation",
+    "infojson",
+    "link",
+    "pl_thumbnail",
+    "pl_description",
+    "pl_infojson",
+    "chapter",
+    "pl_video",
+];
+
+// サイズの推定に使うフォーマット選択のオプション（値を取るもの）
+const FORMAT_OPTIONS: &[&str] = &["-f", "--format", "-S", "--format-sort"];
+
+// 空き容量が足りないときの動作
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum DiskSpaceAction {
+    // 確認しない
+    Off,
+    // 通知だけして実行する
+    #[default]
+    Warn,
+    // 実行しない
+    Refuse,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct DiskSpaceConfig {
+    pub action: DiskSpaceAction,
+    // 推定サイズとは別に残しておく空き容量（MB）。実行中はこれを下回るとジョブを止める
+    pub reserve_mb: u64,
+    // 実行前に情報を取得してダウンロードサイズを推定するかどうか
+    // URLごとにyt-dlpを起動してサイトにアクセスするため既定では無効（残しておく容量だけを確認する）
+    pub estimate_size: bool,
+    // サイズを推定するために情報を取得するURLの上限
+    pub max_probe_urls: usize,
+    // 実行中に空き容量を確認する間隔（秒）
+    pub monitor_interval_secs: u64,
+}
+
+impl Default for DiskSpaceConfig {
+    fn default() -> Self {
+        DiskSpaceConfig {
+            action: DiskSpaceAction::default(),
+            reserve_mb: 1024,
+            estimate_size: false,
+            max_probe_urls: 50,
+            monitor_interval_secs: 10,
+        }
+    }
+}
+
+impl DiskSpaceConfig {
+    pub fn validate(&self) -> Result<(), String> {
+        if self.monitor_interval_secs == 0 {
+            return Err("diskSpace.monitorIntervalSecs must be at least 1".to_string());
+        }
+        Ok(())
+    }
+
+    pub fn reserve_bytes(&self) -> u64 {
+        self.reserve_mb.saturating_mul(MB)
+    }
+}
+
+// 実行前チェックの結果（yt-dlp-disk-checkイベントで送信）
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DiskCheck {
+    pub target_dir: String,
+    pub available_bytes: u64,
+    // 取得できたサイズの合計（サイズ不明の動画は含まない）
+    pub estimated_bytes: u64,
+    pub reserve_bytes: u64,
+    // サイズを取得できたURLの数
+    pub estimated: usize,
+    // サイズが不明、または推定しなかったURLの数
+    pub unknown: usize,
+    pub sufficient: bool,
+}
+
+// 実行中に空き容量が不足したときの通知（disk-space-lowイベントで送信）
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DiskSpaceLow {
+    pub job_id: String,
+    pub target_dir: String,
+    pub available_bytes: u64,
+    pub reserve_bytes: u64,
+}
+
+// 引数から保存先のディレクトリを決定（--pathsのhome、なければカレントディレクトリ）
+pub fn target_dir(args: &[String]) -> PathBuf {
+    let mut dir = None;
+    let mut iter = args.iter();
+    while let Some(arg) = iter.next() {
+        let value = match arg.as_str() {
+            "-P" | "--paths" => iter.next().map(String::as_str),
+            _ => arg.strip_prefix("--paths="),
+        };
+        let Some(value) = value else {
+            continue;
+        };
+        match value.split_once(':') {
+            Some(("home", path)) => dir = Some(path),
+            // 「C:\」のようなドライブ名は種類の指定ではない
+            Some((prefix, _)) if PATH_TYPES.contains(&prefix) => {}
+            _ => dir = Some(value),
+        }
+    }
+    match dir.filter(|dir| !dir.is_empty()) {
+        Some(dir) => PathBuf::from(dir),
+        None => std::env::current_dir().unwrap_or_default(),
+    }
+}
+
+// 保存先のボリュームの空き容量（まだ作成されていなければ存在する親ディレクトリで確認）
+pub fn available_space(dir: &Path) -> Result<u64, String> {
+    let existing = dir
+        .ancestors()
+        .find(|path| path.is_dir())
+        .unwrap_or(Path::new("."));
+    fs2::available_space(existing).map_err(|e| {
+        log::error!("Could not get free space of {:?}: {}", existing, e);
+        format!("Could not get free space of {:?}: {}", existing, e)
+    })
+}
+
+// URLごとに情報を取得してダウンロードサイズを推定し、空き容量と比較
+// 情報の取得はダウンロードと同じくドメインごとの実行枠・開始間隔に従う
+pub async fn check(
+    context: &Context<'_>,
+    urls: &[String],
+    options: &[String],
+    dir: &Path,
+    config: &DiskSpaceConfig,
+) -> Result<DiskCheck, String> {
+    let available_bytes = available_space(dir)?;
+
+    // 実際のダウンロードと同じフォーマットが選ばれるようにフォーマット選択のオプションを渡す
+    // プレイリストは各エントリの情報を取得しない（エントリ数だけアクセスが増えるため、サイズは不明とする）
+    let mut format_args = vec!["--flat-playlist".to_string()];
+    let mut iter = options.iter();
+    while let Some(option) = iter.next() {
+        if FORMAT_OPTIONS.contains(&option.as_str()) {
+            if let Some(value) = iter.next() {
+                format_args.push(option.clone());
+                format_args.push(value.clone());
+            }
+        } else if option == "--format-sort-force" || option == "--S-force" {
+            format_args.push(option.clone());
+        }
+    }
+
+    let probe_count = if config.estimate_size {
+        config.max_probe_urls
+    } else {
+        0
+    };
+    let throttle = context.jobs.throttle.clone();
+    let mut estimated_bytes = 0;
+    let mut estimated = 0;
+    for url in urls.iter().take(probe_count) {
+        let domain = throttle::domain_of(url);
+        let permit = throttle.acquire(&domain).await;
+        let probe_url = url.clone();
+        let probe_args = format_args.clone();
+        let result = tauri::async_runtime::spawn_blocking(move || {
+            let probe_args: Vec<&str> = probe_args.iter().map(String::as_str).collect();
+            crate::probe::probe(&probe_url, &probe_args)
+        })
+        .await
+        .map_err(|e| e.to_string())
+        .and_then(|result| result);
+        drop(permit);
+
+        let size = match result {
+            Ok(info) => estimate_size(&info),
+            Err(e) => {
+                log::warn!("Could not estimate download size of {}: {}", url, e);
+                if e.lines().any(|line| {
+                    diagnostics::classify_line(line).map(|diagnostic| diagnostic.kind)
+                        == Some(diagnostics::ErrorKind::RateLimited)
+                }) {
+                    throttle.cool_down(&domain);
+                }
+                None
+            }
+        };
+        if let Some(size) = size {
+            estimated_bytes += size;
+            estimated += 1;
+        }
+    }
+
+    let reserve_bytes = config.reserve_bytes();
+    Ok(DiskCheck {
+        target_dir: dir.to_string_lossy().to_string(),
+        available_bytes,
+        estimated_bytes,
+        reserve_bytes,
+        estimated,
+        unknown: urls.len() - estimated,
+        sufficient: estimated_bytes.saturating_add(reserve_bytes) <= available_bytes,
+    })
+}
+
+// yt-dlp -Jの結果からサイズを推定（選択されたフォーマットのfilesize、なければfilesize_approx）
+// プレイリストは各エントリの合計。1つでも不明なら不明とする
+fn estimate_size(info: &Value) -> Option<u64> {
+    if let Some(entries) = info.get("entries").and_then(Value::as_array) {
+        return entries
+            .iter()
+            .filter(|entry| !entry.is_null())
+            .map(estimate_size)
+            .sum();
+    }
+    match info.get("requested_formats").and_then(Value::as_array) {
+        Some(formats) => formats.iter().map(format_size).sum(),
+        None => format_size(info),
+    }
+}
+
+fn format_size(format: &Value) -> Option<u64> {
+    ["filesize", "filesize_approx"]
+        .iter()
+        .find_map(|key| format.get(key).and_then(Value::as_f64))
+        .map(|size| size as u64)
+}
+
+// 空き容量を確認し、残しておく容量を下回ったらジョブを止め、回復したら解除する
+// ユーザーの一時停止とは別の理由として記録し、ユーザーの再開では解除されないようにする
+fn update_hold(context: &Context<'_>, job_id: &str, dir: &Path, config: &DiskSpaceConfig) {
+    let Ok(available_bytes) = available_space(dir) else {
+        return;
+    };
+    let reserve_bytes = config.reserve_bytes();
+    let low = available_bytes < reserve_bytes;
+    if low == context.jobs.is_low_disk(job_id) {
+        return;
+    }
+    context.jobs.set_low_disk(job_id, low);
+    context.update_job(job_id, |job| job.low_disk_space = low);
+    if low {
+        log::warn!(
+            "Low disk space on {:?} ({} bytes left), pausing job {}",
+            dir,
+            available_bytes,
+            job_id
+        );
+        context.emit(
+            "disk-space-low",
+            DiskSpaceLow {
+                job_id: job_id.to_string(),
+                target_dir: dir.to_string_lossy().to_string(),
+                available_bytes,
+                reserve_bytes,
+            },
+        );
+    } else {
+        log::info!("Disk space recovered on {:?}, resuming job {}", dir, job_id);
+    }
+}
+
+// 間隔ごとに空き容量を確認し続ける（終了しない）
+// プロセスの実行や一時停止の待機と並行して動かし、止める・再開する判断はそちらに任せる
+pub async fn monitor(context: &Context<'_>, job_id: &str, dir: &Path, config: &DiskSpaceConfig) {
+    if config.action == DiskSpaceAction::Off {
+        return std::future::pending().await;
+    }
+    loop {
+        update_hold(context, job_id, dir, config);
+        tokio::time::sleep(Duration::from_secs(config.monitor_interval_secs)).await;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn target(args: &[&str]) -> PathBuf {
+        let args: Vec<String> = args.iter().map(|arg| arg.to_string()).collect();
+        target_dir(&args)
+    }
+
+    #[test]
+    fn target_dir_follows_home_paths() {
+        assert_eq!(target(&["-P", "/videos", "URL"]), PathBuf::from("/videos"));
+        assert_eq!(
+            target(&["--paths", "home:/videos", "--paths", "temp:/tmp/parts"]),
+            PathBuf::from("/videos")
+        );
+        assert_eq!(target(&["--paths=home:/videos"]), PathBuf::from("/videos"));
+        // 後の指定が優先
+        assert_eq!(
+            target(&["-P", "/old", "-P", "home:/new"]),
+            PathBuf::from("/new")
+        );
+    }
+
+    #[test]
+    fn target_dir_ignores_other_path_types() {
+        let current = std::env::current_dir().unwrap();
+        assert_eq!(target(&["--paths", "temp:/tmp/parts"]), current);
+        assert_eq!(target(&["-P", "thumbnail:/thumbs", "URL"]), current);
+        assert_eq!(target(&["-P", "home:"]), current);
+        assert_eq!(target(&["-P"]), current);
+        assert_eq!(target(&[]), current);
+    }
+
+    #[test]
+    fn target_dir_keeps_windows_drive_letters() {
+        assert_eq!(target(&["-P", r"C:\Videos"]), PathBuf::from(r"C:\Videos"));
+        assert_eq!(
+            target(&["--paths", r"home:D:\Videos\yt"]),
+            PathBuf::from(r"D:\Videos\yt")
+        );
+        assert_eq!(
+            target(&[r"--paths=C:\Videos", "--paths", r"temp:C:\Temp"]),
+            PathBuf::from(r"C:\Videos")
+        );
+    }
+
+    #[test]
+    fn size_uses_requested_formats() {
+        let info = json!({
+            "filesize": 1,
+            "requested_formats": [
+                { "format_id": "137", "filesize": 1000 },
+                { "format_id": "140", "filesize_approx": 200.7 },
+            ],
+        });
+        assert_eq!(estimate_size(&info), Some(1200));
+    }
+
+    #[test]
+    fn size_falls_back_to_approx() {
+        assert_eq!(estimate_size(&json!({ "filesize": 500 })), Some(500));
+        assert_eq!(
+            estimate_size(&json!({ "filesize": null, "filesize_approx": 300 })),
+            Some(300)
+        );
+        assert_eq!(estimate_size(&json!({ "format_id": "18" })), None);
+    }
+
+    #[test]
+    fn unknown_entries_make_the_playlist_unknown() {
+        let playlist = json!({
+            "entries": [
+                { "filesize": 100 },
+                null,
+                { "requested_formats": [{ "filesize": 50 }, { "filesize_approx": 25 }] },
+            ],
+        });
+        assert_eq!(estimate_size(&playlist), Some(175));
+
+        let playlist = json!({
+            "entries": [
+                { "filesize": 100 },
+                { "requested_formats": [{ "filesize": 50 }, { "format_id": "251" }] },
+            ],
+        });
+        assert_eq!(estimate_size(&playlist), None);
+        assert_eq!(estimate_size(&json!({ "entries": [] })), Some(0));
+    }
+}
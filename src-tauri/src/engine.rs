@@ -0,0 +1,1522 @@
+use crate::jobs::{self, JobManager};
+use crate::profiles::{self, ProfileStore};
+use crate::settings::SettingsStore;
+use crate::vault::{self, Vault};
+use crate::{
+    archive, bandwidth, cookies, diagnostics, disk, history, hooks, paths, redact, report, retry,
+    template, throttle, urls,
+};
+use chardetng::EncodingDetector;
+use serde::Serialize;
+use std::fs;
+use std::io::{Read, Write};
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tauri::{Emitter, Manager};
+use tauri_plugin_notification::NotificationExt;
+use time::{format_description::well_known::Rfc3339, OffsetDateTime};
+use tokio::sync::broadcast;
+
+// 実行中のプロセスの終了と中止の要求を確認する間隔
+const PROCESS_POLL_INTERVAL: Duration = Duration::from_millis(200);
+const JOB_CANCELLED: &str = "Job was cancelled";
+
+// ジョブのイベントの送信先（GUIではウィンドウ、ヘッドレスモードでは端末）
+pub trait EventSink: Send + Sync {
+    fn send(&self, event: &str, payload: serde_json::Value);
+
+    // デスクトップ通知（表示先がなければ何もしない）
+    fn notify(&self, _title: &str, _body: &str) {}
+}
+
+impl EventSink for tauri::Window {
+    fn send(&self, event: &str, payload: serde_json::Value) {
+        let _ = self.emit(event, payload);
+    }
+
+    fn notify(&self, title: &str, body: &str) {
+        if let Err(e) = self.notification().builder().title(title).body(body).show() {
+            log::warn!("Failed to show notification: {}", e);
+        }
+    }
+}
+
+// ウィンドウを閉じている・隠している間の実行用（全てのウィンドウに送信）
+impl EventSink for tauri::AppHandle {
+    fn send(&self, event: &str, payload: serde_json::Value) {
+        let _ = self.emit(event, payload);
+    }
+
+    fn notify(&self, title: &str, body: &str) {
+        if let Err(e) = self.notification().builder().title(title).body(body).show() {
+            log::warn!("Failed to show notification: {}", e);
+        }
+    }
+}
+
+// yt-dlpの実行に使う状態とイベントの送信先
+// GUIではTauriが管理する状態、ヘッドレスモードでは起動時に読み込んだ状態を参照する
+#[derive(Clone)]
+pub struct Context<'a> {
+    pub events: Arc<dyn EventSink>,
+    pub settings: &'a SettingsStore,
+    pub jobs: &'a JobManager,
+    pub profiles: &'a ProfileStore,
+    pub vault: &'a Vault,
+}
+
+impl<'a> Context<'a> {
+    pub fn from_window(window: &'a tauri::Window) -> Self {
+        Context {
+            events: Arc::new(window.clone()),
+            settings: window.state::<SettingsStore>().inner(),
+            jobs: window.state::<JobManager>().inner(),
+            profiles: window.state::<ProfileStore>().inner(),
+            vault: window.state::<Vault>().inner(),
+        }
+    }
+
+    // メインウィンドウがなくても実行できる（バックグラウンドの同期・スケジュール実行用）
+    pub fn from_app(app: &'a tauri::AppHandle) -> Self {
+        Context {
+            events: Arc::new(app.clone()),
+            settings: app.state::<SettingsStore>().inner(),
+            jobs: app.state::<JobManager>().inner(),
+            profiles: app.state::<ProfileStore>().inner(),
+            vault: app.state::<Vault>().inner(),
+        }
+    }
+
+    // ジョブのイベントをジョブIDを付けてJobManagerの購読者（ローカルAPI）にも配信する
+    pub fn for_job(&self, job_id: &str) -> Context<'a> {
+        Context {
+            events: Arc::new(JobEventSink {
+                job_id: job_id.to_string(),
+                inner: self.events.clone(),
+                sender: self.jobs.events.clone(),
+            }),
+            ..self.clone()
+        }
+    }
+
+    // 秘密の値を伏せて送信
+    pub fn emit<S: Serialize>(&self, event: &str, payload: S) {
+        redact::emit(self.events.as_ref(), event, payload);
+    }
+
+    // ジョブの情報を更新し、job-updatedイベントで通知
+    pub fn update_job<F>(&self, id: &str, f: F)
+    where
+        F: FnOnce(&mut jobs::JobInfo),
+    {
+        jobs::update_and_emit(self.jobs, self.events.as_ref(), id, f);
+    }
+}
+
+struct JobEventSink {
+    job_id: String,
+    inner: Arc<dyn EventSink>,
+    sender: broadcast::Sender<jobs::JobEvent>,
+}
+
+impl EventSink for JobEventSink {
+    fn send(&self, event: &str, payload: serde_json::Value) {
+        if self.sender.receiver_count() > 0 {
+            let _ = self.sender.send(jobs::JobEvent {
+                job_id: self.job_id.clone(),
+                event: event.to_string(),
+                payload: payload.clone(),
+            });
+        }
+        self.inner.send(event, payload);
+    }
+
+    fn notify(&self, title: &str, body: &str) {
+        self.inner.notify(title, body);
+    }
+}
+
+// バックグラウンドでyt-dlpを実行し、ジョブIDをすぐにリターン（ローカルAPIのキュー登録用）
+// メインウィンドウが閉じていても実行し、実行前に失敗した場合もジョブの一覧に残す
+pub fn spawn_job(
+    app: &tauri::AppHandle,
+    args: Vec<String>,
+    profile: Option<String>,
+) -> Result<String, String> {
+    let id = history::new_entry_id();
+    let (urls, _) = split_url_args(&args);
+    let mut domains: Vec<String> = urls.iter().map(|url| throttle::domain_of(url)).collect();
+    domains.sort();
+    domains.dedup();
+    {
+        let context = Context::from_app(app).for_job(&id);
+        context.jobs.register(jobs::JobInfo {
+            id: id.clone(),
+            profile: profile.clone(),
+            url_count: urls.len(),
+            domains,
+            status: jobs::JobStatus::Waiting,
+            current_domain: None,
+            attempt: 1,
+            started_at: history::now_rfc3339(),
+            finished_at: None,
+            error: None,
+            pause_mode: None,
+            low_disk_space: false,
+        });
+        context.update_job(&id, |_| {});
+    }
+
+    let job_id = id.clone();
+    let app = app.clone();
+    tauri::async_runtime::spawn(async move {
+        let context = Context::from_app(&app);
+        let retry_policy = context.settings.get().retry_policy;
+        if let Err(e) = execute_job(&context, job_id.clone(), args, profile, retry_policy).await {
+            log::error!("Job {} failed: {}", job_id, e);
+            // 実行前の検証で失敗した場合は待機中のまま残っているため終了させる
+            context.for_job(&job_id).update_job(&job_id, |job| {
+                if !job.status.is_finished() {
+                    job.status = jobs::JobStatus::Failed;
+                    job.finished_at = Some(history::now_rfc3339());
+                    job.error = Some(e);
+                }
+            });
+        }
+    });
+    Ok(id)
+}
+
+// 貼り付けられたテキストからURLを取り出し、正規化・重複除去してファイルに書き込み
+pub fn write_url_list(text: &str) -> Result<PathBuf, String> {
+    let parsed = urls::parse_url_list(text);
+    for invalid in &parsed.invalid {
+        log::warn!(
+            "Ignoring invalid line {}: {:?} ({})",
+            invalid.line,
+            invalid.text,
+            invalid.reason
+        );
+    }
+    if !parsed.duplicates.is_empty() {
+        log::info!("Removed {} duplicate URLs", parsed.duplicates.len());
+    }
+
+    if parsed.urls.is_empty() {
+        log::error!("No valid URLs provided");
+        return Err("No valid URLs provided".to_string());
+    }
+
+    save_url_list(&parsed.urls)
+}
+
+// URLリストをyt-dlpディレクトリのurl-list.txtに書き込む
+pub fn save_url_list<S: AsRef<str>>(urls: &[S]) -> Result<PathBuf, String> {
+    // キャッシュディレクトリの固定のファイル名を使用（上書き）
+    let urls_file = paths::cache_dir()?.join("url-list.txt");
+
+    let mut file = fs::File::create(&urls_file).map_err(|e| {
+        log::error!("Could not create URLs file: {}", e);
+        format!("Could not create URLs file: {}", e)
+    })?;
+
+    for url in urls {
+        writeln!(file, "{}", url.as_ref()).map_err(|e| {
+            log::error!("Failed to write URL to file: {}", e);
+            format!("Failed to write URL to file: {}", e)
+        })?;
+    }
+
+    log::info!(
+        "URLs file created/updated: {:?} with {} URLs",
+        urls_file,
+        urls.len()
+    );
+
+    Ok(urls_file)
+}
+
+// 最新のyt-dlpをダウンロード（1時間以内に確認済みなら確認しない）
+pub async fn update_yt_dlp() -> Result<String, String> {
+    // アプリのデータディレクトリに保存
+    let save_dir = paths::bin_dir()?;
+    let asset_name = match std::env::consts::OS {
+        "windows" => "yt-dlp.exe",
+        "macos" => "yt-dlp_macos",
+        "linux" => "yt-dlp_linux",
+        other => {
+            log::error!("Unsupported OS: {}", other);
+            return Err(format!("Unsupported OS: {}", other));
+        }
+    };
+    let yt_dlp_file = save_dir.join(asset_name);
+    let release_time_file = save_dir.join("release-time.txt");
+    let last_check_file = save_dir.join("last-check-time.txt");
+
+    // 最後の確認時間をチェック
+    let last_check_time = fs::read_to_string(&last_check_file)
+        .ok()
+        .and_then(|s| OffsetDateTime::parse(&s.trim(), &Rfc3339).ok());
+
+    if let Some(last_check) = last_check_time {
+        let now = OffsetDateTime::now_utc();
+        let one_hour = time::Duration::hours(1);
+
+        if now - last_check < one_hour {
+            log::info!("Last check was less than 1 hour ago, skipping server check");
+            return Ok("yt-dlp is up to date (last checked less than 1 hour ago)".to_string());
+        }
+    }
+
+    // 既存のyt-dlpのバージョンを確認
+    let yt_dlp_version_output = {
+        let mut cmd = Command::new(&yt_dlp_file);
+        #[cfg(windows)] // window hideのための設定
+        {
+            use std::os::windows::process::CommandExt;
+            cmd.creation_flags(0x08000000);
+        }
+        match cmd.arg("--version").output() {
+            Ok(output) => {
+                log::info!(
+                    "Successfully retrieved existing yt-dlp version: {:?}",
+                    output
+                );
+                Some(output)
+            }
+            Err(e) => {
+                log::warn!("Could not get existing yt-dlp version: {}", e);
+                None
+            }
+        }
+    };
+
+    // HTTPクライアントの初期化
+    let client = reqwest::Client::new();
+
+    // GitHub APIから最新のリリース時間を取得
+    let api_url = "https://api.github.com/repos/yt-dlp/yt-dlp-nightly-builds/releases/latest";
+    let response = client
+        .get(api_url)
+        .header("User-Agent", "TakumiVidDl")
+        .send()
+        .await
+        .map_err(|e| {
+            log::error!("Failed to fetch release info from GitHub: {}", e);
+            format!("Failed to fetch release info from GitHub: {}", e)
+        })?;
+    response.error_for_status_ref().map_err(|e| {
+        log::error!("GitHub API returned error status: {}", e);
+        e.to_string()
+    })?;
+    let release_info: serde_json::Value = response.json().await.map_err(|e| {
+        log::error!("Failed to parse GitHub API JSON: {}", e);
+        e.to_string()
+    })?;
+    let github_latest_release_time = match release_info.get("published_at") {
+        Some(val) => val,
+        None => {
+            log::error!("Could not find published_at in release info");
+            return Err("Could not find published_at in release info".to_string());
+        }
+    };
+
+    // local_binary_release_timeとgithub_latest_release_timeを比較して更新が必要か確認
+    let local_binary_release_time = fs::read_to_string(&release_time_file).ok();
+    let local = local_binary_release_time
+        .as_deref()
+        .and_then(|s| OffsetDateTime::parse(s.trim(), &Rfc3339).ok());
+    let github = github_latest_release_time
+        .as_str()
+        .and_then(|s| OffsetDateTime::parse(s, &Rfc3339).ok());
+    if let (Some(local), Some(github)) = (local, github) {
+        if local >= github && yt_dlp_version_output.is_some() {
+            log::info!("yt-dlp is already up to date");
+
+            // サーバーチェック完了時刻を記録（最新版確認済み）
+            let current_time = OffsetDateTime::now_utc();
+            let current_time_str = current_time.format(&Rfc3339).map_err(|e| {
+                log::error!("Failed to format current time: {}", e);
+                format!("Failed to format current time: {}", e)
+            })?;
+
+            let mut check_file = fs::File::create(&last_check_file).map_err(|e| {
+                log::error!("Could not create last-check-time.txt: {}", e);
+                format!("Could not create last-check-time.txt: {}", e)
+            })?;
+            check_file
+                .write_all(current_time_str.as_bytes())
+                .map_err(|e| {
+                    log::error!("Failed to write last-check-time.txt: {}", e);
+                    format!("Failed to write last-check-time.txt: {}", e)
+                })?;
+
+            return Ok("yt-dlp is already up to date.".to_string());
+        }
+    }
+
+    // 最新のyt-dlpダウンロードURLを取得
+    let assets = release_info
+        .get("assets")
+        .and_then(|a| a.as_array())
+        .ok_or_else(|| {
+            log::error!("No assets found in release info");
+            "No assets found in release info".to_string()
+        })?;
+    let mut download_url: Option<String> = None;
+    for asset in assets {
+        if asset.get("name").and_then(|n| n.as_str()) == Some(asset_name) {
+            download_url = asset
+                .get("browser_download_url")
+                .and_then(|u| u.as_str())
+                .map(|s| s.to_string());
+            break;
+        }
+    }
+    let download_url = download_url.ok_or_else(|| {
+        log::error!("Asset not found: {}", asset_name);
+        format!("Asset not found: {}", asset_name)
+    })?;
+    log::info!("yt-dlp download URL: {}", download_url);
+
+    // yt-dlpのダウンロード
+    let mut resp = client
+        .get(&download_url)
+        .header("User-Agent", "TakumiVidDl")
+        .send()
+        .await
+        .map_err(|e| {
+            log::error!("Failed to download yt-dlp: {}", e);
+            format!("Failed to download yt-dlp: {}", e)
+        })?;
+    let mut out = fs::File::create(&yt_dlp_file).map_err(|e| {
+        log::error!("Could not create yt-dlp file: {}", e);
+        format!("Could not create yt-dlp file: {}", e)
+    })?;
+    while let Some(chunk) = resp.chunk().await.map_err(|e| {
+        log::error!("Failed to read download chunk: {}", e);
+        format!("Failed to read download chunk: {}", e)
+    })? {
+        out.write_all(&chunk).map_err(|e| {
+            log::error!("Failed to write yt-dlp file: {}", e);
+            format!("Failed to write yt-dlp file: {}", e)
+        })?;
+    }
+
+    // ダウンロードが完了したら、実行権限を付与（Linux/Macのみ）
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        let perms = fs::Permissions::from_mode(0o755);
+        fs::set_permissions(&yt_dlp_file, perms).map_err(|e| {
+            log::error!("Failed to set execute permission: {}", e);
+            format!("Failed to set execute permission: {}", e)
+        })?;
+    }
+
+    // release-time.txtにpublished_atを書き込む
+    let time = github_latest_release_time.as_str().ok_or_else(|| {
+        log::error!("published_at is not a string");
+        "published_at is not a string".to_string()
+    })?;
+    let mut file = fs::File::create(&release_time_file).map_err(|e| {
+        log::error!("Could not create release-time.txt: {}", e);
+        format!("Could not create release-time.txt: {}", e)
+    })?;
+    file.write_all(time.as_bytes()).map_err(|e| {
+        log::error!("Failed to write release-time.txt: {}", e);
+        format!("Failed to write release-time.txt: {}", e)
+    })?;
+
+    // 全処理が成功した場合のみlast-check-time.txtに現在時刻を書き込み
+    let current_time = OffsetDateTime::now_utc();
+    let current_time_str = current_time.format(&Rfc3339).map_err(|e| {
+        log::error!("Failed to format current time: {}", e);
+        format!("Failed to format current time: {}", e)
+    })?;
+
+    let mut check_file = fs::File::create(&last_check_file).map_err(|e| {
+        log::error!("Could not create last-check-time.txt: {}", e);
+        format!("Could not create last-check-time.txt: {}", e)
+    })?;
+    check_file
+        .write_all(current_time_str.as_bytes())
+        .map_err(|e| {
+            log::error!("Failed to write last-check-time.txt: {}", e);
+            format!("Failed to write last-check-time.txt: {}", e)
+        })?;
+
+    log::info!("yt-dlp download completed: {:?}", yt_dlp_file);
+    Ok(format!("yt-dlp downloaded successfully: {:?}", yt_dlp_file))
+}
+
+// yt-dlpを実行し、結果を履歴に記録する（run_yt_dlpと履歴からの再実行で共用）
+// 一時的な理由で失敗したURLはリトライポリシーに従って再実行し、
+// プロセスが起動できれば、URLごとの結果をまとめたレポートを返す
+pub async fn execute_yt_dlp(
+    context: &Context<'_>,
+    args: Vec<String>,
+    profile: Option<String>,
+    retry_policy: retry::RetryPolicy,
+) -> Result<report::BatchReport, String> {
+    execute_job(
+        context,
+        history::new_entry_id(),
+        args,
+        profile,
+        retry_policy,
+    )
+    .await
+}
+
+// ジョブIDを指定してyt-dlpを実行（IDは履歴のIDにもなる）
+// 終了後、設定されたフック（通知・Webhook・スクリプト）を実行してからリターン
+pub async fn execute_job(
+    context: &Context<'_>,
+    history_id: String,
+    args: Vec<String>,
+    profile: Option<String>,
+    retry_policy: retry::RetryPolicy,
+) -> Result<report::BatchReport, String> {
+    let context = &context.for_job(&history_id);
+    let (urls, options) = split_url_args(&args);
+    let result = run_job(
+        context,
+        history_id.clone(),
+        args,
+        profile.clone(),
+        retry_policy,
+    )
+    .await;
+    if let Err(e) = &result {
+        record_unfinished(context, &history_id, &urls, &options, profile.clone(), e);
+    }
+    let job_result = hooks::JobResult::new(
+        &history_id,
+        context.jobs.get(&history_id),
+        profile,
+        urls,
+        &result,
+    );
+    hooks::run(context, &job_result).await;
+    result
+}
+
+// 各動画の情報を--print-to-fileで書き出すファイル
+fn items_file(history_id: &str) -> Result<PathBuf, String> {
+    Ok(paths::cache_dir()?.join(format!("history-items-{}.jsonl", history_id)))
+}
+
+// 結果の集計まで進まずに終了したジョブ（実行できなかった・中止された）を履歴に記録
+// それまでに完了した動画があれば含める
+fn record_unfinished(
+    context: &Context<'_>,
+    history_id: &str,
+    urls: &[String],
+    options: &[String],
+    profile: Option<String>,
+    error: &str,
+) {
+    let items = match items_file(history_id) {
+        Ok(items_file) => {
+            let items = history::read_items_file(&items_file);
+            let _ = fs::remove_file(&items_file);
+            items
+        }
+        Err(_) => Vec::new(),
+    };
+    let job = context.jobs.get(history_id);
+    let entry = history::HistoryEntry {
+        id: history_id.to_string(),
+        urls: urls.to_vec(),
+        profile,
+        items,
+        options: redact::redact_args(options),
+        started_at: job
+            .as_ref()
+            .map(|job| job.started_at.clone())
+            .unwrap_or_else(history::now_rfc3339),
+        finished_at: history::now_rfc3339(),
+        exit_code: None,
+        status: if job.is_some_and(|job| job.status == jobs::JobStatus::Cancelled) {
+            history::HistoryStatus::Cancelled
+        } else {
+            history::HistoryStatus::Failed
+        },
+        error_summary: Some(redact::redact(error)),
+    };
+    if let Err(e) = history::append_entry(&entry) {
+        log::warn!("Failed to record download history: {}", e);
+    }
+}
+
+async fn run_job(
+    context: &Context<'_>,
+    history_id: String,
+    args: Vec<String>,
+    profile: Option<String>,
+    retry_policy: retry::RetryPolicy,
+) -> Result<report::BatchReport, String> {
+    retry_policy.validate()?;
+
+    // yt-dlpのパスを決定
+    let yt_dlp_path = paths::yt_dlp_path()?;
+    log::info!("Using yt-dlp path: {:?}", yt_dlp_path);
+
+    // 履歴用にURLとオプションを分離し、各動画の情報を書き出すファイルを用意
+    let (urls, options) = split_url_args(&args);
+    let items_file = items_file(&history_id)?;
+
+    // 登録済みのプロファイルの引数をユーザーのオプションより前に付与（履歴にはユーザーのオプションだけを記録）
+    let profile_args = profiles::profile_args(context.profiles, profile.as_deref());
+    let run_args: Vec<String> = profile_args.iter().chain(&args).cloned().collect();
+    let run_options: Vec<String> = profile_args.iter().chain(&options).cloned().collect();
+
+    // 出力テンプレートを実行前に検証（エラーがあれば実行しない）
+    if let Some(output_template) = template::output_template(&run_args) {
+        let check = template::check(output_template);
+        for issue in &check.issues {
+            log::warn!("Output template issue: {}", issue.message);
+        }
+        if !check.valid {
+            let messages: Vec<&str> = check
+                .issues
+                .iter()
+                .filter(|issue| issue.severity == diagnostics::Severity::Error)
+                .map(|issue| issue.message.as_str())
+                .collect();
+            log::error!("Invalid output template: {}", messages.join("; "));
+            return Err(format!("Invalid output template: {}", messages.join("; ")));
+        }
+        if !check.issues.is_empty() {
+            context.emit("yt-dlp-template-check", &check);
+        }
+    }
+
+    // 初回・リトライ共通でアプリが付与する引数
+    let mut extra_args = Vec::new();
+    // アーカイブ済みでスキップされるURL（空き容量の推定から除く）
+    let mut archived_urls = Vec::new();
+
+    // プロファイルごとのダウンロードアーカイブを自動で指定（ユーザー指定があればそちらを優先）
+    let has_archive_option = run_args
+        .iter()
+        .any(|arg| arg.starts_with("--download-archive") || arg == "--no-download-archive");
+    if !has_archive_option {
+        let archive_file = archive::archive_path(profile.as_deref())?;
+        extra_args.push("--download-archive".to_string());
+        extra_args.push(archive_file.to_string_lossy().to_string());
+
+        // 実行前にアーカイブと照合し、スキップされる件数を通知
+        match archive::check_urls(profile.as_deref(), &urls) {
+            Ok(check) => {
+                log::info!(
+                    "Archive check: {} of {} URLs already downloaded ({} unknown)",
+                    check.skipped,
+                    check.total,
+                    check.unknown
+                );
+                context.emit("yt-dlp-archive-check", &check);
+                archived_urls = check.skipped_urls;
+            }
+            Err(e) => log::warn!("Failed to check download archive: {}", e),
+        }
+    }
+
+    // 中間ファイルはキャッシュディレクトリに置く（ユーザー指定があればそちらを優先）
+    if !run_args
+        .windows(2)
+        .any(|pair| matches!(pair[0].as_str(), "--paths" | "-P") && pair[1].starts_with("temp:"))
+        && !run_args.iter().any(|arg| arg.starts_with("--paths=temp:"))
+    {
+        extra_args.push("--paths".to_string());
+        extra_args.push(format!(
+            "temp:{}",
+            paths::cache_dir()?.join("tmp").to_string_lossy()
+        ));
+    }
+
+    // 保存先の空き容量が推定サイズ（推定しない場合は残しておく容量）に足りるか確認（設定に応じて警告または中止）
+    let disk_config = context.settings.get().disk_space;
+    let disk_dir = disk::target_dir(&run_args);
+    if disk_config.action != disk::DiskSpaceAction::Off {
+        let pending_urls: Vec<String> = urls
+            .iter()
+            .filter(|url| !archived_urls.contains(url))
+            .cloned()
+            .collect();
+        match disk::check(
+            context,
+            &pending_urls,
+            &run_options,
+            &disk_dir,
+            &disk_config,
+        )
+        .await
+        {
+            Ok(check) => {
+                log::info!(
+                    "Disk check: {} bytes estimated ({} unknown), {} bytes available on {:?}",
+                    check.estimated_bytes,
+                    check.unknown,
+                    check.available_bytes,
+                    disk_dir
+                );
+                context.emit("yt-dlp-disk-check", &check);
+                if !check.sufficient {
+                    if disk_config.action == disk::DiskSpaceAction::Refuse {
+                        log::error!(
+                            "Not enough disk space on {:?}: {} bytes needed, {} bytes available",
+                            disk_dir,
+                            check.estimated_bytes + check.reserve_bytes,
+                            check.available_bytes
+                        );
+                        return Err(format!(
+                            "Not enough disk space on {:?}: {} bytes needed, {} bytes available",
+                            disk_dir,
+                            check.estimated_bytes + check.reserve_bytes,
+                            check.available_bytes
+                        ));
+                    }
+                    log::warn!("Disk space may run out on {:?}", disk_dir);
+                }
+            }
+            Err(e) => log::warn!("Failed to check disk space: {}", e),
+        }
+    }
+
+    extra_args.push("--print-to-file".to_string());
+    extra_args.push(history::ITEM_PRINT_TEMPLATE.to_string());
+    // 出力テンプレートとして解釈されるため%をエスケープ
+    extra_args.push(items_file.to_string_lossy().replace('%', "%%"));
+    let started_at = history::now_rfc3339();
+
+    // ジョブとして登録
+    let mut domains: Vec<String> = urls.iter().map(|url| throttle::domain_of(url)).collect();
+    domains.sort();
+    domains.dedup();
+    context.jobs.register(jobs::JobInfo {
+        id: history_id.clone(),
+        profile: profile.clone(),
+        url_count: urls.len(),
+        domains,
+        status: jobs::JobStatus::Running,
+        current_domain: None,
+        attempt: 1,
+        started_at: started_at.clone(),
+        finished_at: None,
+        error: None,
+        pause_mode: None,
+        low_disk_space: false,
+    });
+    context.update_job(&history_id, |_| {});
+
+    // 開始通知（実行するコマンドラインを秘密の値を伏せて送る）
+    let display_args = redact::redact_args(&run_args);
+    let display_command =
+        shlex::try_join(display_args.iter().map(String::as_str)).unwrap_or_default();
+    context.emit("yt-dlp-started", display_command);
+
+    let job = JobRun {
+        id: &history_id,
+        yt_dlp_path: &yt_dlp_path,
+        options: &run_options,
+        extra_args: &extra_args,
+        disk_dir: &disk_dir,
+        disk_config: &disk_config,
+        context,
+    };
+    let (report, stderr_lines) = match job.run_with_retries(&run_args, &urls, &retry_policy).await {
+        Ok(result) => result,
+        Err(e) => {
+            let status = if context.jobs.is_cancelled(&history_id) {
+                jobs::JobStatus::Cancelled
+            } else {
+                jobs::JobStatus::Failed
+            };
+            context.update_job(&history_id, |job| {
+                job.status = status;
+                job.current_domain = None;
+                job.finished_at = Some(history::now_rfc3339());
+                job.error = Some(e.clone());
+            });
+            return Err(e);
+        }
+    };
+
+    // 履歴に記録（失敗してもダウンロード結果には影響させない）
+    let entry = history::HistoryEntry {
+        id: history_id,
+        urls,
+        profile,
+        items: history::read_items_file(&items_file),
+        options: redact::redact_args(&options),
+        started_at,
+        finished_at: history::now_rfc3339(),
+        exit_code: report.exit_code,
+        status: if report.success {
+            history::HistoryStatus::Success
+        } else {
+            history::HistoryStatus::Failed
+        },
+        error_summary: history::summarize_errors(&stderr_lines),
+    };
+    if let Err(e) = history::append_entry(&entry) {
+        log::warn!("Failed to record download history: {}", e);
+    }
+    let _ = fs::remove_file(&items_file);
+
+    log::info!("Batch report summary: {:?}", report.summary);
+    context.emit("yt-dlp-report", &report);
+    context.update_job(&entry.id, |job| {
+        job.status = if report.success {
+            jobs::JobStatus::Completed
+        } else {
+            jobs::JobStatus::Failed
+        };
+        job.current_domain = None;
+        job.finished_at = Some(entry.finished_at.clone());
+    });
+
+    if report.success {
+        log::info!("yt-dlp executed successfully");
+        context.emit("yt-dlp-completed", "success");
+    } else {
+        log::error!("yt-dlp failed with exit code: {:?}", report.exit_code);
+        context.emit("yt-dlp-completed", "failed");
+    }
+    Ok(report)
+}
+
+// 1つのジョブの実行に必要な情報（初回とリトライで共用）
+struct JobRun<'a> {
+    id: &'a str,
+    yt_dlp_path: &'a Path,
+    // URL指定を除いたユーザーのオプション
+    options: &'a [String],
+    // アプリが付与する引数（アーカイブ、履歴用の出力など）
+    extra_args: &'a [String],
+    // 空き容量を監視する保存先
+    disk_dir: &'a Path,
+    disk_config: &'a disk::DiskSpaceConfig,
+    context: &'a Context<'a>,
+}
+
+impl JobRun<'_> {
+    // 初回の実行後、一時的な理由で失敗したURLだけを再実行
+    async fn run_with_retries(
+        &self,
+        args: &[String],
+        urls: &[String],
+        retry_policy: &retry::RetryPolicy,
+    ) -> Result<(report::BatchReport, Vec<String>), String> {
+        let (mut report, mut stderr_lines) = if urls.is_empty() {
+            // URLを特定できない場合は指定されたオプションのまま実行
+            let mut run_args = args.to_vec();
+            run_args.extend(self.extra_args.iter().cloned());
+            self.with_disk_monitor(run_process(
+                self.yt_dlp_path,
+                &run_args,
+                urls,
+                "unknown",
+                self.context,
+                self.id,
+                None,
+            ))
+            .await?
+        } else {
+            self.run_by_domain(urls).await?
+        };
+
+        for attempt in 2..=retry_policy.max_attempts {
+            let retry_urls: Vec<String> = report
+                .results
+                .iter()
+                .filter(|result| {
+                    result.outcome.is_failure() && retry_policy.should_retry(result.error_kind)
+                })
+                .map(|result| result.url.clone())
+                .collect();
+            if retry_urls.is_empty() {
+                break;
+            }
+
+            let delay = retry_policy.backoff(attempt - 1);
+            log::info!(
+                "Retrying {} failed URLs (attempt {}/{}) in {:?}",
+                retry_urls.len(),
+                attempt,
+                retry_policy.max_attempts,
+                delay
+            );
+            self.context.emit(
+                "yt-dlp-retry",
+                serde_json::json!({
+                    "attempt": attempt,
+                    "maxAttempts": retry_policy.max_attempts,
+                    "urls": retry_urls,
+                    "delaySecs": delay.as_secs(),
+                }),
+            );
+            self.context
+                .update_job(self.id, |job| job.attempt = attempt);
+            tokio::time::sleep(delay).await;
+
+            match self.run_by_domain(&retry_urls).await {
+                Ok((retry_report, retry_lines)) => {
+                    report.merge_retry(retry_report);
+                    stderr_lines.extend(retry_lines);
+                }
+                Err(e) => {
+                    log::error!("Retry attempt {} failed to run: {}", attempt, e);
+                    break;
+                }
+            }
+        }
+
+        Ok((report, stderr_lines))
+    }
+
+    // URLをドメインごとに分けて順に実行し、結果をまとめて返す
+    // 各ドメインは全ジョブ共通の同時実行数・開始間隔・クールダウンに従う
+    async fn run_by_domain(
+        &self,
+        urls: &[String],
+    ) -> Result<(report::BatchReport, Vec<String>), String> {
+        let mut groups: Vec<(String, Vec<String>)> = Vec::new();
+        for url in urls {
+            let domain = throttle::domain_of(url);
+            match groups.iter_mut().find(|(d, _)| *d == domain) {
+                Some((_, group)) => group.push(url.clone()),
+                None => groups.push((domain, vec![url.clone()])),
+            }
+        }
+
+        let throttle = self.context.jobs.throttle.clone();
+        let batch_file = paths::cache_dir()?.join(format!("batch-{}.txt", self.id));
+        let mut combined: Option<report::BatchReport> = None;
+        let mut stderr_lines = Vec::new();
+
+        for (domain, group_urls) in groups {
+            // 中止が要求されていれば残りのドメイン・リトライは実行しない
+            if self.context.jobs.is_cancelled(self.id) {
+                return Err(JOB_CANCELLED.to_string());
+            }
+            // 一時停止中、または空き容量が不足していれば、再開・回復するまで次のプロセスを開始しない
+            self.with_disk_monitor(wait_while_paused(self.context, self.id))
+                .await?;
+
+            let permit = acquire_permit(self.context, self.id, &domain).await;
+
+            let mut file = fs::File::create(&batch_file).map_err(|e| {
+                log::error!("Could not create batch file: {}", e);
+                format!("Could not create batch file: {}", e)
+            })?;
+            for url in &group_urls {
+                writeln!(file, "{}", url).map_err(|e| {
+                    log::error!("Failed to write batch file: {}", e);
+                    format!("Failed to write batch file: {}", e)
+                })?;
+            }
+
+            let mut args = self.options.to_vec();
+            // リクエスト間隔の指定がなければ、ドメインの間隔を実行中のプロセス数（自身を含む）で分け合う
+            if !args.iter().any(|arg| arg.starts_with("--sleep-requests")) {
+                let limits = throttle.limits_for(&domain);
+                let sleep = limits.request_spacing_secs * throttle.active(&domain).max(1) as f64;
+                if sleep > 0.0 {
+                    args.push("--sleep-requests".to_string());
+                    args.push(sleep.to_string());
+                }
+            }
+            // 対応するサイトのCookieがあれば自動で指定（ユーザー指定があればそちらを優先）
+            let cookie_file = if args
+                .iter()
+                .any(|arg| arg.starts_with("--cookies") || arg == "--no-cookies")
+            {
+                None
+            } else {
+                cookies::cookies_for_domain(&domain)
+            };
+            if let Some(cookie_file) = &cookie_file {
+                log::info!("Using stored cookies for {}", domain);
+                args.push("--cookies".to_string());
+                args.push(cookie_file.to_string_lossy().to_string());
+            }
+            // ロック解除中の資格情報ストアにサイトのログイン情報があれば付与
+            // （他のプロセスから見えないよう、引数ではなく所有者のみ読める設定ファイルで渡す）
+            let credential_args = self.context.vault.credential_args(&domain, &args);
+            let credential_config = if credential_args.is_empty() {
+                None
+            } else {
+                log::info!("Using stored credentials for {}", domain);
+                let config = paths::cache_dir()?.join(format!("credentials-{}.conf", self.id));
+                vault::write_credential_config(&config, &credential_args)?;
+                args.push("--config-locations".to_string());
+                args.push(config.to_string_lossy().to_string());
+                Some(config)
+            };
+            args.push("--batch-file".to_string());
+            args.push(batch_file.to_string_lossy().to_string());
+            args.extend(self.extra_args.iter().cloned());
+
+            let result = self
+                .with_disk_monitor(run_process(
+                    self.yt_dlp_path,
+                    &args,
+                    &group_urls,
+                    &domain,
+                    self.context,
+                    self.id,
+                    Some(permit),
+                ))
+                .await;
+            if let Some(config) = &credential_config {
+                let _ = fs::remove_file(config);
+            }
+            if let Some(cookie_file) = &cookie_file {
+                cookies::restrict_permissions(cookie_file);
+            }
+
+            let (group_report, group_lines) = result?;
+            stderr_lines.extend(group_lines);
+            match combined.as_mut() {
+                Some(combined) => combined.append(group_report),
+                None => combined = Some(group_report),
+            }
+        }
+        let _ = fs::remove_file(&batch_file);
+
+        let report = combined.ok_or_else(|| "No URLs to download".to_string())?;
+        Ok((report, stderr_lines))
+    }
+
+    // 保存先の空き容量を監視しながら実行（不足したらジョブを止め、回復したら解除する）
+    async fn with_disk_monitor<F: std::future::Future>(&self, future: F) -> F::Output {
+        // 監視を先に確認し、開始時点で不足していればすぐに止める
+        tokio::select! {
+            biased;
+            _ = disk::monitor(self.context, self.id, self.disk_dir, self.disk_config) => {
+                unreachable!("disk monitor never finishes")
+            }
+            output = future => output,
+        }
+    }
+}
+
+// yt-dlpプロセスを実行し、URLごとの結果とstderrの行を返す
+// レート制限を検出した場合はドメインをクールダウンさせ、中止が要求されたらプロセスを終了する
+// 一時停止でプロセスを終了した場合は、再開後に--continueを付けて途中のファイルから実行し直す
+// 一時停止中はドメインの実行枠を手放し、同じドメインの他のジョブを先に進める
+async fn run_process(
+    yt_dlp_path: &Path,
+    args: &[String],
+    urls: &[String],
+    domain: &str,
+    context: &Context<'_>,
+    job_id: &str,
+    permit: Option<throttle::DomainPermit>,
+) -> Result<(report::BatchReport, Vec<String>), String> {
+    // 出力行からURLごとの結果を集計（実行し直した場合も同じ集計を続ける）
+    let tracker = Arc::new(Mutex::new(report::BatchTracker::new(urls)));
+    let mut stderr_lines = Vec::new();
+    let mut run_args = args.to_vec();
+    // ユーザーが--limit-rateを指定していなければ、全体の帯域の上限から配分を受ける
+    let mut slots = ProcessSlots::new(
+        (!bandwidth::has_rate_option(args)).then(|| context.jobs.bandwidth.register()),
+        permit,
+    );
+    let status = loop {
+        let (exit, lines) = spawn_process(
+            yt_dlp_path,
+            &run_args,
+            domain,
+            context,
+            job_id,
+            &tracker,
+            &mut slots,
+        )
+        .await?;
+        stderr_lines.extend(lines);
+        match exit {
+            ProcessExit::Exited(status) => break status,
+            ProcessExit::Stopped => {
+                // 一時停止中は他のプロセスに帯域とドメインの実行枠を譲る
+                slots.release();
+                wait_while_paused(context, job_id).await?;
+                slots.reacquire(context, job_id, domain).await;
+            }
+            ProcessExit::LimitChanged => {}
+            ProcessExit::CoolingDown => wait_for_cooldown(context, job_id, domain).await?,
+        }
+        // 後に指定したものが優先されるため、--no-continueの指定があっても続きから再開する
+        if run_args.last().map(String::as_str) != Some("--continue") {
+            run_args.push("--continue".to_string());
+        }
+    };
+
+    if !status.success() {
+        log::warn!("yt-dlp exited with status: {}", status);
+    }
+
+    let tracker = Arc::try_unwrap(tracker)
+        .ok()
+        .and_then(|tracker| tracker.into_inner().ok())
+        .ok_or_else(|| "Failed to collect batch results".to_string())?;
+    Ok((
+        tracker.finish(status.code(), status.success()),
+        stderr_lines,
+    ))
+}
+
+// プロセスが使う帯域の配分とドメインの実行枠（一時停止中は手放し、再開時に取り直す）
+struct ProcessSlots {
+    bandwidth: Option<bandwidth::BandwidthSlot>,
+    permit: Option<throttle::DomainPermit>,
+    limited: bool,
+    throttled: bool,
+}
+
+impl ProcessSlots {
+    fn new(
+        bandwidth: Option<bandwidth::BandwidthSlot>,
+        permit: Option<throttle::DomainPermit>,
+    ) -> Self {
+        ProcessSlots {
+            limited: bandwidth.is_some(),
+            throttled: permit.is_some(),
+            bandwidth,
+            permit,
+        }
+    }
+
+    fn release(&mut self) {
+        self.bandwidth = None;
+        self.permit = None;
+    }
+
+    async fn reacquire(&mut self, context: &Context<'_>, job_id: &str, domain: &str) {
+        if self.throttled && self.permit.is_none() {
+            self.permit = Some(acquire_permit(context, job_id, domain).await);
+        }
+        if self.limited && self.bandwidth.is_none() {
+            self.bandwidth = Some(context.jobs.bandwidth.register());
+        }
+    }
+}
+
+// プロセスの終了の仕方
+enum ProcessExit {
+    Exited(std::process::ExitStatus),
+    // 一時停止のために終了させた
+    Stopped,
+    // 帯域の配分が変わったため、新しい上限で実行し直すために終了させた
+    LimitChanged,
+    // ドメインがクールダウン中のため、終わってから実行し直すために終了させた
+    CoolingDown,
+}
+
+// yt-dlpプロセスを1回起動し、終了するまで出力を集計
+// 中止・一時停止の要求と帯域の配分を確認しながら待機し、stderrの行と合わせて終了の仕方を返す
+async fn spawn_process(
+    yt_dlp_path: &Path,
+    args: &[String],
+    domain: &str,
+    context: &Context<'_>,
+    job_id: &str,
+    tracker: &Arc<Mutex<report::BatchTracker>>,
+    slots: &mut ProcessSlots,
+) -> Result<(ProcessExit, Vec<String>), String> {
+    let throttle = &context.jobs.throttle;
+    let events = &context.events;
+    let bandwidth = &context.jobs.bandwidth;
+    let limited = slots.limited;
+    // 直接実行（シェルを使わない）
+    let mut cmd = Command::new(yt_dlp_path);
+    cmd.args(args);
+    let limit_kib = if limited { bandwidth.share() } else { None };
+    if let Some(limit_kib) = limit_kib {
+        log::info!("Limiting yt-dlp for job {} to {} KiB/s", job_id, limit_kib);
+        cmd.arg("--limit-rate").arg(format!("{}K", limit_kib));
+        context.emit(
+            "yt-dlp-bandwidth-limit",
+            serde_json::json!({ "limitKib": limit_kib }),
+        );
+    }
+    cmd.stdout(Stdio::piped()).stderr(Stdio::piped());
+
+    #[cfg(windows)]
+    {
+        use std::os::windows::process::CommandExt;
+        cmd.creation_flags(0x08000000);
+    }
+    // 変換中のffmpegなどの子プロセスもまとめて止められるよう、新しいプロセスグループで起動
+    #[cfg(unix)]
+    {
+        use std::os::unix::process::CommandExt;
+        cmd.process_group(0);
+    }
+
+    let mut child = cmd.spawn().map_err(|e| {
+        log::error!("Failed to run yt-dlp: {}", e);
+        redact::emit(
+            events.as_ref(),
+            "yt-dlp-error",
+            format!("Failed to spawn yt-dlp: {}", e),
+        );
+        format!("Failed to run yt-dlp: {}", e)
+    })?;
+
+    // stdoutとstderrを取得
+    let stdout = child.stdout.take().unwrap();
+    let stderr = child.stderr.take().unwrap();
+
+    let stdout_tracker = tracker.clone();
+    let stderr_tracker = tracker.clone();
+    let stdout_events = events.clone();
+    let stderr_events = events.clone();
+    let stdout_throttle = throttle.clone();
+    let stderr_throttle = throttle.clone();
+    let stdout_domain = domain.to_string();
+    let stderr_domain = domain.to_string();
+    // 既知のエラーは対処方法と合わせてyt-dlp-diagnosticイベントで通知
+    let stdout_task = spawn_output_reader(stdout, events.clone(), "yt-dlp-stdout", move |line| {
+        if let Ok(mut tracker) = stdout_tracker.lock() {
+            tracker.feed_stdout(line);
+        }
+        if let Some(diagnostic) = diagnostics::classify_line(line) {
+            if diagnostic.kind == diagnostics::ErrorKind::RateLimited {
+                stdout_throttle.cool_down(&stdout_domain);
+            }
+            redact::emit(stdout_events.as_ref(), "yt-dlp-diagnostic", &diagnostic);
+        }
+    });
+    let stderr_task = spawn_output_reader(stderr, events.clone(), "yt-dlp-stderr", move |line| {
+        if let Ok(mut tracker) = stderr_tracker.lock() {
+            tracker.feed_stderr(line);
+        }
+        if let Some(diagnostic) = diagnostics::classify_line(line) {
+            log::warn!(
+                "yt-dlp diagnostic: {:?} ({})",
+                diagnostic.kind,
+                diagnostic.line
+            );
+            if diagnostic.kind == diagnostics::ErrorKind::RateLimited {
+                stderr_throttle.cool_down(&stderr_domain);
+            }
+            redact::emit(stderr_events.as_ref(), "yt-dlp-diagnostic", &diagnostic);
+        }
+    });
+
+    // 後処理中に終了させると変換をやり直すことになるため、実行し直すのは後処理が終わってから
+    let post_processing = || {
+        tracker
+            .lock()
+            .is_ok_and(|tracker| tracker.is_post_processing())
+    };
+
+    // 中止・一時停止の要求を確認しながらプロセスの完了を待機
+    let exit = loop {
+        match child.try_wait() {
+            Ok(Some(status)) => break ProcessExit::Exited(status),
+            Ok(None) if context.jobs.is_cancelled(job_id) => {
+                log::info!("Cancelling job {}", job_id);
+                kill_process(&mut child);
+                let _ = tokio::join!(stdout_task, stderr_task);
+                return Err(JOB_CANCELLED.to_string());
+            }
+            Ok(None) => match context.jobs.hold_mode(job_id) {
+                // 429を検出したら、実行中のプロセスも止めてクールダウンが終わるまで待つ
+                None if throttle.cooldown_remaining(domain).is_some() && !post_processing() => {
+                    log::info!(
+                        "Stopping yt-dlp for job {} while {} cools down",
+                        job_id,
+                        domain
+                    );
+                    kill_process(&mut child);
+                    break ProcessExit::CoolingDown;
+                }
+                // 他のジョブの開始・終了や時間帯の切り替わりで配分が大きく変わったら実行し直す
+                None if limited
+                    && bandwidth::should_restart(limit_kib, bandwidth.share())
+                    && !post_processing() =>
+                {
+                    log::info!(
+                        "Bandwidth share changed, restarting yt-dlp for job {}",
+                        job_id
+                    );
+                    kill_process(&mut child);
+                    break ProcessExit::LimitChanged;
+                }
+                None => tokio::time::sleep(PROCESS_POLL_INTERVAL).await,
+                // 変換中のffmpegなどの子プロセスも含めてプロセスグループごと止める
+                Some(jobs::PauseMode::Suspend) => {
+                    #[cfg(unix)]
+                    signal_process(&child, libc::SIGSTOP);
+                    // 止めている間は他のプロセスに帯域とドメインの実行枠を譲る
+                    slots.release();
+                    // 待機中に中止された場合は次の確認でプロセスを終了する
+                    if wait_while_paused(context, job_id).await.is_ok() {
+                        slots.reacquire(context, job_id, domain).await;
+                    }
+                    #[cfg(unix)]
+                    signal_process(&child, libc::SIGCONT);
+                }
+                Some(jobs::PauseMode::Restart) => {
+                    log::info!("Stopping yt-dlp to pause job {}", job_id);
+                    kill_process(&mut child);
+                    break ProcessExit::Stopped;
+                }
+            },
+            Err(e) => {
+                log::error!("Failed to wait for yt-dlp: {}", e);
+                redact::emit(
+                    events.as_ref(),
+                    "yt-dlp-error",
+                    format!("Failed to wait for yt-dlp: {}", e),
+                );
+                return Err(format!("Failed to wait for yt-dlp: {}", e));
+            }
+        }
+    };
+
+    // タスクの完了を待機
+    let (_, stderr_lines) = tokio::join!(stdout_task, stderr_task);
+    Ok((exit, stderr_lines.unwrap_or_default()))
+}
+
+// ドメインの実行枠を取得（待つ場合は待機中として通知）
+async fn acquire_permit(
+    context: &Context<'_>,
+    job_id: &str,
+    domain: &str,
+) -> throttle::DomainPermit {
+    let throttle = &context.jobs.throttle;
+    let permit = match throttle.try_acquire(domain) {
+        Ok(permit) => permit,
+        Err(_) => {
+            log::info!("Waiting for throttle slot on {}", domain);
+            context.update_job(job_id, |job| {
+                job.status = jobs::JobStatus::Waiting;
+                job.current_domain = Some(domain.to_string());
+            });
+            throttle.acquire(domain).await
+        }
+    };
+    context.update_job(job_id, |job| {
+        job.status = jobs::JobStatus::Running;
+        job.current_domain = Some(domain.to_string());
+    });
+    permit
+}
+
+// 一時停止または空き容量の不足で止められていれば、両方が解除されるまで待機（待機中に中止された場合はエラー）
+// ユーザーの再開では空き容量の不足による停止は解除されない
+async fn wait_while_paused(context: &Context<'_>, job_id: &str) -> Result<(), String> {
+    if context.jobs.hold_mode(job_id).is_none() {
+        return Ok(());
+    }
+    log::info!("Job {} paused", job_id);
+    let mut notified = None;
+    while context.jobs.hold_mode(job_id).is_some() {
+        if context.jobs.is_cancelled(job_id) {
+            return Err(JOB_CANCELLED.to_string());
+        }
+        // ユーザーの一時停止の有無が変わったときだけ通知
+        let mode = context.jobs.pause_mode(job_id);
+        if notified != Some(mode) {
+            notified = Some(mode);
+            context.update_job(job_id, |job| {
+                job.status = jobs::JobStatus::Paused;
+                job.pause_mode = mode;
+            });
+        }
+        tokio::time::sleep(PROCESS_POLL_INTERVAL).await;
+    }
+    log::info!("Job {} resumed", job_id);
+    context.update_job(job_id, |job| {
+        job.status = jobs::JobStatus::Running;
+        job.pause_mode = None;
+    });
+    Ok(())
+}
+
+// ドメインのクールダウンが終わるまで待機（待機中に中止された場合はエラー）
+async fn wait_for_cooldown(
+    context: &Context<'_>,
+    job_id: &str,
+    domain: &str,
+) -> Result<(), String> {
+    context.update_job(job_id, |job| job.status = jobs::JobStatus::Waiting);
+    while let Some(remaining) = context.jobs.throttle.cooldown_remaining(domain) {
+        if context.jobs.is_cancelled(job_id) {
+            return Err(JOB_CANCELLED.to_string());
+        }
+        tokio::time::sleep(remaining.min(PROCESS_POLL_INTERVAL)).await;
+    }
+    log::info!("Cooldown for {} ended, resuming job {}", domain, job_id);
+    context.update_job(job_id, |job| job.status = jobs::JobStatus::Running);
+    Ok(())
+}
+
+// プロセスを終了（Unixではプロセスグループごと終了し、ffmpegなどの子プロセスを残さない）
+fn kill_process(child: &mut std::process::Child) {
+    #[cfg(unix)]
+    signal_process(child, libc::SIGKILL);
+    let _ = child.kill();
+    let _ = child.wait();
+}
+
+// 実行中のプロセスグループにシグナルを送る（SIGSTOPで一時停止、SIGCONTで再開）
+#[cfg(unix)]
+fn signal_process(child: &std::process::Child, signal: libc::c_int) {
+    // 終了済みのプロセスにはtry_waitで回収するまで送信しても影響しない
+    // プロセスグループのIDはprocess_group(0)で起動したyt-dlpのPIDと同じ
+    if unsafe { libc::kill(-(child.id() as libc::pid_t), signal) } != 0 {
+        log::warn!(
+            "Failed to send signal {} to yt-dlp: {}",
+            signal,
+            std::io::Error::last_os_error()
+        );
+    }
+}
+
+// 子プロセスの出力を非同期で読み取り、キャリッジリターンを考慮してイベントとして送信
+// 改行で確定した行はon_lineに渡し、呼び出し元での解析用に収集して返す
+fn spawn_output_reader<R, F>(
+    reader: R,
+    events: Arc<dyn EventSink>,
+    event: &'static str,
+    mut on_line: F,
+) -> tokio::task::JoinHandle<Vec<String>>
+where
+    R: Read + Send + 'static,
+    F: FnMut(&str) + Send + 'static,
+{
+    tokio::spawn(async move {
+        let mut detector = EncodingDetector::new();
+        // confirmed_encodingは使わず、毎回detector.guessで判定する
+        let mut reader = reader;
+        let mut buffer = [0; 4096];
+        let mut line_buffer = String::new();
+        let mut lines = Vec::new();
+
+        loop {
+            match reader.read(&mut buffer) {
+                Ok(0) => break, // EOF
+                Ok(n) => {
+                    // 毎回検出器を更新
+                    detector.feed(&buffer[..n], false);
+
+                    // 毎回guessでエンコーディングを判定
+                    let encoding = detector.guess(None, true);
+                    let (cow, _, had_errors) = encoding.decode(&buffer[..n]);
+                    let chunk = if had_errors {
+                        // 判別失敗や壊れた部分があればUTF-8で再デコード（置換文字で埋める）
+                        String::from_utf8_lossy(&buffer[..n]).to_string()
+                    } else {
+                        cow.to_string()
+                    };
+                    for ch in chunk.chars() {
+                        match ch {
+                            '\n' => {
+                                // yt-dlpの詳細出力に含まれる引数から秘密の値を伏せる
+                                let line = redact::redact(&line_buffer);
+                                events.send(
+                                    event,
+                                    serde_json::json!({
+                                        "content": line.clone(),
+                                        "overwrite": false
+                                    }),
+                                );
+                                on_line(&line);
+                                lines.push(line);
+                                line_buffer.clear();
+                            }
+                            '\r' => {
+                                events.send(
+                                    event,
+                                    serde_json::json!({
+                                        "content": redact::redact(&line_buffer),
+                                        "overwrite": true
+                                    }),
+                                );
+                                line_buffer.clear();
+                            }
+                            _ => {
+                                line_buffer.push(ch);
+                            }
+                        }
+                    }
+                }
+                Err(_) => break,
+            }
+        }
+
+        // 最後に残った内容があれば送信
+        if !line_buffer.is_empty() {
+            let line = redact::redact(&line_buffer);
+            events.send(
+                event,
+                serde_json::json!({
+                    "content": line.clone(),
+                    "overwrite": false
+                }),
+            );
+            on_line(&line);
+            lines.push(line);
+        }
+
+        lines
+    })
+}
+
+// URLを値として取るオプション（位置引数のURLと区別するため）
+const URL_VALUE_OPTIONS: &[&str] = &[
+    "--proxy",
+    "--geo-verification-proxy",
+    "--referer",
+    "--add-header",
+];
+
+// コマンドライン引数から対象URL（--batch-fileの中身と位置引数）とそれ以外のオプションを分離
+pub fn split_url_args(args: &[String]) -> (Vec<String>, Vec<String>) {
+    let mut urls = Vec::new();
+    let mut options = Vec::new();
+    let mut iter = args.iter().peekable();
+
+    while let Some(arg) = iter.next() {
+        let batch_file = if arg == "--batch-file" || arg == "-a" {
+            iter.next().cloned()
+        } else {
+            arg.strip_prefix("--batch-file=").map(|s| s.to_string())
+        };
+
+        if let Some(path) = batch_file {
+            match fs::read_to_string(&path) {
+                Ok(content) => urls.extend(
+                    content
+                        .lines()
+                        .map(|line| line.trim())
+                        // yt-dlpのバッチファイルと同じくコメント行を無視
+                        .filter(|line| !line.is_empty() && !line.starts_with(['#', ';', ']']))
+                        .map(|line| line.to_string()),
+                ),
+                Err(e) => log::warn!("Could not read batch file {:?}: {}", path, e),
+            }
+        } else if URL_VALUE_OPTIONS.contains(&arg.as_str()) {
+            options.push(arg.clone());
+            if let Some(value) = iter.next() {
+                options.push(value.clone());
+            }
+        } else if arg.starts_with("http://") || arg.starts_with("https://") {
+            urls.push(arg.clone());
+        } else {
+            options.push(arg.clone());
+        }
+    }
+
+    (urls, options)
+}
@@ -0,0 +1,547 @@
+use crate::engine::{self, Context, EventSink};
+use crate::history::{self, HistoryQuery, HistoryStatus};
+use crate::jobs::JobManager;
+use crate::profiles::ProfileStore;
+use crate::settings::SettingsStore;
+use crate::{paths, probe, redact, report, vault};
+use serde_json::Value;
+use std::io::{IsTerminal, Read, Write};
+use std::sync::{Arc, Mutex};
+
+// 終了コード
+const EXIT_SUCCESS: i32 = 0;
+// yt-dlpは実行できたが、失敗したURLがある
+const EXIT_FAILED: i32 = 1;
+const EXIT_USAGE: i32 = 2;
+// yt-dlpの更新・起動や設定の読み込みなどに失敗
+const EXIT_ERROR: i32 = 3;
+
+// historyで表示する件数の既定値
+const DEFAULT_HISTORY_LIMIT: usize = 20;
+
+const COMMANDS: &[&str] = &["update", "download", "probe", "history"];
+
+const USAGE: &str = "\
+Usage: takumi-vid-dl --headless <command> [options]
+
+Commands:
+  update                    Download the latest yt-dlp
+  download [options] [--] <yt-dlp options and URLs>
+    --profile <name>        Apply a saved profile
+    --urls <file>           Read URLs from a text file (- for stdin)
+    --max-attempts <n>      Attempts per URL including retries (default: settings)
+  probe <url>               Print video or playlist metadata as JSON
+  history [options]         Print download history, newest first
+    --search <text>
+    --status <success|failed|cancelled>
+    --from <date>           YYYY-MM-DD (local time) or RFC 3339
+    --to <date>
+    --limit <n>             (default: 20)
+    --json                  Print one JSON entry per line
+
+Exit codes:
+  0  Success
+  1  Some downloads failed
+  2  Invalid arguments
+  3  Could not run (yt-dlp missing, network or configuration error)";
+
+// ウィンドウを開かずにコマンドを実行し、終了コードをリターン（cronやCIからの実行用）
+pub fn run(args: &[String]) -> i32 {
+    attach_console();
+    // 端末の表示を妨げないよう、ログは既定で警告以上だけを出す
+    redact::RedactingLogger::init(
+        env_logger::Builder::from_env(env_logger::Env::default().default_filter_or("warn")).build(),
+    );
+    let Some((command, args)) = args.split_first() else {
+        return usage_error("No command given");
+    };
+    if matches!(command.as_str(), "help" | "--help" | "-h") {
+        println!("{}", USAGE);
+        return EXIT_SUCCESS;
+    }
+    if !COMMANDS.contains(&command.as_str()) {
+        return usage_error(&format!("Unknown command: {}", command));
+    }
+
+    let app_paths = match paths::init_headless() {
+        Ok(app_paths) => app_paths,
+        Err(e) => {
+            eprintln!("Error: {}", e);
+            return EXIT_ERROR;
+        }
+    };
+    redact::set_log_file(&app_paths.log_dir);
+
+    let result = tauri::async_runtime::block_on(async {
+        match command.as_str() {
+            "update" => update(args).await,
+            "download" => download(args, &app_paths.config_dir).await,
+            "probe" => probe(args),
+            _ => history(args),
+        }
+    });
+    exit_code(result)
+}
+
+// リリースビルドはWindowsではGUIアプリのため、起動元のコンソールに接続して出力を表示する
+#[cfg(windows)]
+fn attach_console() {
+    const ATTACH_PARENT_PROCESS: u32 = u32::MAX;
+    #[link(name = "kernel32")]
+    extern "system" {
+        fn AttachConsole(process_id: u32) -> i32;
+    }
+    // 出力がリダイレクトされている場合や、コンソールから起動されていない場合は失敗するが問題ない
+    unsafe {
+        AttachConsole(ATTACH_PARENT_PROCESS);
+    }
+}
+
+#[cfg(not(windows))]
+fn attach_console() {}
+
+// コマンドの結果を終了コードに変換（実行できなかった場合はエラーを表示）
+fn exit_code(result: Result<i32, String>) -> i32 {
+    match result {
+        Ok(code) => code,
+        Err(e) => {
+            eprintln!("Error: {}", e);
+            EXIT_ERROR
+        }
+    }
+}
+
+fn usage_error(message: &str) -> i32 {
+    eprintln!("{}\n\n{}", message, USAGE);
+    EXIT_USAGE
+}
+
+async fn update(args: &[String]) -> Result<i32, String> {
+    if let Some(arg) = args.first() {
+        return Ok(usage_error(&format!("Unexpected argument: {}", arg)));
+    }
+    println!("{}", engine::update_yt_dlp().await?);
+    Ok(EXIT_SUCCESS)
+}
+
+// downloadコマンドの引数
+#[derive(Debug, Default, PartialEq)]
+struct DownloadArgs {
+    profile: Option<String>,
+    urls_file: Option<String>,
+    max_attempts: Option<u32>,
+    // アプリのオプション以外はyt-dlpにそのまま渡す（「--」以降は全て）
+    yt_dlp_args: Vec<String>,
+}
+
+// 不正な引数の場合は使い方のエラーメッセージをリターン
+fn parse_download_args(args: &[String]) -> Result<DownloadArgs, String> {
+    let mut parsed = DownloadArgs::default();
+    let mut iter = args.iter();
+    while let Some(arg) = iter.next() {
+        match arg.as_str() {
+            "--profile" | "--urls" | "--max-attempts" => {
+                let Some(value) = iter.next() else {
+                    return Err(format!("{} requires a value", arg));
+                };
+                match arg.as_str() {
+                    "--profile" => parsed.profile = Some(value.clone()),
+                    "--urls" => parsed.urls_file = Some(value.clone()),
+                    _ => match value.parse::<u32>() {
+                        Ok(value) => parsed.max_attempts = Some(value),
+                        Err(_) => return Err(format!("Invalid --max-attempts: {}", value)),
+                    },
+                }
+            }
+            "--" => {
+                parsed.yt_dlp_args.extend(iter.by_ref().cloned());
+            }
+            _ => parsed.yt_dlp_args.push(arg.clone()),
+        }
+    }
+    if parsed.yt_dlp_args.is_empty() && parsed.urls_file.is_none() {
+        return Err("No URLs or yt-dlp options given".to_string());
+    }
+    Ok(parsed)
+}
+
+async fn download(args: &[String], config_dir: &std::path::Path) -> Result<i32, String> {
+    let DownloadArgs {
+        profile,
+        urls_file,
+        max_attempts,
+        mut yt_dlp_args,
+    } = match parse_download_args(args) {
+        Ok(parsed) => parsed,
+        Err(message) => return Ok(usage_error(&message)),
+    };
+
+    let settings = SettingsStore::load(config_dir);
+    let profiles = ProfileStore::load(config_dir);
+    if let Some(name) = &profile {
+        if profiles.get(name).is_none() {
+            return Ok(usage_error(&format!("Profile not found: {}", name)));
+        }
+    }
+    if let Some(urls_file) = &urls_file {
+        let text = if urls_file == "-" {
+            let mut text = String::new();
+            std::io::stdin()
+                .read_to_string(&mut text)
+                .map_err(|e| format!("Could not read URLs from stdin: {}", e))?;
+            text
+        } else {
+            std::fs::read_to_string(urls_file)
+                .map_err(|e| format!("Could not read URLs file {:?}: {}", urls_file, e))?
+        };
+        let batch_file = engine::write_url_list(&text)?;
+        yt_dlp_args.push("--batch-file".to_string());
+        yt_dlp_args.push(batch_file.to_string_lossy().to_string());
+    }
+
+    let mut retry_policy = settings.get().retry_policy;
+    if let Some(max_attempts) = max_attempts {
+        retry_policy.max_attempts = max_attempts;
+    }
+    let jobs = JobManager::default();
+    jobs.throttle.set_config(settings.get().throttle);
+    jobs.bandwidth.set_config(settings.get().bandwidth);
+    // 資格情報ストアはロックされたまま（保存済みのログイン情報は使わない）
+    let vault = vault::Vault::default();
+    let context = Context {
+        events: Arc::new(TerminalSink::default()),
+        settings: &settings,
+        jobs: &jobs,
+        profiles: &profiles,
+        vault: &vault,
+    };
+
+    // yt-dlpは別のプロセスグループで動くため、Ctrl+Cは届かない。ジョブを中止して終了させる
+    let run = engine::execute_yt_dlp(&context, yt_dlp_args, profile, retry_policy);
+    tokio::pin!(run);
+    let report = tokio::select! {
+        result = &mut run => result?,
+        _ = tokio::signal::ctrl_c() => {
+            eprintln!("Cancelling...");
+            for job in jobs.list() {
+                let _ = jobs.cancel(&job.id);
+            }
+            run.await?
+        }
+    };
+    print_summary(&report);
+    Ok(report_exit_code(&report))
+}
+
+fn report_exit_code(report: &report::BatchReport) -> i32 {
+    if report.success {
+        EXIT_SUCCESS
+    } else {
+        EXIT_FAILED
+    }
+}
+
+fn print_summary(report: &report::BatchReport) {
+    let summary = &report.summary;
+    eprintln!(
+        "\n{} of {} URLs downloaded, {} already in archive, {} failed",
+        summary.succeeded, summary.total, summary.already_archived, summary.failed
+    );
+    for result in report
+        .results
+        .iter()
+        .filter(|result| result.outcome.is_failure())
+    {
+        eprintln!(
+            "  Failed: {} ({})",
+            result.url,
+            result.message.as_deref().unwrap_or("unknown error")
+        );
+    }
+}
+
+fn probe(args: &[String]) -> Result<i32, String> {
+    let [url] = args else {
+        return Ok(usage_error("probe takes exactly one URL"));
+    };
+    let info = probe::probe(url, &[])?;
+    let json = serde_json::to_string_pretty(&info).map_err(|e| e.to_string())?;
+    println!("{}", json);
+    Ok(EXIT_SUCCESS)
+}
+
+// historyコマンドの引数（検索条件と、JSONで表示するか）
+// 不正な引数の場合は使い方のエラーメッセージをリターン
+fn parse_history_args(args: &[String]) -> Result<(HistoryQuery, bool), String> {
+    let mut query = HistoryQuery {
+        limit: Some(DEFAULT_HISTORY_LIMIT),
+        ..HistoryQuery::default()
+    };
+    let mut json = false;
+    let mut iter = args.iter();
+    while let Some(arg) = iter.next() {
+        if arg == "--json" {
+            json = true;
+            continue;
+        }
+        let Some(value) = iter.next().cloned() else {
+            return Err(format!("Unknown option or missing value: {}", arg));
+        };
+        match arg.as_str() {
+            "--search" => query.search = Some(value),
+            "--status" => {
+                query.status = match value.as_str() {
+                    "success" => Some(HistoryStatus::Success),
+                    "failed" => Some(HistoryStatus::Failed),
+                    "cancelled" => Some(HistoryStatus::Cancelled),
+                    _ => return Err(format!("Invalid --status: {}", value)),
+                }
+            }
+            "--from" => query.from = Some(value),
+            "--to" => query.to = Some(value),
+            "--limit" => match value.parse::<usize>() {
+                Ok(limit) => query.limit = Some(limit),
+                Err(_) => return Err(format!("Invalid --limit: {}", value)),
+            },
+            _ => return Err(format!("Unknown option: {}", arg)),
+        }
+    }
+    Ok((query, json))
+}
+
+fn history(args: &[String]) -> Result<i32, String> {
+    let (query, json) = match parse_history_args(args) {
+        Ok(parsed) => parsed,
+        Err(message) => return Ok(usage_error(&message)),
+    };
+
+    for entry in history::query_entries(&query)? {
+        if json {
+            println!(
+                "{}",
+                serde_json::to_string(&entry).map_err(|e| e.to_string())?
+            );
+            continue;
+        }
+        println!(
+            "{}  {:<9}  {:>3} URLs  {}  {}",
+            entry.started_at,
+            match entry.status {
+                HistoryStatus::Success => "success",
+                HistoryStatus::Failed => "failed",
+                HistoryStatus::Cancelled => "cancelled",
+            },
+            entry.urls.len(),
+            entry.profile.as_deref().unwrap_or("-"),
+            entry.id
+        );
+        if let Some(error_summary) = &entry.error_summary {
+            println!("    {}", error_summary);
+        }
+    }
+    Ok(EXIT_SUCCESS)
+}
+
+// ジョブのイベントを端末に表示（yt-dlpの出力はそのまま、それ以外は要約してstderrへ）
+#[derive(Default)]
+struct TerminalSink {
+    // 進捗の上書き表示中の行があるか（次の出力の前に改行する）
+    overwriting: Mutex<bool>,
+}
+
+impl TerminalSink {
+    fn print_output(&self, content: &str, overwrite: bool, to_stderr: bool) {
+        let mut overwriting = self.overwriting.lock().unwrap();
+        let is_terminal = if to_stderr {
+            std::io::stderr().is_terminal()
+        } else {
+            std::io::stdout().is_terminal()
+        };
+        // ログとして保存される場合は進捗の途中経過を出さない
+        if overwrite && !is_terminal {
+            return;
+        }
+        let text = if overwrite {
+            format!("\r\x1b[2K{}", content)
+        } else if *overwriting {
+            format!("\n{}\n", content)
+        } else {
+            format!("{}\n", content)
+        };
+        *overwriting = overwrite;
+        if to_stderr {
+            let _ = std::io::stderr().write_all(text.as_bytes());
+        } else {
+            let mut stdout = std::io::stdout();
+            let _ = stdout.write_all(text.as_bytes());
+            let _ = stdout.flush();
+        }
+    }
+
+    fn message(&self, message: &str) {
+        self.print_output(message, false, true);
+    }
+}
+
+impl EventSink for TerminalSink {
+    fn send(&self, event: &str, payload: Value) {
+        let text = |key: &str| payload.get(key).and_then(Value::as_str).unwrap_or_default();
+        let number = |key: &str| payload.get(key).and_then(Value::as_u64).unwrap_or_default();
+        match event {
+            "yt-dlp-stdout" | "yt-dlp-stderr" => {
+                let overwrite = payload
+                    .get("overwrite")
+                    .and_then(Value::as_bool)
+                    .unwrap_or(false);
+                self.print_output(text("content"), overwrite, event == "yt-dlp-stderr");
+            }
+            "yt-dlp-started" => self.message(&format!(
+                "Running: {}",
+                payload.as_str().unwrap_or_default()
+            )),
+            "yt-dlp-error" => self.message(payload.as_str().unwrap_or_default()),
+            "yt-dlp-diagnostic" => self.message(&format!("Hint: {}", text("hint"))),
+            "yt-dlp-archive-check" if number("skipped") > 0 => self.message(&format!(
+                "{} of {} URLs are already in the download archive",
+                number("skipped"),
+                number("total")
+            )),
+            "yt-dlp-template-check" => {
+                for issue in payload
+                    .get("issues")
+                    .and_then(Value::as_array)
+                    .into_iter()
+                    .flatten()
+                {
+                    self.message(&format!(
+                        "Output template: {}",
+                        issue
+                            .get("message")
+                            .and_then(Value::as_str)
+                            .unwrap_or_default()
+                    ));
+                }
+            }
+            "yt-dlp-disk-check" if payload.get("sufficient") == Some(&Value::Bool(false)) => self
+                .message(&format!(
+                    "Warning: disk space may run out on {}",
+                    text("targetDir")
+                )),
+            "disk-space-low" => self.message(&format!(
+                "Low disk space on {} ({} bytes left), waiting for space",
+                text("targetDir"),
+                number("availableBytes")
+            )),
+            "yt-dlp-retry" => self.message(&format!(
+                "Retrying {} failed URLs (attempt {}/{}) in {}s",
+                payload
+                    .get("urls")
+                    .and_then(Value::as_array)
+                    .map_or(0, Vec::len),
+                number("attempt"),
+                number("maxAttempts"),
+                number("delaySecs")
+            )),
+            _ => {}
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn args(args: &[&str]) -> Vec<String> {
+        args.iter().map(|arg| arg.to_string()).collect()
+    }
+
+    #[test]
+    fn download_args_separate_app_and_yt_dlp_options() {
+        let parsed = parse_download_args(&args(&[
+            "--profile",
+            "music",
+            "-f",
+            "bestaudio",
+            "--max-attempts",
+            "5",
+            "--",
+            "--profile",
+            "https://example.com/watch?v=1",
+        ]))
+        .unwrap();
+        assert_eq!(
+            parsed,
+            DownloadArgs {
+                profile: Some("music".to_string()),
+                urls_file: None,
+                max_attempts: Some(5),
+                // 「--」以降はアプリのオプションと同じ名前でもyt-dlpに渡す
+                yt_dlp_args: args(&[
+                    "-f",
+                    "bestaudio",
+                    "--profile",
+                    "https://example.com/watch?v=1"
+                ]),
+            }
+        );
+
+        let parsed = parse_download_args(&args(&["--urls", "-"])).unwrap();
+        assert_eq!(parsed.urls_file.as_deref(), Some("-"));
+        assert!(parsed.yt_dlp_args.is_empty());
+    }
+
+    #[test]
+    fn invalid_download_args_are_usage_errors() {
+        assert!(parse_download_args(&[]).is_err());
+        assert!(parse_download_args(&args(&["--profile", "music"])).is_err());
+        assert!(parse_download_args(&args(&["https://example.com", "--urls"])).is_err());
+        assert!(
+            parse_download_args(&args(&["--max-attempts", "x", "https://example.com"])).is_err()
+        );
+    }
+
+    #[test]
+    fn history_args_build_the_query() {
+        let (query, json) = parse_history_args(&args(&[
+            "--search",
+            "cat",
+            "--status",
+            "failed",
+            "--from",
+            "2024-05-01",
+            "--json",
+        ]))
+        .unwrap();
+        assert_eq!(query.search.as_deref(), Some("cat"));
+        assert_eq!(query.status, Some(HistoryStatus::Failed));
+        assert_eq!(query.from.as_deref(), Some("2024-05-01"));
+        assert_eq!(query.limit, Some(DEFAULT_HISTORY_LIMIT));
+        assert!(json);
+
+        assert!(parse_history_args(&args(&["--status", "done"])).is_err());
+        assert!(parse_history_args(&args(&["--limit", "-1"])).is_err());
+        assert!(parse_history_args(&args(&["--search"])).is_err());
+        assert!(parse_history_args(&args(&["--verbose", "1"])).is_err());
+    }
+
+    #[test]
+    fn results_map_to_exit_codes() {
+        assert_eq!(run(&[]), EXIT_USAGE);
+        assert_eq!(run(&args(&["fetch"])), EXIT_USAGE);
+        assert_eq!(run(&args(&["--help"])), EXIT_SUCCESS);
+
+        assert_eq!(exit_code(Ok(EXIT_USAGE)), EXIT_USAGE);
+        assert_eq!(exit_code(Err("yt-dlp not found".to_string())), EXIT_ERROR);
+
+        let mut report = report::BatchReport {
+            success: true,
+            exit_code: Some(0),
+            summary: report::BatchSummary::default(),
+            results: Vec::new(),
+        };
+        assert_eq!(report_exit_code(&report), EXIT_SUCCESS);
+        report.success = false;
+        report.exit_code = Some(1);
+        assert_eq!(report_exit_code(&report), EXIT_FAILED);
+    }
+}
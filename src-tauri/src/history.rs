@@ -0,0 +1,396 @@
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
+use time::{format_description::well_known::Rfc3339, OffsetDateTime, UtcOffset};
+
+// 各動画のダウンロード完了時に--print-to-fileで書き出す情報
+pub const ITEM_PRINT_TEMPLATE: &str = "after_move:%(.{webpage_url,title,extractor,filepath})j";
+
+// エラー概要に含める最大行数
+const MAX_ERROR_LINES: usize = 5;
+
+// 起動時に取得したシステムの現地時刻のオフセット（Unixではスレッドを起動した後は取得できない）
+static LOCAL_OFFSET: OnceLock<UtcOffset> = OnceLock::new();
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum HistoryStatus {
+    Success,
+    Failed,
+    // 実行中に中止された
+    Cancelled,
+}
+
+// ダウンロードされた1動画分の情報
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct HistoryItem {
+    pub url: Option<String>,
+    pub title: Option<String>,
+    pub extractor: Option<String>,
+    pub files: Vec<String>,
+}
+
+// yt-dlpの1回の実行（バッチ）分の履歴
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct HistoryEntry {
+    pub id: String,
+    pub urls: Vec<String>,
+    #[serde(default)]
+    pub profile: Option<String>,
+    pub items: Vec<HistoryItem>,
+    // パスワードなどの値は伏せ字にして記録する
+    pub options: Vec<String>,
+    pub started_at: String,
+    pub finished_at: String,
+    pub exit_code: Option<i32>,
+    pub status: HistoryStatus,
+    pub error_summary: Option<String>,
+}
+
+// 履歴検索の条件（すべて省略可能）
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct HistoryQuery {
+    pub search: Option<String>,
+    pub status: Option<HistoryStatus>,
+    pub from: Option<String>,
+    pub to: Option<String>,
+    // YYYY-MM-DDの日付はこのUTCからのオフセット（分）の現地時刻の0時から（省略時は起動時のシステムの現地時刻）
+    pub utc_offset_minutes: Option<i32>,
+    pub limit: Option<usize>,
+}
+
+// 履歴を検索（新しい順）
+#[tauri::command]
+pub async fn query_history(query: Option<HistoryQuery>) -> Result<Vec<HistoryEntry>, String> {
+    log::info!("Invoked query_history with query: {:?}", query);
+    query_entries(&query.unwrap_or_default())
+}
+
+pub fn query_entries(query: &HistoryQuery) -> Result<Vec<HistoryEntry>, String> {
+    let offset = match query.utc_offset_minutes {
+        Some(minutes) => crate::scheduler::offset(minutes)?,
+        None => LOCAL_OFFSET.get().copied().unwrap_or(UtcOffset::UTC),
+    };
+    let from = query
+        .from
+        .as_deref()
+        .map(|s| parse_date_bound(s, false, offset))
+        .transpose()?;
+    let to = query
+        .to
+        .as_deref()
+        .map(|s| parse_date_bound(s, true, offset))
+        .transpose()?;
+    let search = query.search.as_deref().map(|s| s.trim().to_lowercase());
+
+    let mut entries: Vec<HistoryEntry> = load_entries()?
+        .into_iter()
+        .filter(|entry| query.status.is_none_or(|status| entry.status == status))
+        .filter(|entry| {
+            let started = OffsetDateTime::parse(&entry.started_at, &Rfc3339).ok();
+            match started {
+                Some(started) => {
+                    from.is_none_or(|from| started >= from) && to.is_none_or(|to| started < to)
+                }
+                None => from.is_none() && to.is_none(),
+            }
+        })
+        .filter(|entry| match &search {
+            Some(search) if !search.is_empty() => matches_search(entry, search),
+            _ => true,
+        })
+        .collect();
+
+    entries.reverse();
+    if let Some(limit) = query.limit {
+        entries.truncate(limit);
+    }
+    Ok(entries)
+}
+
+// IDを指定して履歴を取得
+#[tauri::command]
+pub async fn get_history_entry(id: String) -> Result<HistoryEntry, String> {
+    log::info!("Invoked get_history_entry with id: {:?}", id);
+    find_entry(&id)
+}
+
+// 履歴のURLとオプションでyt-dlpを再実行（伏せ字にしたオプションは除く）
+#[tauri::command]
+pub async fn rerun_history_entry(
+    id: String,
+    window: tauri::Window,
+) -> Result<crate::report::BatchReport, String> {
+    log::info!("Invoked rerun_history_entry with id: {:?}", id);
+    let entry = find_entry(&id)?;
+
+    if entry.urls.is_empty() {
+        log::error!("History entry {} has no URLs to re-run", id);
+        return Err(format!("History entry {} has no URLs to re-run", id));
+    }
+
+    let urls_file = crate::engine::save_url_list(&entry.urls)?;
+    let mut args = vec![
+        "--batch-file".to_string(),
+        urls_file.to_string_lossy().to_string(),
+    ];
+    args.extend(crate::redact::remove_masked(&entry.options));
+
+    let context = crate::engine::Context::from_window(&window);
+    let retry_policy = context.settings.get().retry_policy;
+    crate::engine::execute_yt_dlp(&context, args, entry.profile, retry_policy).await
+}
+
+// 履歴ファイル（JSONL、1行1エントリの追記形式）のパス
+fn history_file() -> Result<PathBuf, String> {
+    Ok(crate::paths::data_dir()?.join("history.jsonl"))
+}
+
+// 履歴を1件追記
+pub fn append_entry(entry: &HistoryEntry) -> Result<(), String> {
+    let path = history_file()?;
+    let line = serde_json::to_string(entry).map_err(|e| {
+        log::error!("Failed to serialize history entry: {}", e);
+        format!("Failed to serialize history entry: {}", e)
+    })?;
+
+    let mut file = fs::OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(&path)
+        .map_err(|e| {
+            log::error!("Could not open history file: {}", e);
+            format!("Could not open history file: {}", e)
+        })?;
+    writeln!(file, "{}", line).map_err(|e| {
+        log::error!("Failed to write history file: {}", e);
+        format!("Failed to write history file: {}", e)
+    })?;
+
+    log::info!("History entry recorded: {}", entry.id);
+    Ok(())
+}
+
+// 履歴を古い順にすべて読み込む（壊れた行は読み飛ばす）
+pub fn load_entries() -> Result<Vec<HistoryEntry>, String> {
+    let path = history_file()?;
+    let content = match fs::read_to_string(&path) {
+        Ok(content) => content,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(e) => {
+            log::error!("Could not read history file: {}", e);
+            return Err(format!("Could not read history file: {}", e));
+        }
+    };
+
+    Ok(content
+        .lines()
+        .filter(|line| !line.trim().is_empty())
+        .filter_map(|line| match serde_json::from_str(line) {
+            Ok(entry) => Some(entry),
+            Err(e) => {
+                log::warn!("Skipping malformed history line: {}", e);
+                None
+            }
+        })
+        .collect())
+}
+
+fn find_entry(id: &str) -> Result<HistoryEntry, String> {
+    load_entries()?
+        .into_iter()
+        .find(|entry| entry.id == id)
+        .ok_or_else(|| {
+            log::error!("History entry not found: {}", id);
+            format!("History entry not found: {}", id)
+        })
+}
+
+// --print-to-fileで書き出された動画情報を読み込む
+pub fn read_items_file(path: &Path) -> Vec<HistoryItem> {
+    let content = match fs::read_to_string(path) {
+        Ok(content) => content,
+        Err(_) => return Vec::new(),
+    };
+
+    content
+        .lines()
+        .filter_map(|line| serde_json::from_str::<serde_json::Value>(line).ok())
+        .map(|info| {
+            let field = |key: &str| info.get(key).and_then(|v| v.as_str()).map(String::from);
+            HistoryItem {
+                url: field("webpage_url"),
+                title: field("title"),
+                extractor: field("extractor"),
+                files: field("filepath").into_iter().collect(),
+            }
+        })
+        .collect()
+}
+
+// stderrのERROR行からエラー概要を作成
+pub fn summarize_errors(stderr_lines: &[String]) -> Option<String> {
+    let errors: Vec<&str> = stderr_lines
+        .iter()
+        .map(|line| line.trim())
+        .filter(|line| line.starts_with("ERROR:"))
+        .collect();
+
+    if errors.is_empty() {
+        return None;
+    }
+
+    let mut summary = errors
+        .iter()
+        .take(MAX_ERROR_LINES)
+        .copied()
+        .collect::<Vec<_>>()
+        .join("\n");
+    if errors.len() > MAX_ERROR_LINES {
+        summary.push_str(&format!(
+            "\n... and {} more errors",
+            errors.len() - MAX_ERROR_LINES
+        ));
+    }
+    Some(summary)
+}
+
+fn matches_search(entry: &HistoryEntry, search: &str) -> bool {
+    let contains = |s: &str| s.to_lowercase().contains(search);
+
+    entry.urls.iter().any(|url| contains(url))
+        || entry.items.iter().any(|item| {
+            [&item.url, &item.title, &item.extractor]
+                .into_iter()
+                .flatten()
+                .any(|s| contains(s))
+                || item.files.iter().any(|f| contains(f))
+        })
+        || entry.error_summary.as_deref().is_some_and(contains)
+}
+
+// 日付の絞り込み条件をパース（RFC3339またはYYYY-MM-DD）
+// YYYY-MM-DDは指定したオフセットの現地時刻の0時とし、終端指定はその日の終わりまでを含める
+fn parse_date_bound(
+    s: &str,
+    end_of_day: bool,
+    offset: UtcOffset,
+) -> Result<OffsetDateTime, String> {
+    let s = s.trim();
+    if let Ok(datetime) = OffsetDateTime::parse(s, &Rfc3339) {
+        return Ok(datetime);
+    }
+
+    let parts: Vec<&str> = s.split('-').collect();
+    let date = match parts.as_slice() {
+        [year, month, day] => year.parse::<i32>().ok().and_then(|year| {
+            let month = time::Month::try_from(month.parse::<u8>().ok()?).ok()?;
+            time::Date::from_calendar_date(year, month, day.parse().ok()?).ok()
+        }),
+        _ => None,
+    }
+    .ok_or_else(|| {
+        log::error!("Invalid date filter: {:?}", s);
+        format!("Invalid date filter: {:?}", s)
+    })?;
+    let date = if end_of_day {
+        date.next_day().unwrap_or(date)
+    } else {
+        date
+    };
+    Ok(date.midnight().assume_offset(offset))
+}
+
+// システムの現地時刻のオフセットを記録（スレッドを起動する前に呼び出す）
+pub fn init_local_offset() {
+    // ロガーの初期化前に呼ばれるため、取得できなくても記録せずUTCとして扱う
+    if let Ok(offset) = UtcOffset::current_local_offset() {
+        let _ = LOCAL_OFFSET.set(offset);
+    }
+}
+
+// 履歴IDを生成（現在時刻のナノ秒）
+pub fn new_entry_id() -> String {
+    OffsetDateTime::now_utc().unix_timestamp_nanos().to_string()
+}
+
+pub fn now_rfc3339() -> String {
+    OffsetDateTime::now_utc()
+        .format(&Rfc3339)
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry() -> HistoryEntry {
+        HistoryEntry {
+            id: "1".to_string(),
+            urls: vec!["https://www.youtube.com/watch?v=abc".to_string()],
+            profile: None,
+            items: vec![HistoryItem {
+                url: Some("https://www.youtube.com/watch?v=abc".to_string()),
+                title: Some("Live Concert".to_string()),
+                extractor: Some("youtube".to_string()),
+                files: vec!["/videos/Live Concert [abc].mp4".to_string()],
+            }],
+            options: Vec::new(),
+            started_at: "2026-01-05T10:00:00Z".to_string(),
+            finished_at: "2026-01-05T10:05:00Z".to_string(),
+            exit_code: Some(1),
+            status: HistoryStatus::Failed,
+            error_summary: Some("ERROR: [youtube] def: Video unavailable".to_string()),
+        }
+    }
+
+    #[test]
+    fn date_bounds_use_the_offset() {
+        let jst = UtcOffset::from_hms(9, 0, 0).unwrap();
+        assert_eq!(
+            parse_date_bound("2026-01-05", false, jst).unwrap(),
+            OffsetDateTime::parse("2026-01-04T15:00:00Z", &Rfc3339).unwrap()
+        );
+        // 終端はその日の終わりまで含める
+        assert_eq!(
+            parse_date_bound("2026-01-05", true, jst).unwrap(),
+            OffsetDateTime::parse("2026-01-05T15:00:00Z", &Rfc3339).unwrap()
+        );
+        assert_eq!(
+            parse_date_bound(" 2026-01-05T12:00:00+09:00 ", true, jst).unwrap(),
+            OffsetDateTime::parse("2026-01-05T03:00:00Z", &Rfc3339).unwrap()
+        );
+        assert!(parse_date_bound("2026-13-01", false, jst).is_err());
+        assert!(parse_date_bound("yesterday", false, jst).is_err());
+    }
+
+    #[test]
+    fn search_matches_urls_items_and_errors() {
+        let entry = entry();
+        assert!(matches_search(&entry, "watch?v=abc"));
+        assert!(matches_search(&entry, "live concert"));
+        assert!(matches_search(&entry, "youtube"));
+        assert!(matches_search(&entry, "[abc].mp4"));
+        assert!(matches_search(&entry, "unavailable"));
+        assert!(!matches_search(&entry, "vimeo"));
+    }
+
+    #[test]
+    fn error_summary_keeps_the_first_errors() {
+        assert_eq!(summarize_errors(&["WARNING: slow".to_string()]), None);
+
+        let lines: Vec<String> = (1..=7)
+            .map(|i| format!("  ERROR: failure {}", i))
+            .chain(["[download] 100%".to_string()])
+            .collect();
+        let summary = summarize_errors(&lines).unwrap();
+        assert_eq!(summary.lines().count(), MAX_ERROR_LINES + 1);
+        assert!(summary.starts_with("ERROR: failure 1\n"));
+        assert!(summary.ends_with("... and 2 more errors"));
+    }
+}
@@ -0,0 +1,401 @@
+use crate::engine::Context;
+use crate::jobs::{JobInfo, JobStatus};
+use crate::redact;
+use crate::report::{BatchReport, BatchSummary};
+use serde::{Deserialize, Serialize};
+use std::process::Stdio;
+use std::time::Duration;
+use tokio::io::AsyncWriteExt;
+
+// フックを実行する条件
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum HookTrigger {
+    Always,
+    // 正常に完了しなかった場合（失敗・中止）だけ
+    Failure,
+}
+
+// ジョブの終了時に実行するフック
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct HookConfig {
+    pub trigger: HookTrigger,
+    // デスクトップ通知（GUIのみ）
+    pub notify: bool,
+    // 結果のJSONをPOSTするURL（空なら送らない）
+    pub webhook_url: String,
+    pub webhook_timeout_secs: u64,
+    // 結果のJSONを標準入力で渡して実行するスクリプト（空なら実行しない）
+    pub script: String,
+    pub script_timeout_secs: u64,
+}
+
+impl Default for HookConfig {
+    fn default() -> Self {
+        HookConfig {
+            trigger: HookTrigger::Always,
+            notify: false,
+            webhook_url: String::new(),
+            webhook_timeout_secs: 10,
+            script: String::new(),
+            script_timeout_secs: 60,
+        }
+    }
+}
+
+impl HookConfig {
+    pub fn validate(&self) -> Result<(), String> {
+        let webhook_url = self.webhook_url.trim();
+        if !webhook_url.is_empty() {
+            match url::Url::parse(webhook_url) {
+                Ok(url) if matches!(url.scheme(), "http" | "https") => {}
+                _ => return Err("hooks.webhookUrl must be an http or https URL".to_string()),
+            }
+        }
+        if !(1..=300).contains(&self.webhook_timeout_secs) {
+            return Err("hooks.webhookTimeoutSecs must be between 1 and 300".to_string());
+        }
+        if !(1..=3600).contains(&self.script_timeout_secs) {
+            return Err("hooks.scriptTimeoutSecs must be between 1 and 3600".to_string());
+        }
+        Ok(())
+    }
+
+    fn should_run(&self, status: JobStatus) -> bool {
+        match self.trigger {
+            HookTrigger::Always => true,
+            HookTrigger::Failure => status != JobStatus::Completed,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct FailedUrl {
+    pub url: String,
+    pub message: Option<String>,
+}
+
+// フックに渡すジョブの結果（Webhookとスクリプトには同じJSONを送る）
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct JobResult {
+    pub job_id: String,
+    pub status: JobStatus,
+    pub profile: Option<String>,
+    pub urls: Vec<String>,
+    pub started_at: Option<String>,
+    pub finished_at: String,
+    pub exit_code: Option<i32>,
+    pub summary: Option<BatchSummary>,
+    pub failed_urls: Vec<FailedUrl>,
+    // 実行できずに終了した場合の理由
+    pub error: Option<String>,
+}
+
+impl JobResult {
+    pub fn new(
+        job_id: &str,
+        job: Option<JobInfo>,
+        profile: Option<String>,
+        urls: Vec<String>,
+        result: &Result<BatchReport, String>,
+    ) -> Self {
+        // 実行前の検証で失敗した場合はジョブとして登録されていない
+        let job = job.filter(|job| job.status.is_finished());
+        let status = job.as_ref().map(|job| job.status).unwrap_or(match result {
+            Ok(report) if report.success => JobStatus::Completed,
+            _ => JobStatus::Failed,
+        });
+        let (exit_code, summary, failed_urls) = match result {
+            Ok(report) => (
+                report.exit_code,
+                Some(report.summary.clone()),
+                report
+                    .results
+                    .iter()
+                    .filter(|result| result.outcome.is_failure())
+                    .map(|result| FailedUrl {
+                        url: result.url.clone(),
+                        message: result.message.as_deref().map(redact::redact),
+                    })
+                    .collect(),
+            ),
+            Err(_) => (None, None, Vec::new()),
+        };
+        JobResult {
+            job_id: job_id.to_string(),
+            status,
+            profile,
+            urls,
+            started_at: job.as_ref().map(|job| job.started_at.clone()),
+            finished_at: job
+                .and_then(|job| job.finished_at)
+                .unwrap_or_else(crate::history::now_rfc3339),
+            exit_code,
+            summary,
+            failed_urls,
+            error: result.as_ref().err().map(|e| redact::redact(e)),
+        }
+    }
+
+    fn notification(&self) -> (String, String) {
+        let title = match self.status {
+            JobStatus::Completed => "Download completed",
+            JobStatus::Cancelled => "Download cancelled",
+            _ => "Download failed",
+        };
+        let body = match (&self.summary, &self.error) {
+            (_, Some(error)) => error.clone(),
+            (Some(summary), None) => format!(
+                "{} succeeded, {} already downloaded, {} failed (of {})",
+                summary.succeeded, summary.already_archived, summary.failed, summary.total
+            ),
+            (None, None) => format!("{} URLs", self.urls.len()),
+        };
+        (title.to_string(), body)
+    }
+}
+
+// 設定に従ってフックを実行（失敗はログに残すだけでジョブの結果には影響させない）
+pub async fn run(context: &Context<'_>, result: &JobResult) {
+    let config = context.settings.get().hooks;
+    if !config.should_run(result.status) {
+        return;
+    }
+    if config.notify {
+        let (title, body) = result.notification();
+        context.events.notify(&title, &body);
+    }
+    tokio::join!(
+        async {
+            if let Err(e) = post_webhook(&config, result).await {
+                log::warn!("Webhook hook failed: {}", e);
+            }
+        },
+        async {
+            if let Err(e) = run_script(&config, result).await {
+                log::warn!("Script hook failed: {}", e);
+            }
+        }
+    );
+}
+
+async fn post_webhook(config: &HookConfig, result: &JobResult) -> Result<(), String> {
+    let webhook_url = config.webhook_url.trim();
+    if webhook_url.is_empty() {
+        return Ok(());
+    }
+    log::info!("Posting job {} result to webhook", result.job_id);
+    let client = reqwest::Client::builder()
+        .timeout(Duration::from_secs(config.webhook_timeout_secs))
+        .build()
+        .map_err(|e| format!("Failed to create HTTP client: {}", e))?;
+    client
+        .post(webhook_url)
+        .header("User-Agent", "TakumiVidDl")
+        .json(result)
+        .send()
+        .await
+        .and_then(|response| response.error_for_status())
+        .map_err(|e| format!("Failed to post to webhook: {}", e))?;
+    Ok(())
+}
+
+async fn run_script(config: &HookConfig, result: &JobResult) -> Result<(), String> {
+    let script = config.script.trim();
+    if script.is_empty() {
+        return Ok(());
+    }
+    log::info!("Running hook script {:?} for job {}", script, result.job_id);
+    let input = serde_json::to_vec(result).map_err(|e| e.to_string())?;
+
+    // 直接実行（シェルを使わない）し、タイムアウトした場合は終了させる
+    let mut cmd = tokio::process::Command::new(script);
+    cmd.stdin(Stdio::piped())
+        .stdout(Stdio::null())
+        .stderr(Stdio::piped())
+        .kill_on_drop(true);
+    #[cfg(windows)]
+    {
+        cmd.creation_flags(0x08000000);
+    }
+    let mut child = cmd
+        .spawn()
+        .map_err(|e| format!("Failed to run {:?}: {}", script, e))?;
+    // 標準入力を読まないスクリプトで書き込みが止まっても終了させるよう、書き込みもタイムアウトに含める
+    let stdin = child.stdin.take();
+    let write_input = async move {
+        if let Some(mut stdin) = stdin {
+            // スクリプトが標準入力を読まずに終了した場合の書き込みエラーは無視
+            let _ = stdin.write_all(&input).await;
+        }
+    };
+    let timeout = Duration::from_secs(config.script_timeout_secs);
+    let run = async { tokio::join!(write_input, child.wait_with_output()).1 };
+    let output = tokio::time::timeout(timeout, run)
+        .await
+        .map_err(|_| format!("{:?} timed out after {:?}", script, timeout))?
+        .map_err(|e| format!("Failed to wait for {:?}: {}", script, e))?;
+    if !output.status.success() {
+        return Err(format!(
+            "{:?} exited with {}: {}",
+            script,
+            output.status,
+            String::from_utf8_lossy(&output.stderr).trim()
+        ));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::http::StatusCode;
+    use axum::routing::post;
+    use axum::{Json, Router};
+    use serde_json::Value;
+    use tokio::sync::mpsc;
+
+    fn job_result() -> JobResult {
+        JobResult {
+            job_id: "job-1".to_string(),
+            status: JobStatus::Failed,
+            profile: Some("music".to_string()),
+            urls: vec![
+                "https://example.com/a".to_string(),
+                "https://example.com/b".to_string(),
+            ],
+            started_at: Some("2026-01-01T00:00:00Z".to_string()),
+            finished_at: "2026-01-01T00:01:00Z".to_string(),
+            exit_code: Some(1),
+            summary: Some(BatchSummary {
+                total: 2,
+                succeeded: 1,
+                already_archived: 0,
+                failed: 1,
+            }),
+            failed_urls: vec![FailedUrl {
+                url: "https://example.com/b".to_string(),
+                message: Some("Video unavailable".to_string()),
+            }],
+            error: None,
+        }
+    }
+
+    // 受け取った本文を送り、指定したステータスを返すローカルのWebhookの受け口
+    async fn start_server(status: StatusCode, delay: Duration) -> (String, mpsc::Receiver<Value>) {
+        let (sender, receiver) = mpsc::channel(1);
+        let router = Router::new().route(
+            "/hook",
+            post(move |Json(body): Json<Value>| async move {
+                let _ = sender.send(body).await;
+                tokio::time::sleep(delay).await;
+                status
+            }),
+        );
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}/hook", listener.local_addr().unwrap());
+        tokio::spawn(async move { axum::serve(listener, router).await });
+        (url, receiver)
+    }
+
+    fn webhook_config(url: &str) -> HookConfig {
+        HookConfig {
+            webhook_url: url.to_string(),
+            webhook_timeout_secs: 1,
+            ..HookConfig::default()
+        }
+    }
+
+    #[tokio::test]
+    async fn posts_job_result_to_webhook() {
+        let (url, mut receiver) = start_server(StatusCode::NO_CONTENT, Duration::ZERO).await;
+        post_webhook(&webhook_config(&url), &job_result())
+            .await
+            .unwrap();
+
+        let body = receiver.recv().await.unwrap();
+        assert_eq!(body, serde_json::to_value(job_result()).unwrap());
+        assert_eq!(body["jobId"], "job-1");
+        assert_eq!(body["status"], "failed");
+        assert_eq!(body["summary"]["failed"], 1);
+        assert_eq!(body["failedUrls"][0]["url"], "https://example.com/b");
+    }
+
+    #[tokio::test]
+    async fn fails_on_webhook_error_status() {
+        let (url, mut receiver) =
+            start_server(StatusCode::INTERNAL_SERVER_ERROR, Duration::ZERO).await;
+        let error = post_webhook(&webhook_config(&url), &job_result())
+            .await
+            .unwrap_err();
+        assert!(error.contains("500"), "{}", error);
+        assert!(receiver.recv().await.is_some());
+    }
+
+    #[tokio::test]
+    async fn fails_on_webhook_timeout() {
+        let (url, _receiver) = start_server(StatusCode::OK, Duration::from_secs(5)).await;
+        let started = std::time::Instant::now();
+        assert!(post_webhook(&webhook_config(&url), &job_result())
+            .await
+            .is_err());
+        assert!(started.elapsed() < Duration::from_secs(4));
+    }
+
+    #[tokio::test]
+    async fn skips_empty_webhook_and_script() {
+        let config = HookConfig::default();
+        assert!(post_webhook(&config, &job_result()).await.is_ok());
+        assert!(run_script(&config, &job_result()).await.is_ok());
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn runs_script_with_result_on_stdin() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let dir = std::env::temp_dir().join(format!("takumi-vid-dl-hooks-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let write_script = |name: &str, body: &str| {
+            let path = dir.join(name);
+            std::fs::write(&path, format!("#!/bin/sh\n{}\n", body)).unwrap();
+            std::fs::set_permissions(&path, std::fs::Permissions::from_mode(0o755)).unwrap();
+            HookConfig {
+                script: path.to_string_lossy().to_string(),
+                script_timeout_secs: 1,
+                ..HookConfig::default()
+            }
+        };
+        let input_file = dir.join("input.json");
+
+        let config = write_script("save.sh", &format!("cat > '{}'", input_file.display()));
+        run_script(&config, &job_result()).await.unwrap();
+        let input: Value = serde_json::from_slice(&std::fs::read(&input_file).unwrap()).unwrap();
+        assert_eq!(input, serde_json::to_value(job_result()).unwrap());
+
+        let config = write_script("fail.sh", "echo broken >&2\nexit 3");
+        let error = run_script(&config, &job_result()).await.unwrap_err();
+        assert!(error.contains("broken"), "{}", error);
+
+        let config = write_script("slow.sh", "sleep 5");
+        let started = std::time::Instant::now();
+        let error = run_script(&config, &job_result()).await.unwrap_err();
+        assert!(error.contains("timed out"), "{}", error);
+        assert!(started.elapsed() < Duration::from_secs(4));
+
+        // パイプのバッファを超える入力を読まないスクリプトもタイムアウトで終了させる
+        let mut large_result = job_result();
+        large_result.urls = (0..10_000)
+            .map(|i| format!("https://example.com/watch?v={}", i))
+            .collect();
+        let started = std::time::Instant::now();
+        let error = run_script(&config, &large_result).await.unwrap_err();
+        assert!(error.contains("timed out"), "{}", error);
+        assert!(started.elapsed() < Duration::from_secs(4));
+
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+}
@@ -0,0 +1,487 @@
+use crate::urls::{self, UrlListResult};
+use chardetng::EncodingDetector;
+use regex::Regex;
+use serde::Deserialize;
+use serde_json::value::RawValue;
+use serde_json::Value;
+use std::collections::HashMap;
+use std::fs;
+use std::path::Path;
+use std::sync::LazyLock;
+
+// JSONのオブジェクトでURLとして扱うキー（先にあるものを優先）
+const JSON_URL_KEYS: &[&str] = &["url", "webpage_url", "link", "href", "uri"];
+// JSONのオブジェクトでURLの配列として扱うキー
+const JSON_LIST_KEYS: &[&str] = &["urls", "items", "entries", "links", "videos"];
+
+// ブックマークのHTMLのリンク
+static HREF_PATTERN: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r#"(?i)<a\s[^>]*?href\s*=\s*["']([^"']*)["']"#).unwrap());
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum ImportFormat {
+    Text,
+    Csv,
+    Json,
+    Bookmarks,
+    M3u,
+}
+
+impl ImportFormat {
+    // 拡張子から判定（不明ならテキスト）
+    fn from_path(path: &Path) -> ImportFormat {
+        let extension = path
+            .extension()
+            .map(|extension| extension.to_string_lossy().to_lowercase())
+            .unwrap_or_default();
+        match extension.as_str() {
+            "csv" | "tsv" => ImportFormat::Csv,
+            "json" => ImportFormat::Json,
+            "html" | "htm" => ImportFormat::Bookmarks,
+            "m3u" | "m3u8" => ImportFormat::M3u,
+            _ => ImportFormat::Text,
+        }
+    }
+}
+
+// ファイルの内容を行ごとのURLの候補に変換し、貼り付けと同じ解析（正規化・重複除去）を行う
+// 行番号は元のファイルの行（JSONは要素の開始位置の行）に対応させる
+pub fn import_text(
+    content: &str,
+    format: ImportFormat,
+    column: Option<&str>,
+) -> Result<UrlListResult, String> {
+    let text = match format {
+        // M3Uの「#EXTINF」などの行はコメントとして無視される
+        ImportFormat::Text | ImportFormat::M3u => content.to_string(),
+        ImportFormat::Csv => csv_lines(content, column)?.join("\n"),
+        ImportFormat::Json => {
+            // 要素ごとに1行として解析し、行番号を要素のあるファイルの行に置き換える
+            let (items, item_lines) = json_lines(content)?
+                .into_iter()
+                .unzip::<_, _, Vec<_>, Vec<_>>();
+            let mut result = urls::parse_url_list(&items.join("\n"));
+            for invalid in &mut result.invalid {
+                invalid.line = item_lines[invalid.line - 1];
+            }
+            for duplicate in &mut result.duplicates {
+                duplicate.line = item_lines[duplicate.line - 1];
+            }
+            return Ok(result);
+        }
+        ImportFormat::Bookmarks => bookmark_lines(content).join("\n"),
+    };
+    Ok(urls::parse_url_list(&text))
+}
+
+// 文字コードを判定して読み込む（表計算ソフトが書き出したShift_JISのCSVなど）
+fn read_text(path: &Path) -> Result<String, String> {
+    let bytes = fs::read(path).map_err(|e| {
+        log::error!("Could not read import file {:?}: {}", path, e);
+        format!("Could not read import file {:?}: {}", path, e)
+    })?;
+    let bytes = bytes.strip_prefix(b"\xEF\xBB\xBF").unwrap_or(&bytes);
+    let mut detector = EncodingDetector::new();
+    detector.feed(bytes, true);
+    let (text, _, _) = detector.guess(None, true).decode(bytes);
+    Ok(text.into_owned())
+}
+
+// 指定した列（見出しの名前または1から始まる番号）の値を行ごとに取り出す
+// 列の指定がなければURLを含む最初の列を使う
+fn csv_lines(content: &str, column: Option<&str>) -> Result<Vec<String>, String> {
+    let rows = parse_csv(content);
+    let Some(first_row) = rows.first() else {
+        return Ok(Vec::new());
+    };
+    let looks_like_url = |cell: &str| cell.contains("://") || cell.starts_with("www.");
+
+    let column = column.map(str::trim).filter(|column| !column.is_empty());
+    let (index, has_header) = match column {
+        Some(column) => match column.parse::<usize>() {
+            Ok(0) => return Err("CSV column numbers start at 1".to_string()),
+            Ok(number) => (
+                number - 1,
+                first_row
+                    .get(number - 1)
+                    .is_some_and(|cell| !looks_like_url(cell)),
+            ),
+            Err(_) => {
+                let index = first_row
+                    .iter()
+                    .position(|cell| cell.trim().eq_ignore_ascii_case(column))
+                    .ok_or_else(|| format!("CSV column not found: {}", column))?;
+                (index, true)
+            }
+        },
+        None => {
+            let index = rows
+                .iter()
+                .find_map(|row| row.iter().position(|cell| looks_like_url(cell)))
+                .ok_or_else(|| "No column containing URLs was found in the CSV".to_string())?;
+            (
+                index,
+                !looks_like_url(first_row.get(index).map_or("", |s| s)),
+            )
+        }
+    };
+
+    Ok(rows
+        .iter()
+        .enumerate()
+        .map(|(i, row)| {
+            if i == 0 && has_header {
+                String::new()
+            } else {
+                row.get(index).map_or("", |cell| cell.trim()).to_string()
+            }
+        })
+        .collect())
+}
+
+// RFC 4180形式（引用符内の区切り文字・改行・「""」に対応）。区切り文字は1行目から判定
+// 行番号を合わせるため、引用符内で改行した行は空行を補う
+fn parse_csv(content: &str) -> Vec<Vec<String>> {
+    let first_line = content.lines().next().unwrap_or_default();
+    // 同数なら後のものが選ばれるため、カンマを最後に置く
+    let delimiter = [';', '\t', ',']
+        .into_iter()
+        .max_by_key(|delimiter| first_line.matches(*delimiter).count())
+        .unwrap_or(',');
+
+    let mut rows = Vec::new();
+    let mut row = Vec::new();
+    let mut cell = String::new();
+    let mut in_quotes = false;
+    let mut extra_lines = 0;
+    let mut chars = content.chars().peekable();
+    while let Some(ch) = chars.next() {
+        match ch {
+            '"' if in_quotes && chars.peek() == Some(&'"') => {
+                cell.push('"');
+                chars.next();
+            }
+            '"' => in_quotes = !in_quotes,
+            '\n' if in_quotes => {
+                cell.push(' ');
+                extra_lines += 1;
+            }
+            ch if ch == delimiter && !in_quotes => row.push(std::mem::take(&mut cell)),
+            '\r' if !in_quotes => {}
+            '\n' => {
+                row.push(std::mem::take(&mut cell));
+                rows.push(std::mem::take(&mut row));
+                for _ in 0..extra_lines {
+                    rows.push(Vec::new());
+                }
+                extra_lines = 0;
+            }
+            ch => cell.push(ch),
+        }
+    }
+    if !cell.is_empty() || !row.is_empty() {
+        row.push(cell);
+        rows.push(row);
+    }
+    rows
+}
+
+// 文字列の配列、URLのキーを持つオブジェクトの配列、またはそれらを「urls」などのキーに持つオブジェクト
+// 要素ごとのURL（なければ空）と、要素が始まるファイルの行番号を返す
+fn json_lines(content: &str) -> Result<Vec<(String, usize)>, String> {
+    let value: Value = serde_json::from_str(content).map_err(|e| format!("Invalid JSON: {}", e))?;
+    let (items, raw_items): (&Vec<Value>, Vec<&RawValue>) = match &value {
+        Value::Array(items) => (items, parse_raw(content)?),
+        Value::Object(object) => {
+            let key = JSON_LIST_KEYS
+                .iter()
+                .find(|key| object.get(**key).is_some_and(Value::is_array))
+                .ok_or_else(|| {
+                    format!(
+                        "JSON object has no URL list (expected one of: {})",
+                        JSON_LIST_KEYS.join(", ")
+                    )
+                })?;
+            let raw_object: HashMap<String, &RawValue> = parse_raw(content)?;
+            (
+                object[*key].as_array().unwrap(),
+                parse_raw(raw_object[*key].get())?,
+            )
+        }
+        _ => return Err("JSON must be an array or an object".to_string()),
+    };
+
+    Ok(items
+        .iter()
+        .zip(raw_items)
+        .map(|(item, raw)| {
+            let url = match item {
+                Value::String(url) => url.trim(),
+                Value::Object(object) => JSON_URL_KEYS
+                    .iter()
+                    .find_map(|key| object.get(*key).and_then(Value::as_str))
+                    .unwrap_or_default()
+                    .trim(),
+                _ => "",
+            };
+            // 生の値は元の文字列の一部を指すため、その位置から行番号を求める
+            let offset = raw.get().as_ptr() as usize - content.as_ptr() as usize;
+            (
+                // 改行を含む値で行番号がずれないようにする
+                url.replace(['\r', '\n'], " "),
+                content[..offset].matches('\n').count() + 1,
+            )
+        })
+        .collect())
+}
+
+fn parse_raw<'a, T: Deserialize<'a>>(content: &'a str) -> Result<T, String> {
+    serde_json::from_str(content).map_err(|e| format!("Invalid JSON: {}", e))
+}
+
+// ブラウザが書き出したNetscape形式のブックマーク（<A HREF="...">）
+// フォルダやjavascript:などのリンクは除き、元の行番号を保つ
+fn bookmark_lines(content: &str) -> Vec<String> {
+    content
+        .lines()
+        .map(|line| {
+            HREF_PATTERN
+                .captures_iter(line)
+                .map(|captures| captures[1].trim().to_string())
+                .filter(|href| {
+                    let href = href.to_lowercase();
+                    href.starts_with("http://") || href.starts_with("https://")
+                })
+                .collect::<Vec<_>>()
+                .join(" ")
+        })
+        .collect()
+}
+
+// ファイルからURLを取り込む（formatの指定がなければ拡張子から判定）
+// CSVのcolumnは見出しの名前または1から始まる列番号
+#[tauri::command]
+pub async fn import_url_file(
+    path: String,
+    format: Option<ImportFormat>,
+    column: Option<String>,
+) -> Result<UrlListResult, String> {
+    log::info!(
+        "Invoked import_url_file with path: {:?}, format: {:?}, column: {:?}",
+        path,
+        format,
+        column
+    );
+    let path = Path::new(&path);
+    let format = format.unwrap_or_else(|| ImportFormat::from_path(path));
+    let content = read_text(path)?;
+    let result = import_text(&content, format, column.as_deref())?;
+    log::info!(
+        "Imported {} URLs from {:?} ({} duplicates, {} invalid lines)",
+        result.urls.len(),
+        path,
+        result.duplicates.len(),
+        result.invalid.len()
+    );
+    Ok(result)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn import(content: &str, format: ImportFormat, column: Option<&str>) -> UrlListResult {
+        import_text(content, format, column).unwrap()
+    }
+
+    fn invalid_lines(result: &UrlListResult) -> Vec<(usize, &str)> {
+        result
+            .invalid
+            .iter()
+            .map(|invalid| (invalid.line, invalid.text.as_str()))
+            .collect()
+    }
+
+    fn duplicate_lines(result: &UrlListResult) -> Vec<usize> {
+        result
+            .duplicates
+            .iter()
+            .map(|duplicate| duplicate.line)
+            .collect()
+    }
+
+    #[test]
+    fn imports_text() {
+        let content = "https://example.com/a\n\n# comment\nnotaurl\nhttps://example.com/a\nhttps://example.com/b\n";
+        let result = import(content, ImportFormat::Text, None);
+        assert_eq!(
+            result.urls,
+            ["https://example.com/a", "https://example.com/b"]
+        );
+        assert_eq!(invalid_lines(&result), [(4, "notaurl")]);
+        assert_eq!(duplicate_lines(&result), [5]);
+    }
+
+    #[test]
+    fn imports_csv_column_by_header_name() {
+        let content = "title,link\nA,https://example.com/a\nB,notaurl\nC,https://example.com/a\n";
+        let result = import(content, ImportFormat::Csv, Some("Link"));
+        assert_eq!(result.urls, ["https://example.com/a"]);
+        assert_eq!(invalid_lines(&result), [(3, "notaurl")]);
+        assert_eq!(duplicate_lines(&result), [4]);
+
+        assert!(import_text(content, ImportFormat::Csv, Some("url")).is_err());
+    }
+
+    #[test]
+    fn imports_csv_column_by_number() {
+        // 見出しがあれば除き、なければ1行目から取り込む
+        let content = "name,url\nA,https://example.com/a\nB,https://example.com/b\n";
+        let result = import(content, ImportFormat::Csv, Some("2"));
+        assert_eq!(
+            result.urls,
+            ["https://example.com/a", "https://example.com/b"]
+        );
+        assert!(result.invalid.is_empty());
+
+        let content = "https://example.com/x,https://example.com/a\nhttps://example.com/y,https://example.com/b\n";
+        let result = import(content, ImportFormat::Csv, Some("2"));
+        assert_eq!(
+            result.urls,
+            ["https://example.com/a", "https://example.com/b"]
+        );
+
+        assert!(import_text(content, ImportFormat::Csv, Some("0")).is_err());
+    }
+
+    #[test]
+    fn imports_csv_with_quoted_delimiters_and_newlines() {
+        let content = "title,url\n\"Hello, world\",https://example.com/a\n\"multi\nline \"\"title\"\"\",https://example.com/b\nX,notaurl\n";
+        let result = import(content, ImportFormat::Csv, None);
+        assert_eq!(
+            result.urls,
+            ["https://example.com/a", "https://example.com/b"]
+        );
+        // 引用符内の改行があっても元のファイルの行番号になる
+        assert_eq!(invalid_lines(&result), [(5, "notaurl")]);
+    }
+
+    #[test]
+    fn detects_csv_delimiters() {
+        for content in [
+            "title;url\nA, B;https://example.com/a\n",
+            "title\turl\nA, B\thttps://example.com/a\n",
+        ] {
+            let result = import(content, ImportFormat::Csv, Some("url"));
+            assert_eq!(result.urls, ["https://example.com/a"], "{:?}", content);
+        }
+    }
+
+    #[test]
+    fn imports_json_array() {
+        let content =
+            "[\n  \"https://example.com/a\",\n\n  \"notaurl\",\n  \"https://example.com/a\"\n]\n";
+        let result = import(content, ImportFormat::Json, None);
+        assert_eq!(result.urls, ["https://example.com/a"]);
+        assert_eq!(invalid_lines(&result), [(4, "notaurl")]);
+        assert_eq!(duplicate_lines(&result), [5]);
+
+        // 1行に書かれた配列は全て1行目
+        let content = r#"["https://example.com/a", "notaurl", "https://example.com/a"]"#;
+        let result = import(content, ImportFormat::Json, None);
+        assert_eq!(invalid_lines(&result), [(1, "notaurl")]);
+        assert_eq!(duplicate_lines(&result), [1]);
+    }
+
+    #[test]
+    fn imports_json_object_list() {
+        let content = r#"[
+            {"title": "A", "url": "https://example.com/a"},
+            {"title": "B", "webpage_url": "https://example.com/b"},
+            {"title": "No URL"},
+            {
+                "title": "Duplicate",
+                "url": "https://example.com/a"
+            },
+            {"link": "https://example.com/c"}
+        ]"#;
+        let result = import(content, ImportFormat::Json, None);
+        assert_eq!(
+            result.urls,
+            [
+                "https://example.com/a",
+                "https://example.com/b",
+                "https://example.com/c"
+            ]
+        );
+        assert_eq!(duplicate_lines(&result), [5]);
+    }
+
+    #[test]
+    fn imports_json_url_list_object() {
+        let content = r#"{"name": "list", "urls": ["https://example.com/a", {"href": "https://example.com/b"}]}"#;
+        let result = import(content, ImportFormat::Json, None);
+        assert_eq!(
+            result.urls,
+            ["https://example.com/a", "https://example.com/b"]
+        );
+
+        assert!(import_text(r#"{"name": "list"}"#, ImportFormat::Json, None).is_err());
+        assert!(import_text("not json", ImportFormat::Json, None).is_err());
+    }
+
+    #[test]
+    fn imports_netscape_bookmarks() {
+        let content = r#"<!DOCTYPE NETSCAPE-Bookmark-file-1>
+<META HTTP-EQUIV="Content-Type" CONTENT="text/html; charset=UTF-8">
+<TITLE>Bookmarks</TITLE>
+<H1>Bookmarks</H1>
+<DL><p>
+    <DT><H3 ADD_DATE="1700000000">Videos</H3>
+    <DL><p>
+        <DT><A HREF="https://www.youtube.com/watch?v=dQw4w9WgXcQ" ADD_DATE="1700000000">Video</A>
+        <DT><A HREF="javascript:alert(1)">Bookmarklet</A>
+        <DT><A HREF="https://youtu.be/dQw4w9WgXcQ">Short link</A>
+        <DT><A HREF="https://vimeo.com/76979871" ICON="data:image/png;base64,AAAA">Vimeo</A>
+    </DL><p>
+</DL><p>
+"#;
+        let result = import(content, ImportFormat::Bookmarks, None);
+        assert_eq!(
+            result.urls,
+            [
+                "https://www.youtube.com/watch?v=dQw4w9WgXcQ",
+                "https://vimeo.com/76979871"
+            ]
+        );
+        assert!(result.invalid.is_empty());
+        assert_eq!(duplicate_lines(&result), [10]);
+    }
+
+    #[test]
+    fn imports_m3u() {
+        let content = "#EXTM3U\n#EXTINF:123,Artist - Title\nhttps://example.com/a.mp4\n#EXTINF:-1,Other\nhttps://example.com/b.mp4\nnotaurl\n";
+        let result = import(content, ImportFormat::M3u, None);
+        assert_eq!(
+            result.urls,
+            ["https://example.com/a.mp4", "https://example.com/b.mp4"]
+        );
+        assert_eq!(invalid_lines(&result), [(6, "notaurl")]);
+    }
+
+    #[test]
+    fn detects_format_from_extension() {
+        for (path, format) in [
+            ("list.txt", ImportFormat::Text),
+            ("list.CSV", ImportFormat::Csv),
+            ("list.tsv", ImportFormat::Csv),
+            ("list.json", ImportFormat::Json),
+            ("bookmarks.html", ImportFormat::Bookmarks),
+            ("playlist.m3u8", ImportFormat::M3u),
+            ("list", ImportFormat::Text),
+        ] {
+            assert_eq!(ImportFormat::from_path(Path::new(path)), format);
+        }
+    }
+}
@@ -0,0 +1,280 @@
+use crate::bandwidth::BandwidthBudget;
+use crate::engine::EventSink;
+use crate::throttle::DomainThrottle;
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use std::sync::{Arc, Mutex};
+use tokio::sync::broadcast;
+
+// 一覧に残す終了済みジョブの数
+const MAX_FINISHED_JOBS: usize = 50;
+// 購読者が受け取りきれずに破棄されるまでに溜めておくイベントの数
+const EVENT_CAPACITY: usize = 1024;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum JobStatus {
+    // ドメインの実行枠待ち
+    Waiting,
+    Running,
+    // 空き容量の不足、またはユーザーの一時停止で待っている
+    Paused,
+    Completed,
+    Failed,
+    Cancelled,
+}
+
+impl JobStatus {
+    pub fn is_finished(self) -> bool {
+        matches!(
+            self,
+            JobStatus::Completed | JobStatus::Failed | JobStatus::Cancelled
+        )
+    }
+}
+
+// 一時停止の方法
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum PauseMode {
+    // 実行中のプロセスを止めたままにする（Unixのみ）
+    Suspend,
+    // プロセスを終了し、再開時に--continueで途中のファイルから続ける
+    Restart,
+}
+
+impl Default for PauseMode {
+    fn default() -> Self {
+        if cfg!(unix) {
+            PauseMode::Suspend
+        } else {
+            PauseMode::Restart
+        }
+    }
+}
+
+// job-updatedイベントとlist_jobsで返すジョブの情報
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct JobInfo {
+    pub id: String,
+    pub profile: Option<String>,
+    pub url_count: usize,
+    pub domains: Vec<String>,
+    pub status: JobStatus,
+    pub current_domain: Option<String>,
+    pub attempt: u32,
+    pub started_at: String,
+    pub finished_at: Option<String>,
+    // 実行できずに終了した場合の理由
+    pub error: Option<String>,
+    // ユーザーが一時停止している場合の方法
+    pub pause_mode: Option<PauseMode>,
+    // 保存先の空き容量が不足して止まっているかどうか（ユーザーの一時停止とは別に解除される）
+    pub low_disk_space: bool,
+}
+
+// ジョブIDを付けたイベント（ローカルAPIのServer-Sent Eventsで配信）
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct JobEvent {
+    pub job_id: String,
+    pub event: String,
+    pub payload: serde_json::Value,
+}
+
+// 実行中・終了済みのジョブと、全ジョブで共有する流量制御・帯域の上限を管理
+pub struct JobManager {
+    jobs: Mutex<HashMap<String, JobInfo>>,
+    // 中止が要求されたジョブ
+    cancelled: Mutex<HashSet<String>>,
+    // ユーザーが一時停止を要求したジョブ
+    paused: Mutex<HashMap<String, PauseMode>>,
+    // 空き容量の不足で止めているジョブ（ユーザーの再開では解除しない）
+    low_disk: Mutex<HashSet<String>>,
+    pub throttle: Arc<DomainThrottle>,
+    pub bandwidth: Arc<BandwidthBudget>,
+    pub events: broadcast::Sender<JobEvent>,
+}
+
+impl Default for JobManager {
+    fn default() -> Self {
+        JobManager {
+            jobs: Mutex::default(),
+            cancelled: Mutex::default(),
+            paused: Mutex::default(),
+            low_disk: Mutex::default(),
+            throttle: Arc::default(),
+            bandwidth: Arc::default(),
+            events: broadcast::channel(EVENT_CAPACITY).0,
+        }
+    }
+}
+
+impl JobManager {
+    pub fn register(&self, info: JobInfo) {
+        self.jobs.lock().unwrap().insert(info.id.clone(), info);
+    }
+
+    // ジョブの情報を更新し、更新後の内容をリターン
+    pub fn update<F>(&self, id: &str, f: F) -> Option<JobInfo>
+    where
+        F: FnOnce(&mut JobInfo),
+    {
+        let mut jobs = self.jobs.lock().unwrap();
+        let info = jobs.get_mut(id)?;
+        f(info);
+        let updated = info.clone();
+
+        if updated.status.is_finished() {
+            self.cancelled.lock().unwrap().remove(id);
+            self.paused.lock().unwrap().remove(id);
+            self.low_disk.lock().unwrap().remove(id);
+            prune_finished(&mut jobs);
+        }
+        Some(updated)
+    }
+
+    pub fn get(&self, id: &str) -> Option<JobInfo> {
+        self.jobs.lock().unwrap().get(id).cloned()
+    }
+
+    // 中止を要求（実行中のプロセスは終了させ、以降のドメイン・リトライは実行しない）
+    pub fn cancel(&self, id: &str) -> Result<(), String> {
+        let jobs = self.jobs.lock().unwrap();
+        match jobs.get(id) {
+            None => Err(format!("Job not found: {}", id)),
+            Some(job) if job.status.is_finished() => {
+                Err(format!("Job {} has already finished", id))
+            }
+            Some(_) => {
+                self.cancelled.lock().unwrap().insert(id.to_string());
+                Ok(())
+            }
+        }
+    }
+
+    pub fn is_cancelled(&self, id: &str) -> bool {
+        self.cancelled.lock().unwrap().contains(id)
+    }
+
+    // 一時停止を要求（実行中のプロセスは次の確認時に止める）
+    pub fn pause(&self, id: &str, mode: PauseMode) -> Result<(), String> {
+        if mode == PauseMode::Suspend && !cfg!(unix) {
+            return Err("Suspending processes is only supported on Unix".to_string());
+        }
+        let jobs = self.jobs.lock().unwrap();
+        match jobs.get(id) {
+            None => Err(format!("Job not found: {}", id)),
+            Some(job) if job.status.is_finished() => {
+                Err(format!("Job {} has already finished", id))
+            }
+            Some(_) => {
+                self.paused.lock().unwrap().insert(id.to_string(), mode);
+                Ok(())
+            }
+        }
+    }
+
+    pub fn resume(&self, id: &str) -> Result<(), String> {
+        if self.paused.lock().unwrap().remove(id).is_some() {
+            return Ok(());
+        }
+        match self.jobs.lock().unwrap().get(id) {
+            None => Err(format!("Job not found: {}", id)),
+            Some(_) => Err(format!("Job {} is not paused", id)),
+        }
+    }
+
+    // ユーザーが一時停止している場合の方法
+    pub fn pause_mode(&self, id: &str) -> Option<PauseMode> {
+        self.paused.lock().unwrap().get(id).copied()
+    }
+
+    // 空き容量の不足による停止を設定・解除
+    pub fn set_low_disk(&self, id: &str, low: bool) {
+        let mut low_disk = self.low_disk.lock().unwrap();
+        if low {
+            low_disk.insert(id.to_string());
+        } else {
+            low_disk.remove(id);
+        }
+    }
+
+    pub fn is_low_disk(&self, id: &str) -> bool {
+        self.low_disk.lock().unwrap().contains(id)
+    }
+
+    // ジョブを止めておく方法（ユーザーの一時停止と空き容量の不足のどちらかがあれば止める）
+    // 空き容量の不足だけの場合は既定の方法で止める
+    pub fn hold_mode(&self, id: &str) -> Option<PauseMode> {
+        self.pause_mode(id)
+            .or_else(|| self.is_low_disk(id).then(PauseMode::default))
+    }
+
+    pub fn list(&self) -> Vec<JobInfo> {
+        let mut jobs: Vec<JobInfo> = self.jobs.lock().unwrap().values().cloned().collect();
+        jobs.sort_by(|a, b| a.started_at.cmp(&b.started_at).then(a.id.cmp(&b.id)));
+        jobs
+    }
+}
+
+// ジョブの情報を更新し、job-updatedイベントで通知
+pub fn update_and_emit<F>(jobs: &JobManager, events: &dyn EventSink, id: &str, f: F)
+where
+    F: FnOnce(&mut JobInfo),
+{
+    if let Some(info) = jobs.update(id, f) {
+        if let Ok(payload) = serde_json::to_value(&info) {
+            events.send("job-updated", payload);
+        }
+    }
+}
+
+// 古い終了済みジョブを一覧から削除
+fn prune_finished(jobs: &mut HashMap<String, JobInfo>) {
+    let mut finished: Vec<(String, String)> = jobs
+        .values()
+        .filter(|job| job.status.is_finished())
+        .map(|job| (job.finished_at.clone().unwrap_or_default(), job.id.clone()))
+        .collect();
+    if finished.len() <= MAX_FINISHED_JOBS {
+        return;
+    }
+
+    finished.sort();
+    for (_, id) in finished.iter().take(finished.len() - MAX_FINISHED_JOBS) {
+        jobs.remove(id);
+    }
+}
+
+// ジョブ一覧（開始順）
+#[tauri::command]
+pub async fn list_jobs(jobs: tauri::State<'_, JobManager>) -> Result<Vec<JobInfo>, String> {
+    Ok(jobs.list())
+}
+
+// ジョブを中止
+#[tauri::command]
+pub async fn cancel_job(id: String, jobs: tauri::State<'_, JobManager>) -> Result<(), String> {
+    log::info!("Invoked cancel_job with id: {:?}", id);
+    jobs.cancel(&id)
+}
+
+// ジョブを一時停止（modeの指定がなければUnixではプロセスを止め、それ以外では終了させる）
+#[tauri::command]
+pub async fn pause_job(
+    id: String,
+    mode: Option<PauseMode>,
+    jobs: tauri::State<'_, JobManager>,
+) -> Result<(), String> {
+    log::info!("Invoked pause_job with id: {:?}, mode: {:?}", id, mode);
+    jobs.pause(&id, mode.unwrap_or_default())
+}
+
+// 一時停止したジョブを再開
+#[tauri::command]
+pub async fn resume_job(id: String, jobs: tauri::State<'_, JobManager>) -> Result<(), String> {
+    log::info!("Invoked resume_job with id: {:?}", id);
+    jobs.resume(&id)
+}
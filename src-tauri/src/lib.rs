@@ -1,86 +1,163 @@
-use chardetng::EncodingDetector;
-use reqwest;
-use serde_json;
 use shlex;
-use std::fs;
-use std::io::Read;
-use std::io::Write;
 use std::path::PathBuf;
-use std::process::{Command, Stdio};
-use tauri::Emitter;
-use time::{format_description::well_known::Rfc3339, OffsetDateTime};
+use std::process::Command;
+use std::sync::Arc;
+use tauri::Manager;
+
+mod api;
+mod archive;
+mod bandwidth;
+mod clipboard;
+mod cookies;
+mod diagnostics;
+mod disk;
+mod engine;
+mod headless;
+mod history;
+mod hooks;
+mod import;
+mod jobs;
+mod paths;
+mod playlist;
+mod probe;
+mod profiles;
+mod redact;
+mod report;
+mod retry;
+mod scheduler;
+mod settings;
+mod subscriptions;
+mod template;
+mod throttle;
+mod urls;
+mod vault;
 
 // Tauriのエントリポイント
 #[cfg_attr(mobile, tauri::mobile_entry_point)]
 pub fn run() {
+    // 現地時刻のオフセットは、Unixではスレッドを起動する前にしか取得できない
+    history::init_local_offset();
     // env_logger::init();
-    env_logger::Builder::from_env(env_logger::Env::default().default_filter_or("debug")).init();
+    // 資格情報ストアのパスワードなどをログに出さないため、伏せ字処理を挟む
+    redact::RedactingLogger::init(
+        env_logger::Builder::from_env(env_logger::Env::default().default_filter_or("debug"))
+            .build(),
+    );
     tauri::Builder::default()
+        .manage(jobs::JobManager::default())
+        .manage(vault::Vault::default())
+        .manage(playlist::PlaylistCache::default())
+        .manage(clipboard::ClipboardWatcher::default())
+        .manage(api::ApiServer::default())
+        .setup(|app| {
+            // 保存先を決定してからログファイルと設定を用意し、流量制御・帯域の上限の設定を反映
+            let app_paths = paths::init(app)?;
+            redact::set_log_file(&app_paths.log_dir);
+            let config_dir = &app_paths.config_dir;
+            let store = settings::SettingsStore::load(config_dir);
+            app.state::<jobs::JobManager>()
+                .throttle
+                .set_config(store.get().throttle);
+            app.state::<jobs::JobManager>()
+                .bandwidth
+                .set_config(store.get().bandwidth);
+            // クリップボードの監視は設定で有効な場合だけ開始
+            app.state::<clipboard::ClipboardWatcher>()
+                .apply(app.handle(), &store.get().clipboard);
+            app.state::<api::ApiServer>()
+                .apply(app.handle(), &store.get().api);
+            app.manage(store);
+            app.manage(profiles::ProfileStore::load(config_dir));
+            // 保存済みのスケジュールの次の実行時刻を計算し直してから待機を開始
+            app.manage(scheduler::Scheduler::load(
+                &app_paths.data_dir,
+                Arc::new(scheduler::SystemClock),
+            ));
+            scheduler::start(app.handle().clone());
+            app.manage(subscriptions::SubscriptionStore::load(
+                &app_paths.data_dir,
+                Arc::new(scheduler::SystemClock),
+            ));
+            subscriptions::start(app.handle().clone());
+            Ok(())
+        })
         .plugin(tauri_plugin_dialog::init())
+        .plugin(tauri_plugin_notification::init())
         .plugin(tauri_plugin_opener::init())
         .invoke_handler(tauri::generate_handler![
             check_ffmpeg_ffprobe_version,
             download_latest_yt_dlp,
             run_yt_dlp,
             write_urls_to_file,
+            api::get_api_status,
+            api::regenerate_api_token,
+            archive::list_archive_entries,
+            archive::import_archive,
+            archive::export_archive,
+            archive::delete_archive_entries,
+            archive::check_archive,
+            clipboard::set_clipboard_watch,
+            clipboard::get_pending_urls,
+            clipboard::clear_pending_urls,
+            cookies::import_cookies,
+            cookies::list_cookie_sets,
+            cookies::delete_cookie_set,
+            history::query_history,
+            history::get_history_entry,
+            history::rerun_history_entry,
+            import::import_url_file,
+            settings::get_settings,
+            settings::update_settings,
+            subscriptions::list_subscriptions,
+            subscriptions::save_subscription,
+            subscriptions::delete_subscription,
+            subscriptions::sync_subscription,
+            template::validate_output_template,
+            template::preview_output_template,
+            jobs::list_jobs,
+            jobs::cancel_job,
+            jobs::pause_job,
+            jobs::resume_job,
+            paths::get_app_paths,
+            playlist::expand_playlist,
+            playlist::download_playlist_selection,
+            probe::probe_url,
+            profiles::list_profiles,
+            profiles::get_profile,
+            profiles::save_profile,
+            profiles::delete_profile,
+            profiles::export_profiles,
+            profiles::import_profiles,
+            scheduler::list_schedules,
+            scheduler::save_schedule,
+            scheduler::delete_schedule,
+            throttle::get_throttle_status,
+            throttle::get_throttle_config,
+            throttle::set_throttle_config,
+            urls::parse_urls,
+            vault::unlock_vault,
+            vault::lock_vault,
+            vault::get_vault_status,
+            vault::list_credentials,
+            vault::set_credential,
+            vault::delete_credential,
         ])
         .run(tauri::generate_context!())
         .expect("Failed to run Tauri application");
 }
 
+// ヘッドレスモードのエントリポイント（ウィンドウを開かずに実行し、終了コードをリターン）
+pub fn run_headless(args: &[String]) -> i32 {
+    history::init_local_offset();
+    headless::run(args)
+}
+
 // URLsをファイルに書き込み、ファイルパスをリターン
 #[tauri::command]
 async fn write_urls_to_file(urls: String) -> Result<String, String> {
     log::info!("Invoked write_urls_to_file with urls: {:?}", urls);
-
-    // カレントディレクトリを取得
-    let current_dir = std::env::current_dir().map_err(|e| {
-        log::error!("Could not get current directory: {}", e);
-        format!("Could not get current directory: {}", e)
-    })?;
-
-    // yt-dlpディレクトリを使用（release-time.txt、last-check-time.txtと同じ場所）
-    let save_dir = current_dir.join("yt-dlp");
-    fs::create_dir_all(&save_dir).map_err(|e| {
-        log::error!("Could not create yt-dlp directory: {}", e);
-        format!("Could not create yt-dlp directory: {}", e)
-    })?;
-
-    // 固定のファイル名を使用（上書き）
-    let urls_file = save_dir.join("url-list.txt");
-
-    // URLsを改行で分割し、空行を除去してファイルに書き込み
-    let cleaned_urls: Vec<&str> = urls
-        .lines()
-        .map(|line| line.trim())
-        .filter(|line| !line.is_empty())
-        .collect();
-
-    if cleaned_urls.is_empty() {
-        log::error!("No valid URLs provided");
-        return Err("No valid URLs provided".to_string());
-    }
-
-    let mut file = fs::File::create(&urls_file).map_err(|e| {
-        log::error!("Could not create URLs file: {}", e);
-        format!("Could not create URLs file: {}", e)
-    })?;
-
-    for url in &cleaned_urls {
-        writeln!(file, "{}", url).map_err(|e| {
-            log::error!("Failed to write URL to file: {}", e);
-            format!("Failed to write URL to file: {}", e)
-        })?;
-    }
-
-    let file_path = urls_file.to_string_lossy().to_string();
-    log::info!(
-        "URLs file created/updated: {:?} with {} URLs",
-        file_path,
-        cleaned_urls.len()
-    );
-
-    Ok(file_path)
+    let urls_file = engine::write_url_list(&urls)?;
+    Ok(urls_file.to_string_lossy().to_string())
 }
 
 // ffmpegとffprobeのバージョンを確認するコマンド
@@ -162,436 +239,31 @@ async fn check_ffmpeg_ffprobe_version(dir: String) -> Result<String, String> {
 #[tauri::command]
 async fn download_latest_yt_dlp() -> Result<String, String> {
     log::info!("Starting download_latest_yt_dlp");
-
-    // カレントディレクトリを取得
-    let current_dir = std::env::current_dir().map_err(|e| {
-        log::error!("Could not get current directory: {}", e);
-        format!("Could not get current directory: {}", e)
-    })?;
-
-    // パス作成＋ディレクトリ作成
-    let save_dir = current_dir.join("yt-dlp");
-    fs::create_dir_all(&save_dir).map_err(|e| {
-        log::error!("Could not create save directory: {}", e);
-        format!("Could not create save directory: {}", e)
-    })?;
-    let asset_name = match std::env::consts::OS {
-        "windows" => "yt-dlp.exe",
-        "macos" => "yt-dlp_macos",
-        "linux" => "yt-dlp_linux",
-        other => {
-            log::error!("Unsupported OS: {}", other);
-            return Err(format!("Unsupported OS: {}", other));
-        }
-    };
-    let yt_dlp_file = save_dir.join(asset_name);
-    let release_time_file = save_dir.join("release-time.txt");
-    let last_check_file = save_dir.join("last-check-time.txt");
-
-    // 最後の確認時間をチェック
-    let last_check_time = fs::read_to_string(&last_check_file)
-        .ok()
-        .and_then(|s| OffsetDateTime::parse(&s.trim(), &Rfc3339).ok());
-    
-    if let Some(last_check) = last_check_time {
-        let now = OffsetDateTime::now_utc();
-        let one_hour = time::Duration::hours(1);
-        
-        if now - last_check < one_hour {
-            log::info!("Last check was less than 1 hour ago, skipping server check");
-            return Ok("yt-dlp is up to date (last checked less than 1 hour ago)".to_string());
-        }
-    }
-
-    // 既存のyt-dlpのバージョンを確認
-    let yt_dlp_version_output = {
-        let mut cmd = Command::new(&yt_dlp_file);
-        #[cfg(windows)] // window hideのための設定
-        {
-            use std::os::windows::process::CommandExt;
-            cmd.creation_flags(0x08000000);
-        }
-        match cmd.arg("--version").output() {
-            Ok(output) => {
-                log::info!(
-                    "Successfully retrieved existing yt-dlp version: {:?}",
-                    output
-                );
-                Some(output)
-            }
-            Err(e) => {
-                log::warn!("Could not get existing yt-dlp version: {}", e);
-                None
-            }
-        }
-    };
-
-    // HTTPクライアントの初期化
-    let client = reqwest::Client::new();
-
-    // GitHub APIから最新のリリース時間を取得
-    let api_url = "https://api.github.com/repos/yt-dlp/yt-dlp-nightly-builds/releases/latest";
-    let response = client
-        .get(api_url)
-        .header("User-Agent", "TakumiVidDl")
-        .send()
-        .await
-        .map_err(|e| {
-            log::error!("Failed to fetch release info from GitHub: {}", e);
-            format!("Failed to fetch release info from GitHub: {}", e)
-        })?;
-    response.error_for_status_ref().map_err(|e| {
-        log::error!("GitHub API returned error status: {}", e);
-        e.to_string()
-    })?;
-    let release_info: serde_json::Value = response.json().await.map_err(|e| {
-        log::error!("Failed to parse GitHub API JSON: {}", e);
-        e.to_string()
-    })?;
-    let github_latest_release_time = match release_info.get("published_at") {
-        Some(val) => val,
-        None => {
-            log::error!("Could not find published_at in release info");
-            return Err("Could not find published_at in release info".to_string());
-        }
-    };
-
-    // local_binary_release_timeとgithub_latest_release_timeを比較して更新が必要か確認
-    let local_binary_release_time = fs::read_to_string(&release_time_file).ok();
-    let local = local_binary_release_time
-        .as_deref()
-        .and_then(|s| OffsetDateTime::parse(s.trim(), &Rfc3339).ok());
-    let github = github_latest_release_time
-        .as_str()
-        .and_then(|s| OffsetDateTime::parse(s, &Rfc3339).ok());
-    if let (Some(local), Some(github)) = (local, github) {
-        if local >= github && yt_dlp_version_output.is_some() {
-            log::info!("yt-dlp is already up to date");
-            
-            // サーバーチェック完了時刻を記録（最新版確認済み）
-            let current_time = OffsetDateTime::now_utc();
-            let current_time_str = current_time.format(&Rfc3339).map_err(|e| {
-                log::error!("Failed to format current time: {}", e);
-                format!("Failed to format current time: {}", e)
-            })?;
-            
-            let mut check_file = fs::File::create(&last_check_file).map_err(|e| {
-                log::error!("Could not create last-check-time.txt: {}", e);
-                format!("Could not create last-check-time.txt: {}", e)
-            })?;
-            check_file
-                .write_all(current_time_str.as_bytes())
-                .map_err(|e| {
-                log::error!("Failed to write last-check-time.txt: {}", e);
-                format!("Failed to write last-check-time.txt: {}", e)
-            })?;
-            
-            return Ok("yt-dlp is already up to date.".to_string());
-        }
-    }
-
-    // 最新のyt-dlpダウンロードURLを取得
-    let assets = release_info
-        .get("assets")
-        .and_then(|a| a.as_array())
-        .ok_or_else(|| {
-            log::error!("No assets found in release info");
-            "No assets found in release info".to_string()
-        })?;
-    let mut download_url: Option<String> = None;
-    for asset in assets {
-        if asset.get("name").and_then(|n| n.as_str()) == Some(asset_name) {
-            download_url = asset
-                .get("browser_download_url")
-                .and_then(|u| u.as_str())
-                .map(|s| s.to_string());
-            break;
-        }
-    }
-    let download_url = download_url.ok_or_else(|| {
-        log::error!("Asset not found: {}", asset_name);
-        format!("Asset not found: {}", asset_name)
-    })?;
-    log::info!("yt-dlp download URL: {}", download_url);
-
-    // yt-dlpのダウンロード
-    let mut resp = client
-        .get(&download_url)
-        .header("User-Agent", "TakumiVidDl")
-        .send()
-        .await
-        .map_err(|e| {
-            log::error!("Failed to download yt-dlp: {}", e);
-            format!("Failed to download yt-dlp: {}", e)
-        })?;
-    let mut out = fs::File::create(&yt_dlp_file).map_err(|e| {
-        log::error!("Could not create yt-dlp file: {}", e);
-        format!("Could not create yt-dlp file: {}", e)
-    })?;
-    while let Some(chunk) = resp.chunk().await.map_err(|e| {
-        log::error!("Failed to read download chunk: {}", e);
-        format!("Failed to read download chunk: {}", e)
-    })? {
-        out.write_all(&chunk).map_err(|e| {
-            log::error!("Failed to write yt-dlp file: {}", e);
-            format!("Failed to write yt-dlp file: {}", e)
-        })?;
-    }
-
-    // ダウンロードが完了したら、実行権限を付与（Linux/Macのみ）
-    #[cfg(unix)]
-    {
-        use std::os::unix::fs::PermissionsExt;
-        let perms = fs::Permissions::from_mode(0o755);
-        fs::set_permissions(&yt_dlp_file, perms).map_err(|e| {
-            log::error!("Failed to set execute permission: {}", e);
-            format!("Failed to set execute permission: {}", e)
-        })?;
-    }
-
-    // release-time.txtにpublished_atを書き込む
-    let time = github_latest_release_time.as_str().ok_or_else(|| {
-        log::error!("published_at is not a string");
-        "published_at is not a string".to_string()
-    })?;
-    let mut file = fs::File::create(&release_time_file).map_err(|e| {
-        log::error!("Could not create release-time.txt: {}", e);
-        format!("Could not create release-time.txt: {}", e)
-    })?;
-    file.write_all(time.as_bytes()).map_err(|e| {
-        log::error!("Failed to write release-time.txt: {}", e);
-        format!("Failed to write release-time.txt: {}", e)
-    })?;
-
-    // 全処理が成功した場合のみlast-check-time.txtに現在時刻を書き込み
-    let current_time = OffsetDateTime::now_utc();
-    let current_time_str = current_time.format(&Rfc3339).map_err(|e| {
-        log::error!("Failed to format current time: {}", e);
-        format!("Failed to format current time: {}", e)
-    })?;
-    
-    let mut check_file = fs::File::create(&last_check_file).map_err(|e| {
-        log::error!("Could not create last-check-time.txt: {}", e);
-        format!("Could not create last-check-time.txt: {}", e)
-    })?;
-    check_file
-        .write_all(current_time_str.as_bytes())
-        .map_err(|e| {
-        log::error!("Failed to write last-check-time.txt: {}", e);
-        format!("Failed to write last-check-time.txt: {}", e)
-    })?;
-
-    log::info!("yt-dlp download completed: {:?}", yt_dlp_file);
-    Ok(format!("yt-dlp downloaded successfully: {:?}", yt_dlp_file))
+    engine::update_yt_dlp().await
 }
 
 // yt-dlpのコマンド（リアルタイム出力対応）
 #[tauri::command]
-async fn run_yt_dlp(command_line: String, window: tauri::Window) -> Result<String, String> {
-    log::info!("Invoked run_yt_dlp with command_line: {:?}", command_line);
-
-    // yt-dlpのパスを決定
-    let current_dir = std::env::current_dir().map_err(|e| {
-        log::error!("Failed to get current directory: {}", e);
-        e.to_string()
-    })?;
-    let yt_dlp_path = match std::env::consts::OS {
-        "windows" => current_dir.join("yt-dlp").join("yt-dlp.exe"),
-        "macos" => current_dir.join("yt-dlp").join("yt-dlp_macos"),
-        "linux" => current_dir.join("yt-dlp").join("yt-dlp_linux"),
-        other => {
-            log::error!("Unsupported OS: {}", other);
-            return Err(format!("Unsupported OS: {}", other));
-        }
-    };
-    log::info!("Using yt-dlp path: {:?}", yt_dlp_path);
-
-    // コマンドライン引数を安全にパース
+async fn run_yt_dlp(
+    command_line: String,
+    profile: Option<String>,
+    retry_policy: Option<retry::RetryPolicy>,
+    window: tauri::Window,
+) -> Result<report::BatchReport, String> {
+    // コマンドライン引数を安全にパース（ログにはパスワードなどを伏せた引数だけを出す）
     let args = shlex::split(&command_line)
         .ok_or_else(|| "Invalid command line syntax - failed to parse arguments".to_string())?;
-    log::info!("Parsed args: {:?}", args);
-
-    // 開始通知
-    let _ = window.emit("yt-dlp-started", ());
-
-    // 直接実行（シェルを使わない）
-    let mut cmd = Command::new(&yt_dlp_path);
-    cmd.args(&args);
-    cmd.stdout(Stdio::piped()).stderr(Stdio::piped());
-
-    #[cfg(windows)]
-    {
-        use std::os::windows::process::CommandExt;
-        cmd.creation_flags(0x08000000);
-    }
-
-    let mut child = cmd.spawn().map_err(|e| {
-        log::error!("Failed to run yt-dlp: {}", e);
-        let _ = window.emit("yt-dlp-error", format!("Failed to spawn yt-dlp: {}", e));
-        format!("Failed to run yt-dlp: {}", e)
-    })?;
-
-    // stdoutとstderrを取得
-    let stdout = child.stdout.take().unwrap();
-    let stderr = child.stderr.take().unwrap();
-
-    let window_stdout = window.clone();
-    let window_stderr = window.clone();
-
-    // stdoutを非同期で読み取り、キャリッジリターンを考慮してフロントエンドに送信
-    let stdout_task = tokio::spawn(async move {
-        let mut detector = EncodingDetector::new();
-        // confirmed_encodingは使わず、毎回detector.guessで判定する
-        let mut reader = stdout;
-        let mut buffer = [0; 4096];
-        let mut line_buffer = String::new();
-
-        loop {
-            match reader.read(&mut buffer) {
-                Ok(0) => break, // EOF
-                Ok(n) => {
-                    // 毎回検出器を更新
-                        detector.feed(&buffer[..n], false);
-
-                    // 毎回guessでエンコーディングを判定
-                    let encoding = detector.guess(None, true);
-                    let (cow, _, had_errors) = encoding.decode(&buffer[..n]);
-                    let chunk = if had_errors {
-                        // 判別失敗や壊れた部分があればUTF-8で再デコード（置換文字で埋める）
-                        String::from_utf8_lossy(&buffer[..n]).to_string()
-                    } else {
-                        cow.to_string()
-                    };
-                    for ch in chunk.chars() {
-                        match ch {
-                            '\n' => {
-                                let _ = window_stdout.emit(
-                                    "yt-dlp-stdout",
-                                    serde_json::json!({
-                                        "content": line_buffer.clone(),
-                                        "overwrite": false
-                                    }),
-                                );
-                                line_buffer.clear();
-                            }
-                            '\r' => {
-                                let _ = window_stdout.emit(
-                                    "yt-dlp-stdout",
-                                    serde_json::json!({
-                                        "content": line_buffer.clone(),
-                                        "overwrite": true
-                                    }),
-                                );
-                                line_buffer.clear();
-                            }
-                            _ => {
-                                line_buffer.push(ch);
-                            }
-                        }
-                    }
-                }
-                Err(_) => break,
-            }
-        }
-
-        // 最後に残った内容があれば送信
-        if !line_buffer.is_empty() {
-            let _ = window_stdout.emit(
-                "yt-dlp-stdout",
-                serde_json::json!({
-                    "content": line_buffer,
-                    "overwrite": false
-                }),
-            );
-        }
-    });
-
-    // stderrを非同期で読み取り、キャリッジリターンを考慮してフロントエンドに送信
-    let stderr_task = tokio::spawn(async move {
-        let mut detector = EncodingDetector::new();
-        // confirmed_encodingは使わず、毎回detector.guessで判定する
-        let mut reader = stderr;
-        let mut buffer = [0; 4096];
-        let mut line_buffer = String::new();
-
-        loop {
-            match reader.read(&mut buffer) {
-                Ok(0) => break, // EOF
-                Ok(n) => {
-                    // 毎回検出器を更新
-                        detector.feed(&buffer[..n], false);
-
-                    // 毎回guessでエンコーディングを判定
-                    let encoding = detector.guess(None, true);
-                    let (cow, _, had_errors) = encoding.decode(&buffer[..n]);
-                    let chunk = if had_errors {
-                        String::from_utf8_lossy(&buffer[..n]).to_string()
-                    } else {
-                        cow.to_string()
-                    };
-                    for ch in chunk.chars() {
-                        match ch {
-                            '\n' => {
-                                let _ = window_stderr.emit(
-                                    "yt-dlp-stderr",
-                                    serde_json::json!({
-                                        "content": line_buffer.clone(),
-                                        "overwrite": false
-                                    }),
-                                );
-                                line_buffer.clear();
-                            }
-                            '\r' => {
-                                let _ = window_stderr.emit(
-                                    "yt-dlp-stderr",
-                                    serde_json::json!({
-                                        "content": line_buffer.clone(),
-                                        "overwrite": true
-                                    }),
-                                );
-                                line_buffer.clear();
-                            }
-                            _ => {
-                                line_buffer.push(ch);
-                            }
-                        }
-                    }
-                }
-                Err(_) => break,
-            }
-        }
-
-        // 最後に残った内容があれば送信
-        if !line_buffer.is_empty() {
-            let _ = window_stderr.emit(
-                "yt-dlp-stderr",
-                serde_json::json!({
-                    "content": line_buffer,
-                    "overwrite": false
-                }),
-            );
-        }
-    });
-
-    // プロセスの完了を待機
-    let status = child.wait().map_err(|e| {
-        log::error!("Failed to wait for yt-dlp: {}", e);
-        let _ = window.emit("yt-dlp-error", format!("Failed to wait for yt-dlp: {}", e));
-        format!("Failed to wait for yt-dlp: {}", e)
-    })?;
-
-    // タスクの完了を待機
-    let _ = tokio::join!(stdout_task, stderr_task);
+    log::info!(
+        "Invoked run_yt_dlp with args: {:?}, profile: {:?}, retry_policy: {:?}",
+        redact::redact_args(&args),
+        profile,
+        retry_policy
+    );
 
-    if status.success() {
-        log::info!("yt-dlp executed successfully");
-        let _ = window.emit("yt-dlp-completed", "success");
-        Ok("yt-dlp completed successfully".to_string())
-    } else {
-        log::error!("yt-dlp failed with status: {}", status);
-        let _ = window.emit("yt-dlp-completed", "failed");
-        Err(format!("yt-dlp failed with status: {}", status))
-    }
+    // リトライポリシーの指定がなければ設定の値を使う
+    let context = engine::Context::from_window(&window);
+    let retry_policy = retry_policy.unwrap_or_else(|| context.settings.get().retry_policy);
+    engine::execute_yt_dlp(&context, args, profile, retry_policy).await
 }
 
 /*
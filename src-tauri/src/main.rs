@@ -2,5 +2,10 @@
 #![cfg_attr(not(debug_assertions), windows_subsystem = "windows")]
 
 fn main() {
+    // --headlessならウィンドウを開かずに実行（cronやCIから使う）
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    if args.first().is_some_and(|arg| arg == "--headless") {
+        std::process::exit(takumi_vid_dl_lib::run_headless(&args[1..]));
+    }
     takumi_vid_dl_lib::run()
 }
@@ -0,0 +1,257 @@
+use serde::Serialize;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
+use tauri::Manager;
+
+// 実行ファイルと同じ場所にこのファイルがあればポータブルモード
+const PORTABLE_MARKER: &str = "portable";
+// 保存先のディレクトリを明示的に指定する環境変数（指定があればポータブルモード扱い）
+const DATA_DIR_ENV: &str = "TAKUMI_VID_DL_DATA_DIR";
+// 以前のバージョンの保存先（カレントディレクトリ）から移行済みであることを示すファイル
+const MIGRATED_MARKER: &str = ".migrated-from-current-dir";
+// tauri.conf.jsonのidentifier（ヘッドレスモードの保存先をGUIと揃えるため）
+const APP_IDENTIFIER: &str = "com.takumi-vid-dl.app";
+
+// 旧保存先から移行するファイル（yt-dlp本体と更新確認の記録はbin_dir、それ以外はdata_dir）
+const LEGACY_BIN_FILES: &[&str] = &[
+    "yt-dlp.exe",
+    "yt-dlp_macos",
+    "yt-dlp_linux",
+    "release-time.txt",
+    "last-check-time.txt",
+];
+const LEGACY_DATA_FILES: &[&str] = &["history.jsonl", "vault.json", "archives", "cookies"];
+
+// アプリが管理するファイルの保存先
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AppPaths {
+    pub portable: bool,
+    // 履歴、アーカイブ、Cookie、資格情報ストア、yt-dlp本体
+    pub data_dir: PathBuf,
+    // 設定、プロファイル
+    pub config_dir: PathBuf,
+    // URLリストやバッチファイルなどの一時ファイル、yt-dlpの中間ファイル
+    pub cache_dir: PathBuf,
+    pub log_dir: PathBuf,
+}
+
+static APP_PATHS: OnceLock<AppPaths> = OnceLock::new();
+
+// 起動時に保存先を決定し、旧保存先からの移行を行う
+pub fn init(app: &tauri::App) -> Result<&'static AppPaths, String> {
+    let paths = match portable_root() {
+        Some(root) => portable_paths(&root),
+        None => {
+            let resolver = app.path();
+            let resolve = |dir: tauri::Result<PathBuf>, name: &str| {
+                dir.map_err(|e| {
+                    log::error!("Could not resolve app {} directory: {}", name, e);
+                    format!("Could not resolve app {} directory: {}", name, e)
+                })
+            };
+            AppPaths {
+                portable: false,
+                data_dir: resolve(resolver.app_data_dir(), "data")?,
+                config_dir: resolve(resolver.app_config_dir(), "config")?,
+                cache_dir: resolve(resolver.app_cache_dir(), "cache")?,
+                log_dir: resolve(resolver.app_log_dir(), "log")?,
+            }
+        }
+    };
+    setup(paths)
+}
+
+// ヘッドレスモード用（Tauriを起動しないため、Tauriと同じ規則でOSのディレクトリから決定）
+pub fn init_headless() -> Result<&'static AppPaths, String> {
+    let paths = match portable_root() {
+        Some(root) => portable_paths(&root),
+        None => {
+            let resolve = |dir: Option<PathBuf>, name: &str| {
+                dir.map(|dir| dir.join(APP_IDENTIFIER)).ok_or_else(|| {
+                    log::error!("Could not resolve app {} directory", name);
+                    format!("Could not resolve app {} directory", name)
+                })
+            };
+            // ログはTauriと同じくmacOSでは~/Library/Logs、それ以外はローカルのデータディレクトリのlogs
+            let log_dir = if cfg!(target_os = "macos") {
+                resolve(
+                    dirs::home_dir().map(|dir| dir.join("Library").join("Logs")),
+                    "log",
+                )?
+            } else {
+                resolve(dirs::data_local_dir(), "log")?.join("logs")
+            };
+            AppPaths {
+                portable: false,
+                data_dir: resolve(dirs::data_dir(), "data")?,
+                config_dir: resolve(dirs::config_dir(), "config")?,
+                cache_dir: resolve(dirs::cache_dir(), "cache")?,
+                log_dir,
+            }
+        }
+    };
+    setup(paths)
+}
+
+fn portable_paths(root: &Path) -> AppPaths {
+    AppPaths {
+        portable: true,
+        data_dir: root.join("data"),
+        config_dir: root.join("config"),
+        cache_dir: root.join("cache"),
+        log_dir: root.join("logs"),
+    }
+}
+
+fn setup(paths: AppPaths) -> Result<&'static AppPaths, String> {
+    log::info!("Using app paths: {:?}", paths);
+
+    for dir in [
+        &paths.data_dir,
+        &paths.config_dir,
+        &paths.cache_dir,
+        &paths.log_dir,
+    ] {
+        create_dir(dir)?;
+    }
+    migrate_legacy_dir(&paths);
+
+    Ok(APP_PATHS.get_or_init(|| paths))
+}
+
+pub fn get() -> Result<&'static AppPaths, String> {
+    APP_PATHS
+        .get()
+        .ok_or_else(|| "App paths are not initialized".to_string())
+}
+
+pub fn data_dir() -> Result<PathBuf, String> {
+    Ok(get()?.data_dir.clone())
+}
+
+pub fn cache_dir() -> Result<PathBuf, String> {
+    let dir = get()?.cache_dir.clone();
+    create_dir(&dir)?;
+    Ok(dir)
+}
+
+// yt-dlp本体と更新確認の記録を置くディレクトリ
+pub fn bin_dir() -> Result<PathBuf, String> {
+    let dir = data_dir()?.join("yt-dlp");
+    create_dir(&dir)?;
+    Ok(dir)
+}
+
+// OSに応じたyt-dlp本体のパス
+pub fn yt_dlp_path() -> Result<PathBuf, String> {
+    let name = match std::env::consts::OS {
+        "windows" => "yt-dlp.exe",
+        "macos" => "yt-dlp_macos",
+        "linux" => "yt-dlp_linux",
+        other => {
+            log::error!("Unsupported OS: {}", other);
+            return Err(format!("Unsupported OS: {}", other));
+        }
+    };
+    Ok(bin_dir()?.join(name))
+}
+
+fn create_dir(dir: &Path) -> Result<(), String> {
+    fs::create_dir_all(dir).map_err(|e| {
+        log::error!("Could not create directory {:?}: {}", dir, e);
+        format!("Could not create directory {:?}: {}", dir, e)
+    })
+}
+
+// ポータブルモードの保存先（環境変数の指定、または実行ファイルの横のマーカー）
+fn portable_root() -> Option<PathBuf> {
+    if let Some(dir) = std::env::var_os(DATA_DIR_ENV).filter(|dir| !dir.is_empty()) {
+        return Some(PathBuf::from(dir));
+    }
+    let exe_dir = std::env::current_exe().ok()?.parent()?.to_path_buf();
+    exe_dir
+        .join(PORTABLE_MARKER)
+        .exists()
+        .then(|| exe_dir.join("portable-data"))
+}
+
+// カレントディレクトリのyt-dlpフォルダ（以前の保存先）の内容を1度だけ移行
+// 移行先に同名のファイルがあれば上書きせず、旧フォルダはそのまま残す
+fn migrate_legacy_dir(paths: &AppPaths) {
+    let marker = paths.data_dir.join(MIGRATED_MARKER);
+    if marker.exists() {
+        return;
+    }
+    let Some(legacy_dir) = std::env::current_dir()
+        .ok()
+        .map(|dir| dir.join("yt-dlp"))
+        .filter(|dir| dir.is_dir())
+    else {
+        return;
+    };
+
+    log::info!("Migrating app files from {:?}", legacy_dir);
+    let bin_dir = paths.data_dir.join("yt-dlp");
+    let targets = LEGACY_BIN_FILES
+        .iter()
+        .map(|name| (name, bin_dir.join(name)))
+        .chain(
+            LEGACY_DATA_FILES
+                .iter()
+                .map(|name| (name, paths.data_dir.join(name))),
+        );
+    let mut failed = false;
+    for (name, target) in targets {
+        let source = legacy_dir.join(name);
+        if !source.exists() {
+            continue;
+        }
+        if target.exists() {
+            log::warn!(
+                "Skipping migration of {:?}: {:?} already exists",
+                source,
+                target
+            );
+            continue;
+        }
+        match copy_recursive(&source, &target) {
+            Ok(()) => log::info!("Migrated {:?} to {:?}", source, target),
+            Err(e) => {
+                log::error!("Failed to migrate {:?}: {}", source, e);
+                failed = true;
+            }
+        }
+    }
+
+    // 失敗した場合は次回の起動時に再試行する
+    if !failed {
+        if let Err(e) = fs::write(&marker, legacy_dir.to_string_lossy().as_bytes()) {
+            log::warn!("Failed to write migration marker: {}", e);
+        }
+    }
+}
+
+// 別のドライブへの移行もあるため、移動ではなくコピーする（権限も引き継がれる）
+fn copy_recursive(source: &Path, target: &Path) -> std::io::Result<()> {
+    if source.is_dir() {
+        fs::create_dir_all(target)?;
+        for entry in fs::read_dir(source)? {
+            let entry = entry?;
+            copy_recursive(&entry.path(), &target.join(entry.file_name()))?;
+        }
+        Ok(())
+    } else {
+        if let Some(parent) = target.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        fs::copy(source, target).map(|_| ())
+    }
+}
+
+// 現在の保存先（設定画面での表示用）
+#[tauri::command]
+pub async fn get_app_paths() -> Result<AppPaths, String> {
+    get().cloned()
+}
@@ -0,0 +1,322 @@
+use serde::Serialize;
+use serde_json::Value;
+use std::collections::HashMap;
+use std::sync::Mutex;
+use tauri::Manager;
+
+// 展開結果を保持しておくURLの数
+const MAX_CACHED_PLAYLISTS: usize = 20;
+
+// プレイリスト・チャンネルの1エントリ
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PlaylistEntry {
+    // プレイリスト内の位置（1から、--playlist-itemsと同じ番号）
+    pub index: usize,
+    pub id: Option<String>,
+    pub title: Option<String>,
+    pub url: Option<String>,
+    // 秒
+    pub duration: Option<f64>,
+    // YYYYMMDD
+    pub upload_date: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PlaylistExpansion {
+    pub url: String,
+    // 単独の動画ならfalse（entriesはその動画だけ）
+    pub is_playlist: bool,
+    pub title: Option<String>,
+    pub entries: Vec<PlaylistEntry>,
+}
+
+// 展開済みのプレイリスト（選択したエントリの実行時に再取得しないため）
+#[derive(Default)]
+pub struct PlaylistCache {
+    expansions: Mutex<Vec<PlaylistExpansion>>,
+}
+
+impl PlaylistCache {
+    fn get(&self, url: &str) -> Option<PlaylistExpansion> {
+        self.expansions
+            .lock()
+            .unwrap()
+            .iter()
+            .find(|expansion| expansion.url == url)
+            .cloned()
+    }
+
+    fn insert(&self, expansion: PlaylistExpansion) {
+        let mut expansions = self.expansions.lock().unwrap();
+        expansions.retain(|cached| cached.url != expansion.url);
+        expansions.push(expansion);
+        if expansions.len() > MAX_CACHED_PLAYLISTS {
+            expansions.remove(0);
+        }
+    }
+}
+
+// --flat-playlistでエントリの一覧を取得（動画ごとの情報は取得しないため速い）
+pub fn expand(url: &str) -> Result<PlaylistExpansion, String> {
+    let info = crate::probe::probe(url, &["--flat-playlist"])?;
+    let text =
+        |value: &Value, key: &str| value.get(key).and_then(Value::as_str).map(str::to_string);
+
+    let Some(entries) = info.get("entries").and_then(Value::as_array) else {
+        return Ok(PlaylistExpansion {
+            url: url.to_string(),
+            is_playlist: false,
+            title: text(&info, "title"),
+            entries: vec![PlaylistEntry {
+                index: 1,
+                id: text(&info, "id"),
+                title: text(&info, "title"),
+                url: Some(url.to_string()),
+                duration: info.get("duration").and_then(Value::as_f64),
+                upload_date: text(&info, "upload_date"),
+            }],
+        });
+    };
+
+    let entries = entries
+        .iter()
+        .enumerate()
+        .map(|(i, entry)| PlaylistEntry {
+            index: i + 1,
+            id: text(entry, "id"),
+            title: text(entry, "title"),
+            url: ["url", "webpage_url"]
+                .iter()
+                .filter_map(|key| text(entry, key))
+                .find(|url| url.starts_with("http://") || url.starts_with("https://")),
+            duration: entry.get("duration").and_then(Value::as_f64),
+            upload_date: text(entry, "upload_date"),
+        })
+        .collect();
+    Ok(PlaylistExpansion {
+        url: url.to_string(),
+        is_playlist: true,
+        title: text(&info, "title"),
+        entries,
+    })
+}
+
+// expandを別スレッドで実行（yt-dlpの終了を待つ間、非同期ランタイムのスレッドを塞がない）
+async fn expand_blocking(url: &str) -> Result<PlaylistExpansion, String> {
+    let url = url.to_string();
+    tauri::async_runtime::spawn_blocking(move || expand(&url))
+        .await
+        .map_err(|e| {
+            log::error!("Failed to expand playlist: {}", e);
+            format!("Failed to expand playlist: {}", e)
+        })?
+}
+
+// 選択したエントリをyt-dlpの引数に変換
+// 全てのエントリのURLが分かれば個別のURL、そうでなければ--playlist-itemsとプレイリストのURL
+pub fn selection_args(
+    expansion: &PlaylistExpansion,
+    indices: &[usize],
+) -> Result<Vec<String>, String> {
+    let mut indices = indices.to_vec();
+    indices.sort_unstable();
+    indices.dedup();
+    if indices.is_empty() {
+        return Err("No playlist entries selected".to_string());
+    }
+
+    let entries: HashMap<usize, &PlaylistEntry> = expansion
+        .entries
+        .iter()
+        .map(|entry| (entry.index, entry))
+        .collect();
+    let mut urls = Vec::new();
+    for index in &indices {
+        let entry = entries.get(index).ok_or_else(|| {
+            format!(
+                "Playlist entry {} does not exist in {}",
+                index, expansion.url
+            )
+        })?;
+        urls.push(entry.url.clone());
+    }
+
+    if !expansion.is_playlist {
+        return Ok(vec![expansion.url.clone()]);
+    }
+    match urls.into_iter().collect::<Option<Vec<String>>>() {
+        Some(urls) => Ok(urls),
+        None => Ok(vec![
+            "--playlist-items".to_string(),
+            playlist_items(&indices),
+            expansion.url.clone(),
+        ]),
+    }
+}
+
+// 昇順の番号を「1-3,5,8-9」の形式にする
+fn playlist_items(indices: &[usize]) -> String {
+    let mut ranges: Vec<(usize, usize)> = Vec::new();
+    for &index in indices {
+        match ranges.last_mut() {
+            Some((_, end)) if *end + 1 == index => *end = index,
+            _ => ranges.push((index, index)),
+        }
+    }
+    ranges
+        .iter()
+        .map(|&(start, end)| {
+            if start == end {
+                start.to_string()
+            } else {
+                format!("{}-{}", start, end)
+            }
+        })
+        .collect::<Vec<_>>()
+        .join(",")
+}
+
+// プレイリスト・チャンネルのURLをダウンロードせずにエントリの一覧に展開
+#[tauri::command]
+pub async fn expand_playlist(
+    url: String,
+    cache: tauri::State<'_, PlaylistCache>,
+) -> Result<PlaylistExpansion, String> {
+    log::info!("Invoked expand_playlist with url: {:?}", url);
+    let expansion = expand_blocking(url.trim()).await?;
+    log::info!(
+        "Expanded {} into {} entries",
+        expansion.url,
+        expansion.entries.len()
+    );
+    cache.insert(expansion.clone());
+    Ok(expansion)
+}
+
+// 選択したエントリだけをダウンロード（command_lineはrun_yt_dlpと同じ形式のオプション）
+#[tauri::command]
+pub async fn download_playlist_selection(
+    url: String,
+    indices: Vec<usize>,
+    command_line: Option<String>,
+    profile: Option<String>,
+    window: tauri::Window,
+) -> Result<crate::report::BatchReport, String> {
+    log::info!(
+        "Invoked download_playlist_selection with url: {:?}, indices: {:?}, profile: {:?}",
+        url,
+        indices,
+        profile
+    );
+    let url = url.trim();
+    let expansion = match window.state::<PlaylistCache>().get(url) {
+        Some(expansion) => expansion,
+        None => {
+            let expansion = expand_blocking(url).await?;
+            window.state::<PlaylistCache>().insert(expansion.clone());
+            expansion
+        }
+    };
+
+    let mut args = shlex::split(command_line.as_deref().unwrap_or_default())
+        .ok_or_else(|| "Invalid command line syntax - failed to parse arguments".to_string())?;
+    args.extend(selection_args(&expansion, &indices)?);
+    log::info!("Selection args: {:?}", crate::redact::redact_args(&args));
+
+    let context = crate::engine::Context::from_window(&window);
+    let retry_policy = context.settings.get().retry_policy;
+    crate::engine::execute_yt_dlp(&context, args, profile, retry_policy).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PLAYLIST_URL: &str = "https://www.youtube.com/playlist?list=PL1";
+
+    fn entry(index: usize, url: Option<&str>) -> PlaylistEntry {
+        PlaylistEntry {
+            index,
+            id: Some(format!("id{}", index)),
+            title: Some(format!("Video {}", index)),
+            url: url.map(str::to_string),
+            duration: None,
+            upload_date: None,
+        }
+    }
+
+    fn playlist(entries: Vec<PlaylistEntry>) -> PlaylistExpansion {
+        PlaylistExpansion {
+            url: PLAYLIST_URL.to_string(),
+            is_playlist: true,
+            title: Some("Playlist".to_string()),
+            entries,
+        }
+    }
+
+    #[test]
+    fn known_urls_are_passed_individually() {
+        let expansion = playlist(
+            (1..=4)
+                .map(|i| entry(i, Some(&format!("https://example.com/{}", i))))
+                .collect(),
+        );
+        assert_eq!(
+            selection_args(&expansion, &[3, 1, 3]).unwrap(),
+            vec!["https://example.com/1", "https://example.com/3"]
+        );
+    }
+
+    #[test]
+    fn unknown_urls_fall_back_to_playlist_items() {
+        let expansion = playlist(vec![
+            entry(1, Some("https://example.com/1")),
+            entry(2, None),
+            entry(3, Some("https://example.com/3")),
+            entry(4, None),
+            entry(5, None),
+        ]);
+        assert_eq!(
+            selection_args(&expansion, &[5, 1, 2, 4, 2]).unwrap(),
+            vec!["--playlist-items", "1-2,4-5", PLAYLIST_URL]
+        );
+        // 選択したエントリのURLが全て分かれば個別のURL
+        assert_eq!(
+            selection_args(&expansion, &[3, 1]).unwrap(),
+            vec!["https://example.com/1", "https://example.com/3"]
+        );
+    }
+
+    #[test]
+    fn invalid_selections_are_rejected() {
+        let expansion = playlist(vec![entry(1, None), entry(2, None)]);
+        assert!(selection_args(&expansion, &[]).is_err());
+        let error = selection_args(&expansion, &[1, 3]).unwrap_err();
+        assert!(error.contains("entry 3"), "{}", error);
+        assert!(selection_args(&expansion, &[0]).is_err());
+    }
+
+    #[test]
+    fn single_videos_use_the_original_url() {
+        let url = "https://youtu.be/dQw4w9WgXcQ";
+        let expansion = PlaylistExpansion {
+            url: url.to_string(),
+            is_playlist: false,
+            title: Some("Video".to_string()),
+            entries: vec![entry(1, Some(url))],
+        };
+        assert_eq!(selection_args(&expansion, &[1]).unwrap(), vec![url]);
+        assert!(selection_args(&expansion, &[2]).is_err());
+    }
+
+    #[test]
+    fn playlist_items_compress_ranges() {
+        assert_eq!(playlist_items(&[1]), "1");
+        assert_eq!(playlist_items(&[1, 2, 3]), "1-3");
+        assert_eq!(playlist_items(&[1, 2, 3, 5, 8, 9]), "1-3,5,8-9");
+        assert_eq!(playlist_items(&[2, 4, 6]), "2,4,6");
+        assert_eq!(playlist_items(&[]), "");
+    }
+}
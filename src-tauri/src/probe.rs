@@ -0,0 +1,43 @@
+use serde_json::Value;
+use std::process::Command;
+
+// yt-dlp -Jで動画（またはプレイリスト）の情報を取得（ダウンロードはしない）
+// 保存済みのCookieがあれば、ダウンロード時と同じ条件で取得できるように指定する
+pub fn probe(url: &str, extra_args: &[&str]) -> Result<Value, String> {
+    let yt_dlp_path = crate::paths::yt_dlp_path()?;
+    let mut cmd = Command::new(&yt_dlp_path);
+    cmd.args(["-J", "--no-warnings"]).args(extra_args);
+    if let Some(cookie_file) = crate::cookies::cookies_for_domain(&crate::throttle::domain_of(url))
+    {
+        cmd.arg("--cookies").arg(cookie_file);
+    }
+    cmd.arg("--").arg(url);
+
+    #[cfg(windows)]
+    {
+        use std::os::windows::process::CommandExt;
+        cmd.creation_flags(0x08000000);
+    }
+
+    let output = cmd.output().map_err(|e| {
+        log::error!("Failed to run yt-dlp: {}", e);
+        format!("Failed to run yt-dlp: {}", e)
+    })?;
+    if !output.status.success() {
+        let stderr = crate::redact::redact(&String::from_utf8_lossy(&output.stderr));
+        log::error!("Failed to probe {}: {}", url, stderr.trim());
+        return Err(format!("Failed to probe {}: {}", url, stderr.trim()));
+    }
+
+    serde_json::from_slice(&output.stdout).map_err(|e| {
+        log::error!("Failed to parse yt-dlp JSON output: {}", e);
+        format!("Failed to parse yt-dlp JSON output: {}", e)
+    })
+}
+
+// URLの動画情報（プレビューや確認用）
+#[tauri::command]
+pub async fn probe_url(url: String) -> Result<Value, String> {
+    log::info!("Invoked probe_url with url: {:?}", url);
+    probe(url.trim(), &["--no-playlist"])
+}
@@ -0,0 +1,513 @@
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+// プロファイルファイル（保存用とエクスポート用で共通）のスキーマバージョン
+const SCHEMA_VERSION: u32 = 1;
+
+// yt-dlpのダウンロードオプション
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct DownloadOptions {
+    // -f
+    pub format: Option<String>,
+    // -S
+    pub format_sort: Option<String>,
+    pub merge_output_format: Option<String>,
+    pub limit_rate: Option<String>,
+    // 上記以外のオプション（そのままyt-dlpに渡す）
+    pub extra_args: Vec<String>,
+}
+
+// 後処理（リストの順にyt-dlpのオプションとして付与）
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "camelCase")]
+pub enum PostProcessor {
+    ExtractAudio {
+        format: String,
+        quality: Option<String>,
+    },
+    RemuxVideo {
+        format: String,
+    },
+    RecodeVideo {
+        format: String,
+    },
+    EmbedMetadata,
+    EmbedThumbnail,
+    EmbedChapters,
+    EmbedSubtitles {
+        languages: Vec<String>,
+    },
+    SponsorBlockRemove {
+        categories: Vec<String>,
+    },
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Profile {
+    pub name: String,
+    #[serde(default)]
+    pub options: DownloadOptions,
+    #[serde(default)]
+    pub output_dir: Option<String>,
+    #[serde(default)]
+    pub filename_template: Option<String>,
+    #[serde(default)]
+    pub post_processing: Vec<PostProcessor>,
+}
+
+impl Profile {
+    pub fn validate(&self) -> Result<(), String> {
+        let name = self.name.trim();
+        if name.is_empty() {
+            return Err("Profile name must not be empty".to_string());
+        }
+        if name.contains(['/', '\\']) {
+            return Err(format!("Invalid profile name: {:?}", self.name));
+        }
+        for processor in &self.post_processing {
+            match processor {
+                PostProcessor::ExtractAudio { format, .. }
+                | PostProcessor::RemuxVideo { format }
+                | PostProcessor::RecodeVideo { format }
+                    if format.trim().is_empty() =>
+                {
+                    return Err(format!(
+                        "Post-processing step in profile {:?} has an empty format",
+                        self.name
+                    ));
+                }
+                _ => {}
+            }
+        }
+        // 共有されたプロファイルの取り込みで任意のコマンドが実行されないようにする
+        if let Some(arg) = self
+            .options
+            .extra_args
+            .iter()
+            .find(|arg| crate::api::is_forbidden_option(arg))
+        {
+            return Err(format!(
+                "Option {} is not allowed in profile {:?}",
+                arg, self.name
+            ));
+        }
+        Ok(())
+    }
+
+    // yt-dlpの引数に変換（ユーザーのオプションより前に置き、後から指定した値を優先させる）
+    pub fn to_args(&self) -> Vec<String> {
+        let mut args = Vec::new();
+        let mut push = |option: &str, value: &Option<String>| {
+            if let Some(value) = value.as_deref().filter(|v| !v.trim().is_empty()) {
+                args.push(option.to_string());
+                args.push(value.to_string());
+            }
+        };
+        push("-f", &self.options.format);
+        push("-S", &self.options.format_sort);
+        push("--merge-output-format", &self.options.merge_output_format);
+        push("--limit-rate", &self.options.limit_rate);
+        push(
+            "--paths",
+            &self.output_dir.as_ref().map(|dir| format!("home:{}", dir)),
+        );
+        push("-o", &self.filename_template);
+
+        for processor in &self.post_processing {
+            match processor {
+                PostProcessor::ExtractAudio { format, quality } => {
+                    args.extend(["-x".to_string(), "--audio-format".to_string()]);
+                    args.push(format.clone());
+                    if let Some(quality) = quality {
+                        args.push("--audio-quality".to_string());
+                        args.push(quality.clone());
+                    }
+                }
+                PostProcessor::RemuxVideo { format } => {
+                    args.push("--remux-video".to_string());
+                    args.push(format.clone());
+                }
+                PostProcessor::RecodeVideo { format } => {
+                    args.push("--recode-video".to_string());
+                    args.push(format.clone());
+                }
+                PostProcessor::EmbedMetadata => args.push("--embed-metadata".to_string()),
+                PostProcessor::EmbedThumbnail => args.push("--embed-thumbnail".to_string()),
+                PostProcessor::EmbedChapters => args.push("--embed-chapters".to_string()),
+                PostProcessor::EmbedSubtitles { languages } => {
+                    args.push("--write-subs".to_string());
+                    if !languages.is_empty() {
+                        args.push("--sub-langs".to_string());
+                        args.push(languages.join(","));
+                    }
+                    args.push("--embed-subs".to_string());
+                }
+                PostProcessor::SponsorBlockRemove { categories } => {
+                    args.push("--sponsorblock-remove".to_string());
+                    args.push(if categories.is_empty() {
+                        "default".to_string()
+                    } else {
+                        categories.join(",")
+                    });
+                }
+            }
+        }
+
+        // 以前のバージョンで保存されたプロファイルに含まれていても渡さない
+        // （値を取るオプションの値だけが残らないよう、その他のオプションもまとめて無視する）
+        match self
+            .options
+            .extra_args
+            .iter()
+            .find(|arg| crate::api::is_forbidden_option(arg))
+        {
+            Some(arg) => log::warn!(
+                "Ignoring extra options of profile {:?} because {} is not allowed",
+                self.name,
+                arg
+            ),
+            None => args.extend(self.options.extra_args.iter().cloned()),
+        }
+        args
+    }
+}
+
+// profiles.jsonとエクスポートファイルの形式
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct ProfileFile {
+    #[serde(default)]
+    schema_version: u32,
+    profiles: Vec<Profile>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ImportResult {
+    pub imported: Vec<String>,
+    // 同名のプロファイルがあり、上書きしなかったもの
+    pub skipped: Vec<String>,
+}
+
+// 保存済みのプロファイル
+pub struct ProfileStore {
+    path: PathBuf,
+    profiles: Mutex<BTreeMap<String, Profile>>,
+}
+
+impl ProfileStore {
+    pub fn load(config_dir: &Path) -> ProfileStore {
+        let path = config_dir.join("profiles.json");
+        let profiles = match fs::read_to_string(&path) {
+            Ok(content) => match parse_profiles(&content) {
+                Ok(profiles) => profiles,
+                Err(e) => {
+                    log::error!("Failed to load profiles: {}", e);
+                    let backup = path.with_extension("json.bak");
+                    if let Err(e) = fs::rename(&path, &backup) {
+                        log::warn!("Failed to back up broken profiles file: {}", e);
+                    }
+                    Vec::new()
+                }
+            },
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Vec::new(),
+            Err(e) => {
+                log::error!("Could not read profiles file: {}", e);
+                Vec::new()
+            }
+        };
+
+        ProfileStore {
+            path,
+            profiles: Mutex::new(
+                profiles
+                    .into_iter()
+                    .map(|profile| (profile.name.clone(), profile))
+                    .collect(),
+            ),
+        }
+    }
+
+    pub fn get(&self, name: &str) -> Option<Profile> {
+        self.profiles.lock().unwrap().get(name).cloned()
+    }
+
+    fn list(&self) -> Vec<Profile> {
+        self.profiles.lock().unwrap().values().cloned().collect()
+    }
+
+    // プロファイルを変更して保存（保存に失敗したら変更を戻す）
+    fn modify<T, F>(&self, f: F) -> Result<T, String>
+    where
+        F: FnOnce(&mut BTreeMap<String, Profile>) -> Result<T, String>,
+    {
+        let mut profiles = self.profiles.lock().unwrap();
+        let mut updated = profiles.clone();
+        let result = f(&mut updated)?;
+        write_profiles(&self.path, updated.values())?;
+        *profiles = updated;
+        Ok(result)
+    }
+}
+
+// 指定されたプロファイルのyt-dlpの引数（未登録の名前なら空）
+pub fn profile_args(store: &ProfileStore, name: Option<&str>) -> Vec<String> {
+    name.and_then(|name| store.get(name))
+        .map(|profile| profile.to_args())
+        .unwrap_or_default()
+}
+
+fn parse_profiles(content: &str) -> Result<Vec<Profile>, String> {
+    let file: ProfileFile = serde_json::from_str(content).map_err(|e| e.to_string())?;
+    if file.schema_version > SCHEMA_VERSION {
+        log::warn!(
+            "Profiles file has a newer schema version ({} > {}), unknown fields will be ignored",
+            file.schema_version,
+            SCHEMA_VERSION
+        );
+    }
+    Ok(file.profiles)
+}
+
+// 一時ファイルに書き込んでから置き換え、書き込み途中の破損を防ぐ
+fn write_profiles<'a>(
+    path: &Path,
+    profiles: impl Iterator<Item = &'a Profile>,
+) -> Result<(), String> {
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir).map_err(|e| {
+            log::error!("Could not create directory {:?}: {}", dir, e);
+            format!("Could not create directory {:?}: {}", dir, e)
+        })?;
+    }
+    let file = ProfileFile {
+        schema_version: SCHEMA_VERSION,
+        profiles: profiles.cloned().collect(),
+    };
+    let json = serde_json::to_string_pretty(&file).map_err(|e| {
+        log::error!("Failed to serialize profiles: {}", e);
+        format!("Failed to serialize profiles: {}", e)
+    })?;
+    let tmp_path = path.with_extension("json.tmp");
+    fs::write(&tmp_path, json).map_err(|e| {
+        log::error!("Failed to write profiles file: {}", e);
+        format!("Failed to write profiles file: {}", e)
+    })?;
+    fs::rename(&tmp_path, path).map_err(|e| {
+        log::error!("Failed to save profiles file: {}", e);
+        format!("Failed to save profiles file: {}", e)
+    })
+}
+
+// プロファイル一覧（名前順）
+#[tauri::command]
+pub async fn list_profiles(store: tauri::State<'_, ProfileStore>) -> Result<Vec<Profile>, String> {
+    Ok(store.list())
+}
+
+#[tauri::command]
+pub async fn get_profile(
+    name: String,
+    store: tauri::State<'_, ProfileStore>,
+) -> Result<Profile, String> {
+    store
+        .get(&name)
+        .ok_or_else(|| format!("Profile not found: {}", name))
+}
+
+// プロファイルを作成または更新（previous_nameを指定すると名前を変更）
+#[tauri::command]
+pub async fn save_profile(
+    mut profile: Profile,
+    previous_name: Option<String>,
+    store: tauri::State<'_, ProfileStore>,
+) -> Result<Profile, String> {
+    log::info!("Invoked save_profile with profile: {:?}", profile);
+    profile.name = profile.name.trim().to_string();
+    profile.validate()?;
+
+    store.modify(|profiles| {
+        if let Some(previous) = previous_name.filter(|previous| *previous != profile.name) {
+            if profiles.contains_key(&profile.name) {
+                return Err(format!("Profile already exists: {}", profile.name));
+            }
+            profiles.remove(&previous);
+        }
+        profiles.insert(profile.name.clone(), profile.clone());
+        Ok(profile)
+    })
+}
+
+#[tauri::command]
+pub async fn delete_profile(
+    name: String,
+    store: tauri::State<'_, ProfileStore>,
+) -> Result<(), String> {
+    log::info!("Invoked delete_profile with name: {:?}", name);
+    store.modify(|profiles| {
+        profiles
+            .remove(&name)
+            .map(|_| ())
+            .ok_or_else(|| format!("Profile not found: {}", name))
+    })
+}
+
+// プロファイルをJSONファイルに書き出す（namesを省略すると全て）
+#[tauri::command]
+pub async fn export_profiles(
+    path: String,
+    names: Option<Vec<String>>,
+    store: tauri::State<'_, ProfileStore>,
+) -> Result<usize, String> {
+    log::info!(
+        "Invoked export_profiles with path: {:?}, names: {:?}",
+        path,
+        names
+    );
+    let profiles: Vec<Profile> = store
+        .list()
+        .into_iter()
+        .filter(|profile| {
+            names
+                .as_ref()
+                .is_none_or(|names| names.contains(&profile.name))
+        })
+        .collect();
+    if profiles.is_empty() {
+        log::error!("No profiles to export");
+        return Err("No profiles to export".to_string());
+    }
+
+    write_profiles(Path::new(&path), profiles.iter())?;
+    log::info!("Exported {} profiles to {:?}", profiles.len(), path);
+    Ok(profiles.len())
+}
+
+// JSONファイルからプロファイルを取り込む
+#[tauri::command]
+pub async fn import_profiles(
+    path: String,
+    overwrite: bool,
+    store: tauri::State<'_, ProfileStore>,
+) -> Result<ImportResult, String> {
+    log::info!(
+        "Invoked import_profiles with path: {:?}, overwrite: {}",
+        path,
+        overwrite
+    );
+    let content = fs::read_to_string(&path).map_err(|e| {
+        log::error!("Could not read profiles file: {}", e);
+        format!("Could not read profiles file: {}", e)
+    })?;
+    let incoming = parse_profiles(&content).map_err(|e| {
+        log::error!("Failed to parse profiles file: {}", e);
+        format!("Failed to parse profiles file: {}", e)
+    })?;
+    for profile in &incoming {
+        profile.validate()?;
+    }
+
+    store.modify(|profiles| {
+        let mut result = ImportResult {
+            imported: Vec::new(),
+            skipped: Vec::new(),
+        };
+        for mut profile in incoming {
+            profile.name = profile.name.trim().to_string();
+            if !overwrite && profiles.contains_key(&profile.name) {
+                result.skipped.push(profile.name);
+                continue;
+            }
+            result.imported.push(profile.name.clone());
+            profiles.insert(profile.name.clone(), profile);
+        }
+        log::info!(
+            "Imported {} profiles ({} skipped)",
+            result.imported.len(),
+            result.skipped.len()
+        );
+        Ok(result)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn profile(extra_args: &[&str]) -> Profile {
+        Profile {
+            name: "music".to_string(),
+            options: DownloadOptions {
+                format: Some("bestaudio".to_string()),
+                format_sort: Some(" ".to_string()),
+                extra_args: extra_args.iter().map(|arg| arg.to_string()).collect(),
+                ..Default::default()
+            },
+            output_dir: Some("/music".to_string()),
+            filename_template: None,
+            post_processing: vec![
+                PostProcessor::ExtractAudio {
+                    format: "mp3".to_string(),
+                    quality: None,
+                },
+                PostProcessor::EmbedSubtitles {
+                    languages: vec!["en".to_string(), "ja".to_string()],
+                },
+            ],
+        }
+    }
+
+    #[test]
+    fn to_args_orders_options_and_post_processing() {
+        assert_eq!(
+            profile(&["--no-mtime"]).to_args(),
+            vec![
+                "-f",
+                "bestaudio",
+                "--paths",
+                "home:/music",
+                "-x",
+                "--audio-format",
+                "mp3",
+                "--write-subs",
+                "--sub-langs",
+                "en,ja",
+                "--embed-subs",
+                "--no-mtime",
+            ]
+        );
+    }
+
+    #[test]
+    fn to_args_ignores_extra_args_with_forbidden_options() {
+        let args = profile(&["--exec", "touch /tmp/x", "--no-mtime"]).to_args();
+        assert!(!args
+            .iter()
+            .any(|arg| arg == "--exec" || arg == "touch /tmp/x"));
+        assert!(!args.contains(&"--no-mtime".to_string()));
+        assert!(args.contains(&"bestaudio".to_string()));
+    }
+
+    #[test]
+    fn import_rejects_forbidden_options() {
+        let content = serde_json::json!({
+            "schemaVersion": 1,
+            "profiles": [{
+                "name": "shared",
+                "options": { "extraArgs": ["--downloader", "/tmp/evil"] },
+            }],
+        })
+        .to_string();
+        let profiles = parse_profiles(&content).unwrap();
+        let error = profiles[0].validate().unwrap_err();
+        assert!(error.contains("--downloader"), "{}", error);
+
+        assert!(profile(&["--netrc-cmd=cat secret"]).validate().is_err());
+        assert!(profile(&["--no-mtime"]).validate().is_ok());
+    }
+}
@@ -0,0 +1,566 @@
+use crate::diagnostics::{self, ErrorKind};
+use serde::Serialize;
+use std::collections::HashMap;
+
+// 後処理（ffmpegによる結合・変換など）の出力に付くタグ（前方一致）
+const POST_PROCESSOR_TAGS: &[&str] = &[
+    "Merger",
+    "Fixup",
+    "ExtractAudio",
+    "VideoConvertor",
+    "VideoRemuxer",
+    "EmbedSubtitle",
+    "EmbedThumbnail",
+    "Metadata",
+    "ThumbnailsConvertor",
+    "SubtitlesConvertor",
+    "SponsorBlock",
+    "ModifyChapters",
+    "SplitChapters",
+    "Exec",
+    "MoveFiles",
+];
+
+// URLごとの処理結果
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum UrlOutcome {
+    Success,
+    AlreadyArchived,
+    Unavailable,
+    GeoBlocked,
+    LoginRequired,
+    Private,
+    OtherError,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct UrlResult {
+    pub url: String,
+    pub video_id: Option<String>,
+    pub outcome: UrlOutcome,
+    pub error_kind: Option<ErrorKind>,
+    pub message: Option<String>,
+    // 自動リトライを含めた試行回数
+    pub attempts: u32,
+}
+
+#[derive(Debug, Clone, Default, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BatchSummary {
+    pub total: usize,
+    pub succeeded: usize,
+    pub already_archived: usize,
+    pub failed: usize,
+}
+
+// バッチ実行全体の結果
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BatchReport {
+    pub success: bool,
+    pub exit_code: Option<i32>,
+    pub summary: BatchSummary,
+    pub results: Vec<UrlResult>,
+}
+
+impl UrlOutcome {
+    pub fn is_failure(self) -> bool {
+        !matches!(self, UrlOutcome::Success | UrlOutcome::AlreadyArchived)
+    }
+}
+
+impl BatchReport {
+    // 別のプロセスの結果を追加（ドメインごとに分けて実行した場合）
+    pub fn append(&mut self, other: BatchReport) {
+        self.results.extend(other.results);
+        self.summary.total += other.summary.total;
+        self.summary.succeeded += other.summary.succeeded;
+        self.summary.already_archived += other.summary.already_archived;
+        self.summary.failed += other.summary.failed;
+        if !other.success || self.success {
+            self.exit_code = other.exit_code;
+        }
+        self.success = self.success && other.success;
+    }
+
+    // リトライの結果を反映し、集計を更新
+    pub fn merge_retry(&mut self, retry: BatchReport) {
+        for result in retry.results {
+            match self.results.iter_mut().find(|r| r.url == result.url) {
+                Some(existing) => {
+                    let attempts = existing.attempts + 1;
+                    *existing = result;
+                    existing.attempts = attempts;
+                }
+                None => self.results.push(result),
+            }
+        }
+
+        self.summary = BatchSummary::default();
+        for result in &self.results {
+            match result.outcome {
+                UrlOutcome::Success => self.summary.succeeded += 1,
+                UrlOutcome::AlreadyArchived => self.summary.already_archived += 1,
+                _ => self.summary.failed += 1,
+            }
+        }
+        self.summary.total = self.results.len();
+        self.exit_code = retry.exit_code;
+        self.success = retry.success && self.summary.failed == 0;
+    }
+}
+
+// 集計中のURLの状態
+struct TrackedUrl {
+    url: String,
+    video_id: Option<String>,
+    // 抽出ログに現れたextractor名（[youtube]など）
+    extractor: Option<String>,
+    seen: bool,
+    outcome: Option<UrlOutcome>,
+    error_kind: Option<ErrorKind>,
+    message: Option<String>,
+}
+
+impl TrackedUrl {
+    // 結果を置き換え、前回のエラーを消す
+    fn set_outcome(&mut self, outcome: Option<UrlOutcome>) {
+        self.outcome = outcome;
+        self.error_kind = None;
+        self.message = None;
+    }
+}
+
+// yt-dlpの出力行からURLごとの結果を組み立てる
+pub struct BatchTracker {
+    urls: Vec<TrackedUrl>,
+    id_index: HashMap<String, usize>,
+    current: Option<usize>,
+    // 最後の出力が後処理のものかどうか
+    post_processing: bool,
+}
+
+impl BatchTracker {
+    pub fn new(urls: &[String]) -> Self {
+        let mut tracker = BatchTracker {
+            urls: Vec::new(),
+            id_index: HashMap::new(),
+            current: None,
+            post_processing: false,
+        };
+        for url in urls {
+            let index = tracker.push_url(url);
+            // アーカイブ済みの動画は抽出前にスキップされるため、URLから分かるIDを先に登録
+            if let Some(entry) = crate::archive::archive_entry_for_url(url) {
+                tracker.set_video_id(index, &entry.id);
+            }
+        }
+        tracker
+    }
+
+    // 後処理の実行中かどうか（次の動画の抽出・ダウンロードが始まるまで）
+    pub fn is_post_processing(&self) -> bool {
+        self.post_processing
+    }
+
+    // stdoutの1行を解析
+    pub fn feed_stdout(&mut self, line: &str) {
+        let Some((tag, rest)) = split_tag(line) else {
+            return;
+        };
+        self.post_processing = POST_PROCESSOR_TAGS
+            .iter()
+            .any(|prefix| tag.starts_with(prefix));
+
+        // [youtube] Extracting URL: https://...
+        if let Some(url) = rest.strip_prefix("Extracting URL: ") {
+            let index = self.find_or_push_url(url.trim());
+            // 一時停止後の再実行などで再び処理される場合は、前回の結果ではなく今回の結果を使う
+            if self.urls[index].seen {
+                self.urls[index].set_outcome(None);
+            }
+            self.urls[index].seen = true;
+            self.urls[index].extractor = Some(tag.to_string());
+            self.current = Some(index);
+            return;
+        }
+
+        // [download] 100% of   12.34MiB in 00:00:05 at 2.47MiB/s
+        // [download] /videos/title [abc123].mp4 has already been downloaded
+        if tag == "download"
+            && (rest.starts_with("100% of ") || rest.ends_with("has already been downloaded"))
+        {
+            if let Some(current) = self.current {
+                self.urls[current].set_outcome(Some(UrlOutcome::Success));
+            }
+            return;
+        }
+
+        let Some((id, message)) = split_id(rest) else {
+            return;
+        };
+
+        // [download] abc123: has already been recorded in the archive
+        if tag == "download" && message.contains("has already been recorded in the archive") {
+            let index = self.index_for_id(id);
+            if let Some(index) = index {
+                let tracked = &mut self.urls[index];
+                tracked.seen = true;
+                if tracked.outcome != Some(UrlOutcome::Success) {
+                    tracked.set_outcome(Some(UrlOutcome::AlreadyArchived));
+                }
+            }
+            return;
+        }
+
+        // [youtube] abc123: Downloading webpage（抽出中のURLとIDを対応付け）
+        if let Some(current) = self.current {
+            if self.urls[current].extractor.as_deref() == Some(tag)
+                && self.urls[current].video_id.is_none()
+            {
+                self.set_video_id(current, id);
+            }
+        }
+    }
+
+    // stderrの1行を解析
+    pub fn feed_stderr(&mut self, line: &str) {
+        let Some(error) = line.trim().strip_prefix("ERROR:") else {
+            return;
+        };
+        let error = error.trim();
+
+        // ERROR: [youtube] abc123: Video unavailable
+        let index = split_tag(error)
+            .and_then(|(_, rest)| split_id(rest))
+            .and_then(|(id, _)| self.index_for_id(id))
+            // URL自体が不正な場合などはメッセージ中のURLで判別
+            .or_else(|| self.urls.iter().position(|t| error.contains(&t.url)))
+            .or(self.current);
+
+        if let Some(index) = index {
+            let tracked = &mut self.urls[index];
+            tracked.seen = true;
+            // 同じ試行で続けて出たエラーは最初のものを残す
+            if tracked.message.is_none() {
+                let kind = diagnostics::classify_message(error);
+                tracked.outcome = Some(outcome_for(kind));
+                tracked.error_kind = kind;
+                tracked.message = Some(error.to_string());
+            }
+        }
+    }
+
+    // プロセス終了後に結果を確定
+    pub fn finish(self, exit_code: Option<i32>, success: bool) -> BatchReport {
+        let mut summary = BatchSummary::default();
+        let results: Vec<UrlResult> = self
+            .urls
+            .into_iter()
+            .map(|tracked| {
+                let (outcome, message) = match (tracked.outcome, tracked.message) {
+                    (Some(outcome), message) => (outcome, message),
+                    // 出力に現れなかったURLは、正常終了なら成功、異常終了なら未処理とみなす
+                    (None, _) if tracked.seen || success => (UrlOutcome::Success, None),
+                    (None, _) => (
+                        UrlOutcome::OtherError,
+                        Some("yt-dlp exited before processing this URL".to_string()),
+                    ),
+                };
+                match outcome {
+                    UrlOutcome::Success => summary.succeeded += 1,
+                    UrlOutcome::AlreadyArchived => summary.already_archived += 1,
+                    _ => summary.failed += 1,
+                }
+                UrlResult {
+                    url: tracked.url,
+                    video_id: tracked.video_id,
+                    outcome,
+                    error_kind: tracked.error_kind,
+                    message,
+                    attempts: 1,
+                }
+            })
+            .collect();
+        summary.total = results.len();
+
+        BatchReport {
+            success,
+            exit_code,
+            summary,
+            results,
+        }
+    }
+
+    fn push_url(&mut self, url: &str) -> usize {
+        self.urls.push(TrackedUrl {
+            url: url.to_string(),
+            video_id: None,
+            extractor: None,
+            seen: false,
+            outcome: None,
+            error_kind: None,
+            message: None,
+        });
+        self.urls.len() - 1
+    }
+
+    // 入力リストにないURL（プレイリストの各動画など）は結果に追加する
+    fn find_or_push_url(&mut self, url: &str) -> usize {
+        match self
+            .urls
+            .iter()
+            .position(|tracked| tracked.url == url && !tracked.seen)
+            .or_else(|| self.urls.iter().position(|tracked| tracked.url == url))
+        {
+            Some(index) => index,
+            None => self.push_url(url),
+        }
+    }
+
+    fn set_video_id(&mut self, index: usize, id: &str) {
+        self.urls[index].video_id = Some(id.to_string());
+        self.id_index.insert(id.to_string(), index);
+    }
+
+    fn index_for_id(&self, id: &str) -> Option<usize> {
+        self.id_index.get(id).copied()
+    }
+}
+
+// 「[tag] rest」形式の行を分割
+fn split_tag(line: &str) -> Option<(&str, &str)> {
+    let line = line.trim().strip_prefix('[')?;
+    let (tag, rest) = line.split_once("] ")?;
+    Some((tag, rest))
+}
+
+// 「id: message」形式を分割（IDは空白を含まない）
+fn split_id(rest: &str) -> Option<(&str, &str)> {
+    let (id, message) = rest.split_once(": ")?;
+    if id.is_empty() || id.contains(char::is_whitespace) {
+        return None;
+    }
+    Some((id, message))
+}
+
+// エラーの種類から処理結果を判別
+fn outcome_for(kind: Option<ErrorKind>) -> UrlOutcome {
+    match kind {
+        Some(ErrorKind::Private) => UrlOutcome::Private,
+        Some(ErrorKind::GeoBlocked) => UrlOutcome::GeoBlocked,
+        Some(ErrorKind::LoginRequired | ErrorKind::BotCheck) => UrlOutcome::LoginRequired,
+        Some(ErrorKind::Unavailable) => UrlOutcome::Unavailable,
+        _ => UrlOutcome::OtherError,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // yt-dlpの出力（「out: 」はstdout、「err: 」はstderr）を順に解析
+    fn track(urls: &[&str], output: &str, success: bool) -> BatchReport {
+        let urls: Vec<String> = urls.iter().map(|url| url.to_string()).collect();
+        let mut tracker = BatchTracker::new(&urls);
+        for line in output.lines().map(str::trim).filter(|l| !l.is_empty()) {
+            match line.split_once(": ") {
+                Some(("out", line)) => tracker.feed_stdout(line),
+                Some(("err", line)) => tracker.feed_stderr(line),
+                _ => panic!("invalid fixture line: {}", line),
+            }
+        }
+        tracker.finish(Some(if success { 0 } else { 1 }), success)
+    }
+
+    fn outcomes(report: &BatchReport) -> Vec<(&str, UrlOutcome)> {
+        report
+            .results
+            .iter()
+            .map(|result| (result.url.as_str(), result.outcome))
+            .collect()
+    }
+
+    #[test]
+    fn archived_urls_are_reported_without_extraction() {
+        let report = track(
+            &[
+                "https://www.youtube.com/watch?v=dQw4w9WgXcQ",
+                "https://youtu.be/jNQXAC9IVRw",
+            ],
+            "
+            out: [download] dQw4w9WgXcQ: has already been recorded in the archive
+            out: [youtube] Extracting URL: https://youtu.be/jNQXAC9IVRw
+            out: [youtube] jNQXAC9IVRw: Downloading webpage
+            out: [info] jNQXAC9IVRw: Downloading 1 format(s): 18
+            out: [download] Destination: /videos/Me at the zoo [jNQXAC9IVRw].mp4
+            out: [download] 100% of  770.55KiB in 00:00:01 at 601.06KiB/s
+            ",
+            true,
+        );
+        assert_eq!(
+            outcomes(&report),
+            vec![
+                (
+                    "https://www.youtube.com/watch?v=dQw4w9WgXcQ",
+                    UrlOutcome::AlreadyArchived
+                ),
+                ("https://youtu.be/jNQXAC9IVRw", UrlOutcome::Success),
+            ]
+        );
+        assert_eq!(report.summary.already_archived, 1);
+        assert_eq!(report.summary.succeeded, 1);
+    }
+
+    #[test]
+    fn errors_are_attributed_to_their_urls() {
+        let report = track(
+            &[
+                "https://www.youtube.com/watch?v=dQw4w9WgXcQ",
+                "https://www.youtube.com/watch?v=aaaaaaaaaaa",
+                "https://example.com/missing",
+                "https://www.youtube.com/watch?v=jNQXAC9IVRw",
+            ],
+            "
+            out: [youtube] Extracting URL: https://www.youtube.com/watch?v=dQw4w9WgXcQ
+            out: [youtube] dQw4w9WgXcQ: Downloading webpage
+            out: [youtube] dQw4w9WgXcQ: Downloading tv client config
+            out: [info] dQw4w9WgXcQ: Downloading 1 format(s): 18
+            out: [download] Destination: /videos/Never Gonna Give You Up [dQw4w9WgXcQ].mp4
+            out: [download] 100% of    9.20MiB in 00:00:02 at 4.12MiB/s
+            out: [youtube] Extracting URL: https://www.youtube.com/watch?v=aaaaaaaaaaa
+            out: [youtube] aaaaaaaaaaa: Downloading webpage
+            err: ERROR: [youtube] aaaaaaaaaaa: Private video. Sign in if you've been granted access to this video
+            out: [generic] Extracting URL: https://example.com/missing
+            out: [generic] missing: Downloading webpage
+            err: ERROR: [generic] Unable to download webpage: HTTP Error 404: Not Found (caused by <HTTPError 404: Not Found>)
+            ",
+            false,
+        );
+        assert_eq!(
+            outcomes(&report),
+            vec![
+                (
+                    "https://www.youtube.com/watch?v=dQw4w9WgXcQ",
+                    UrlOutcome::Success
+                ),
+                (
+                    "https://www.youtube.com/watch?v=aaaaaaaaaaa",
+                    UrlOutcome::Private
+                ),
+                ("https://example.com/missing", UrlOutcome::Unavailable),
+                // 異常終了で処理されなかったURL
+                (
+                    "https://www.youtube.com/watch?v=jNQXAC9IVRw",
+                    UrlOutcome::OtherError
+                ),
+            ]
+        );
+        assert_eq!(report.results[1].video_id.as_deref(), Some("aaaaaaaaaaa"));
+        assert_eq!(report.results[1].error_kind, Some(ErrorKind::Private));
+        assert_eq!(report.summary.failed, 3);
+    }
+
+    #[test]
+    fn later_success_replaces_an_earlier_error() {
+        // 1回目はダウンロード中にエラー、--continueを付けた再実行で成功
+        let report = track(
+            &["https://www.youtube.com/watch?v=dQw4w9WgXcQ"],
+            "
+            out: [youtube] Extracting URL: https://www.youtube.com/watch?v=dQw4w9WgXcQ
+            out: [youtube] dQw4w9WgXcQ: Downloading webpage
+            out: [download] Destination: /videos/Never Gonna Give You Up [dQw4w9WgXcQ].mp4
+            out: [download]  41.3% of    9.20MiB at    1.02MiB/s ETA 00:05
+            err: ERROR: unable to download video data: HTTP Error 403: Forbidden
+            out: [youtube] Extracting URL: https://www.youtube.com/watch?v=dQw4w9WgXcQ
+            out: [youtube] dQw4w9WgXcQ: Downloading webpage
+            out: [download] Resuming download at byte 3984588
+            out: [download] Destination: /videos/Never Gonna Give You Up [dQw4w9WgXcQ].mp4
+            out: [download] 100% of    9.20MiB in 00:00:03 at 2.98MiB/s
+            ",
+            true,
+        );
+        let result = &report.results[0];
+        assert_eq!(result.outcome, UrlOutcome::Success);
+        assert_eq!(result.error_kind, None);
+        assert_eq!(result.message, None);
+        assert_eq!(report.summary.succeeded, 1);
+        assert_eq!(report.summary.failed, 0);
+
+        // 再実行でもエラーになった場合は新しいエラーを使う
+        let report = track(
+            &["https://www.youtube.com/watch?v=dQw4w9WgXcQ"],
+            "
+            out: [youtube] Extracting URL: https://www.youtube.com/watch?v=dQw4w9WgXcQ
+            err: ERROR: unable to download video data: HTTP Error 403: Forbidden
+            out: [youtube] Extracting URL: https://www.youtube.com/watch?v=dQw4w9WgXcQ
+            err: ERROR: [youtube] dQw4w9WgXcQ: Video unavailable. This video has been removed by the uploader
+            ",
+            false,
+        );
+        assert_eq!(report.results[0].outcome, UrlOutcome::Unavailable);
+        assert_eq!(report.results.len(), 1);
+    }
+
+    #[test]
+    fn retry_results_replace_earlier_failures() {
+        let result = |url: &str, outcome: UrlOutcome| UrlResult {
+            url: url.to_string(),
+            video_id: None,
+            outcome,
+            error_kind: None,
+            message: None,
+            attempts: 1,
+        };
+        let report = |success: bool, results: Vec<UrlResult>| BatchReport {
+            success,
+            exit_code: Some(if success { 0 } else { 1 }),
+            summary: BatchSummary::default(),
+            results,
+        };
+        let mut batch = report(
+            false,
+            vec![
+                result("https://example.com/a", UrlOutcome::Success),
+                result("https://example.com/b", UrlOutcome::OtherError),
+                result("https://example.com/c", UrlOutcome::OtherError),
+                result("https://example.com/d", UrlOutcome::AlreadyArchived),
+            ],
+        );
+
+        batch.merge_retry(report(
+            false,
+            vec![
+                result("https://example.com/b", UrlOutcome::Success),
+                result("https://example.com/c", UrlOutcome::Unavailable),
+            ],
+        ));
+        assert_eq!(
+            outcomes(&batch),
+            vec![
+                ("https://example.com/a", UrlOutcome::Success),
+                ("https://example.com/b", UrlOutcome::Success),
+                ("https://example.com/c", UrlOutcome::Unavailable),
+                ("https://example.com/d", UrlOutcome::AlreadyArchived),
+            ]
+        );
+        let attempts: Vec<u32> = batch.results.iter().map(|r| r.attempts).collect();
+        assert_eq!(attempts, vec![1, 2, 2, 1]);
+        assert_eq!(batch.summary.total, 4);
+        assert_eq!(batch.summary.succeeded, 2);
+        assert_eq!(batch.summary.already_archived, 1);
+        assert_eq!(batch.summary.failed, 1);
+        assert!(!batch.success);
+
+        batch.merge_retry(report(
+            true,
+            vec![result("https://example.com/c", UrlOutcome::Success)],
+        ));
+        assert_eq!(batch.results[2].attempts, 3);
+        assert_eq!(batch.summary.failed, 0);
+        assert!(batch.success);
+        assert_eq!(batch.exit_code, Some(0));
+    }
+}
@@ -0,0 +1,119 @@
+use crate::diagnostics::ErrorKind;
+use serde::{Deserialize, Serialize};
+use std::time::Duration;
+
+// 待機時間の上限（1日）
+const MAX_BACKOFF_LIMIT_SECS: u64 = 24 * 60 * 60;
+
+// 失敗したURLの自動リトライ設定
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct RetryPolicy {
+    // 最初の実行を含めた最大試行回数（1ならリトライしない）
+    pub max_attempts: u32,
+    pub initial_backoff_secs: u64,
+    pub backoff_multiplier: f64,
+    pub max_backoff_secs: u64,
+    // リトライ対象のエラーの種類
+    pub retry_on: Vec<ErrorKind>,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        RetryPolicy {
+            max_attempts: 3,
+            initial_backoff_secs: 30,
+            backoff_multiplier: 2.0,
+            max_backoff_secs: 600,
+            retry_on: vec![
+                ErrorKind::RateLimited,
+                ErrorKind::Timeout,
+                ErrorKind::NetworkError,
+                ErrorKind::FragmentError,
+            ],
+        }
+    }
+}
+
+impl RetryPolicy {
+    pub fn validate(&self) -> Result<(), String> {
+        if self.max_attempts == 0 {
+            return Err("maxAttempts must be at least 1".to_string());
+        }
+        if !self.backoff_multiplier.is_finite() || self.backoff_multiplier < 1.0 {
+            return Err("backoffMultiplier must be a finite number >= 1.0".to_string());
+        }
+        if self.max_backoff_secs > MAX_BACKOFF_LIMIT_SECS {
+            return Err(format!(
+                "maxBackoffSecs must not exceed {}",
+                MAX_BACKOFF_LIMIT_SECS
+            ));
+        }
+        if self.initial_backoff_secs > self.max_backoff_secs {
+            return Err("initialBackoffSecs must not exceed maxBackoffSecs".to_string());
+        }
+        Ok(())
+    }
+
+    pub fn should_retry(&self, kind: Option<ErrorKind>) -> bool {
+        kind.is_some_and(|kind| self.retry_on.contains(&kind))
+    }
+
+    // n回目のリトライ（1始まり）までの待機時間
+    pub fn backoff(&self, retry: u32) -> Duration {
+        let exponent = retry.saturating_sub(1).min(i32::MAX as u32) as i32;
+        let factor = self.backoff_multiplier.powi(exponent);
+        let secs = (self.initial_backoff_secs as f64 * factor).min(self.max_backoff_secs as f64);
+        // 検証前の設定ファイルの値でもパニックしないよう、変換できなければ上限まで待つ
+        Duration::try_from_secs_f64(secs).unwrap_or(Duration::from_secs(self.max_backoff_secs))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn backoff_grows_until_the_limit() {
+        let policy = RetryPolicy::default();
+        let secs: Vec<u64> = (1..=7)
+            .map(|retry| policy.backoff(retry).as_secs())
+            .collect();
+        assert_eq!(secs, vec![30, 60, 120, 240, 480, 600, 600]);
+        assert_eq!(policy.backoff(0), Duration::from_secs(30));
+        assert_eq!(policy.backoff(u32::MAX), Duration::from_secs(600));
+    }
+
+    #[test]
+    fn huge_values_do_not_panic() {
+        let policy = RetryPolicy {
+            initial_backoff_secs: u64::MAX,
+            max_backoff_secs: u64::MAX,
+            backoff_multiplier: f64::MAX,
+            ..RetryPolicy::default()
+        };
+        assert!(policy.validate().is_err());
+        assert_eq!(policy.backoff(3), Duration::from_secs(u64::MAX));
+
+        let policy = RetryPolicy {
+            max_backoff_secs: MAX_BACKOFF_LIMIT_SECS,
+            ..RetryPolicy::default()
+        };
+        assert!(policy.validate().is_ok());
+    }
+
+    #[test]
+    fn only_listed_errors_are_retried() {
+        let policy = RetryPolicy::default();
+        assert!(policy.should_retry(Some(ErrorKind::RateLimited)));
+        assert!(policy.should_retry(Some(ErrorKind::Timeout)));
+        assert!(!policy.should_retry(Some(ErrorKind::Private)));
+        assert!(!policy.should_retry(None));
+
+        let policy = RetryPolicy {
+            retry_on: Vec::new(),
+            ..RetryPolicy::default()
+        };
+        assert!(!policy.should_retry(Some(ErrorKind::RateLimited)));
+    }
+}
@@ -0,0 +1,1045 @@
+use crate::jobs::{JobManager, PauseMode};
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::Duration as StdDuration;
+use tauri::{Emitter, Manager};
+use time::{
+    format_description::well_known::Rfc3339, Date, Duration, OffsetDateTime, Time, UtcOffset,
+};
+use tokio::sync::Notify;
+
+// スケジュールファイルのスキーマバージョン
+const SCHEMA_VERSION: u32 = 1;
+// 次の実行時刻が先でも、この間隔で時刻を確認し直す（スリープからの復帰や時計の変更に追従するため）
+const MAX_SLEEP_SECS: u64 = 30;
+// cronの実行時刻からこれ以上遅れた場合は、スリープなどで逃した実行として取り戻さない
+const MISSED_RUN_GRACE_SECS: i64 = 2 * MAX_SLEEP_SECS as i64;
+// 時間枠の終わり・始まりを確認する間隔
+const WINDOW_CHECK_SECS: u64 = 15;
+// cronの次の実行日を探す範囲（2月29日だけの指定でも見つかるように閏年を含む日数）
+const CRON_SEARCH_DAYS: u32 = 366 * 8;
+
+// 現在時刻の取得元（テストや動作確認では任意の時刻を返す実装に差し替える）
+pub trait Clock: Send + Sync {
+    fn now(&self) -> OffsetDateTime;
+}
+
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now(&self) -> OffsetDateTime {
+        OffsetDateTime::now_utc()
+    }
+}
+
+// 実行のタイミング
+// 時刻はフロントエンドから渡されたUTCからのオフセット（分）の現地時刻として扱う
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "camelCase")]
+pub enum Trigger {
+    // 指定した日時に1回だけ（RFC3339）
+    #[serde(rename_all = "camelCase")]
+    Once { at: String },
+    // 毎日、start〜end（HH:MM）の間に1回（アプリの起動が遅れても枠内なら実行し、枠の外では一時停止）
+    #[serde(rename_all = "camelCase")]
+    DailyWindow {
+        start: String,
+        end: String,
+        utc_offset_minutes: i32,
+    },
+    // cron形式（分 時 日 月 曜日）、または@hourly・@daily・@weekly・@monthly
+    #[serde(rename_all = "camelCase")]
+    Cron {
+        expression: String,
+        utc_offset_minutes: i32,
+    },
+}
+
+// 直近の実行結果
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ScheduleResult {
+    pub finished_at: String,
+    pub success: bool,
+    pub error: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Schedule {
+    // 新規作成時は空（保存時に採番）
+    #[serde(default)]
+    pub id: String,
+    pub name: String,
+    // run_yt_dlpと同じ形式のコマンドライン
+    pub command_line: String,
+    #[serde(default)]
+    pub profile: Option<String>,
+    pub trigger: Trigger,
+    #[serde(default = "default_enabled")]
+    pub enabled: bool,
+    // 以下はスケジューラーが管理する
+    #[serde(default)]
+    pub next_run_at: Option<String>,
+    #[serde(default)]
+    pub last_run_at: Option<String>,
+    #[serde(default)]
+    pub last_result: Option<ScheduleResult>,
+}
+
+fn default_enabled() -> bool {
+    true
+}
+
+impl Schedule {
+    pub fn validate(&self) -> Result<(), String> {
+        if self.name.trim().is_empty() {
+            return Err("Schedule name must not be empty".to_string());
+        }
+        match shlex::split(&self.command_line) {
+            Some(args) if !args.is_empty() => {}
+            _ => return Err("Schedule command line is empty or invalid".to_string()),
+        }
+        match &self.trigger {
+            Trigger::Once { at } => {
+                parse_time(at)?;
+            }
+            Trigger::DailyWindow {
+                start,
+                end,
+                utc_offset_minutes,
+            } => {
+                offset(*utc_offset_minutes)?;
+                if parse_hhmm(start)? == parse_hhmm(end)? {
+                    return Err("Daily window start and end must differ".to_string());
+                }
+            }
+            Trigger::Cron {
+                expression,
+                utc_offset_minutes,
+            } => {
+                offset(*utc_offset_minutes)?;
+                CronSchedule::parse(expression)?;
+            }
+        }
+        Ok(())
+    }
+
+    // 次の実行時刻（1回だけのスケジュールが実行済みならNone）
+    fn compute_next_run(&self, now: OffsetDateTime) -> Option<OffsetDateTime> {
+        let last_run = self
+            .last_run_at
+            .as_deref()
+            .and_then(|last_run| parse_time(last_run).ok());
+        match &self.trigger {
+            // 起動していなかった間に過ぎた日時は、起動時にすぐ実行する
+            Trigger::Once { at } => match last_run {
+                Some(_) => None,
+                None => parse_time(at).ok(),
+            },
+            Trigger::DailyWindow {
+                start,
+                end,
+                utc_offset_minutes,
+            } => {
+                let offset = offset(*utc_offset_minutes).ok()?;
+                next_in_window(
+                    parse_hhmm(start).ok()?,
+                    parse_hhmm(end).ok()?,
+                    offset,
+                    now,
+                    last_run,
+                )
+            }
+            // 起動していなかった間の実行は取り戻さず、次の時刻から
+            Trigger::Cron {
+                expression,
+                utc_offset_minutes,
+            } => {
+                let offset = offset(*utc_offset_minutes).ok()?;
+                CronSchedule::parse(expression)
+                    .ok()?
+                    .next_after(now.to_offset(offset))
+            }
+        }
+    }
+
+    // 実行時刻を過ぎたスケジュールを今実行してよいか
+    // スリープからの復帰などで遅れた場合、時間枠の外やcronの時刻から離れた実行は取り戻さない
+    fn is_current(&self, scheduled: OffsetDateTime, now: OffsetDateTime) -> bool {
+        match &self.trigger {
+            Trigger::Once { .. } => true,
+            Trigger::DailyWindow { .. } => self.compute_next_run(now) == Some(now),
+            Trigger::Cron { .. } => now - scheduled <= Duration::seconds(MISSED_RUN_GRACE_SECS),
+        }
+    }
+
+    // 時間枠の中かどうか（時間枠のスケジュール以外はNone）
+    fn in_window(&self, now: OffsetDateTime) -> Option<bool> {
+        let Trigger::DailyWindow {
+            start,
+            end,
+            utc_offset_minutes,
+        } = &self.trigger
+        else {
+            return None;
+        };
+        let offset = offset(*utc_offset_minutes).ok()?;
+        let window = current_window(parse_hhmm(start).ok()?, parse_hhmm(end).ok()?, offset, now);
+        Some(window.is_some())
+    }
+}
+
+// ファイルに保存する形式
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct ScheduleFile {
+    schema_version: u32,
+    schedules: Vec<Schedule>,
+}
+
+// スケジュールの保存先と実行待ちのループへの通知
+pub struct Scheduler {
+    path: PathBuf,
+    clock: Arc<dyn Clock>,
+    schedules: Mutex<Vec<Schedule>>,
+    // 実行中のスケジュールのID（前回の実行が終わるまで重ねて実行しない）
+    running: Mutex<HashSet<String>>,
+    changed: Notify,
+}
+
+impl Scheduler {
+    // スケジュールを読み込み、現在時刻から次の実行時刻を計算し直す
+    pub fn load(data_dir: &Path, clock: Arc<dyn Clock>) -> Scheduler {
+        let path = data_dir.join("schedules.json");
+        let schedules = match fs::read_to_string(&path) {
+            Ok(content) => match serde_json::from_str::<ScheduleFile>(&content) {
+                Ok(file) => file.schedules,
+                Err(e) => {
+                    log::error!("Failed to load schedules: {}", e);
+                    let backup = path.with_extension("json.bak");
+                    if let Err(e) = fs::rename(&path, &backup) {
+                        log::warn!("Failed to back up broken schedules file: {}", e);
+                    }
+                    Vec::new()
+                }
+            },
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Vec::new(),
+            Err(e) => {
+                log::error!("Could not read schedules file: {}", e);
+                Vec::new()
+            }
+        };
+
+        let scheduler = Scheduler {
+            path,
+            clock,
+            schedules: Mutex::new(schedules),
+            running: Mutex::new(HashSet::new()),
+            changed: Notify::new(),
+        };
+        if scheduler.list().is_empty() {
+            return scheduler;
+        }
+        if let Err(e) = scheduler.modify(|schedules| {
+            let now = scheduler.clock.now();
+            for schedule in schedules.iter_mut() {
+                refresh_next_run(schedule, now);
+            }
+            Ok(())
+        }) {
+            log::warn!("Failed to save recomputed schedules: {}", e);
+        }
+        scheduler
+    }
+
+    pub fn list(&self) -> Vec<Schedule> {
+        self.schedules.lock().unwrap().clone()
+    }
+
+    // スケジュールを変更して保存（保存に失敗したら変更を戻す）
+    fn modify<T, F>(&self, f: F) -> Result<T, String>
+    where
+        F: FnOnce(&mut Vec<Schedule>) -> Result<T, String>,
+    {
+        let mut schedules = self.schedules.lock().unwrap();
+        let mut updated = schedules.clone();
+        let result = f(&mut updated)?;
+        write_schedules(&self.path, &updated)?;
+        *schedules = updated;
+        self.changed.notify_one();
+        Ok(result)
+    }
+
+    // 追加または更新（実行履歴は保存済みのものを引き継ぐ）
+    pub fn save(&self, mut schedule: Schedule) -> Result<Schedule, String> {
+        schedule.validate()?;
+        let now = self.clock.now();
+        self.modify(|schedules| {
+            if schedule.id.is_empty() {
+                schedule.id = crate::history::new_entry_id();
+                schedule.last_run_at = None;
+                schedule.last_result = None;
+            }
+            match schedules.iter_mut().find(|s| s.id == schedule.id) {
+                Some(existing) => {
+                    schedule.last_run_at = existing.last_run_at.clone();
+                    schedule.last_result = existing.last_result.clone();
+                    // 1回だけのスケジュールは日時を変更したら再び実行できるようにする
+                    if existing.trigger != schedule.trigger {
+                        if let Trigger::Once { .. } = schedule.trigger {
+                            schedule.last_run_at = None;
+                        }
+                    }
+                    refresh_next_run(&mut schedule, now);
+                    *existing = schedule.clone();
+                }
+                None => {
+                    refresh_next_run(&mut schedule, now);
+                    schedules.push(schedule.clone());
+                }
+            }
+            Ok(schedule)
+        })
+    }
+
+    pub fn delete(&self, id: &str) -> Result<bool, String> {
+        self.modify(|schedules| {
+            let before = schedules.len();
+            schedules.retain(|schedule| schedule.id != id);
+            Ok(schedules.len() != before)
+        })
+    }
+
+    // 実行時刻を過ぎたスケジュールを実行済みにして返し、次の実行時刻を更新
+    fn take_due(&self) -> Vec<Schedule> {
+        let now = self.clock.now();
+        let any_due = self
+            .schedules
+            .lock()
+            .unwrap()
+            .iter()
+            .any(|schedule| schedule.enabled && is_due(schedule, now));
+        if !any_due {
+            return Vec::new();
+        }
+        let result = self.modify(|schedules| {
+            let mut running = self.running.lock().unwrap();
+            let mut due = Vec::new();
+            for schedule in schedules.iter_mut() {
+                let Some(scheduled) = scheduled_at(schedule).filter(|_| schedule.enabled) else {
+                    continue;
+                };
+                if scheduled > now {
+                    continue;
+                }
+                if !schedule.is_current(scheduled, now) {
+                    log::info!(
+                        "Skipping missed run of schedule {} ({})",
+                        schedule.name,
+                        schedule.id
+                    );
+                    refresh_next_run(schedule, now);
+                    continue;
+                }
+                schedule.last_run_at = Some(format_time(now));
+                refresh_next_run(schedule, now);
+                if !running.insert(schedule.id.clone()) {
+                    // 時間枠で一時停止した前回の実行は、枠の始まりで再開する
+                    log::info!(
+                        "Schedule {} ({}) is still running, not starting another run",
+                        schedule.name,
+                        schedule.id
+                    );
+                    continue;
+                }
+                due.push(schedule.clone());
+            }
+            Ok(due)
+        });
+        result.unwrap_or_else(|e| {
+            // 保存できない状態で実行すると、再起動のたびに同じ実行を繰り返すため実行しない
+            log::error!("Failed to update schedules before running: {}", e);
+            Vec::new()
+        })
+    }
+
+    fn record_result(&self, id: &str, result: ScheduleResult) -> Option<Schedule> {
+        self.running.lock().unwrap().remove(id);
+        self.modify(|schedules| {
+            Ok(schedules
+                .iter_mut()
+                .find(|schedule| schedule.id == id)
+                .map(|schedule| {
+                    schedule.last_result = Some(result);
+                    schedule.clone()
+                }))
+        })
+        .unwrap_or_else(|e| {
+            log::warn!("Failed to record schedule result: {}", e);
+            None
+        })
+    }
+
+    // 次の確認までの待ち時間
+    fn sleep_duration(&self) -> StdDuration {
+        let now = self.clock.now();
+        let next = self
+            .schedules
+            .lock()
+            .unwrap()
+            .iter()
+            .filter(|schedule| schedule.enabled)
+            .filter_map(|schedule| schedule.next_run_at.as_deref())
+            .filter_map(|next| parse_time(next).ok())
+            .min();
+        let max = StdDuration::from_secs(MAX_SLEEP_SECS);
+        match next {
+            Some(next) if next <= now => StdDuration::ZERO,
+            Some(next) => StdDuration::try_from(next - now).map_or(max, |wait| wait.min(max)),
+            None => max,
+        }
+    }
+}
+
+fn is_due(schedule: &Schedule, now: OffsetDateTime) -> bool {
+    scheduled_at(schedule).is_some_and(|next| next <= now)
+}
+
+fn scheduled_at(schedule: &Schedule) -> Option<OffsetDateTime> {
+    schedule
+        .next_run_at
+        .as_deref()
+        .and_then(|next| parse_time(next).ok())
+}
+
+fn refresh_next_run(schedule: &mut Schedule, now: OffsetDateTime) {
+    schedule.next_run_at = if schedule.enabled {
+        schedule.compute_next_run(now).map(format_time)
+    } else {
+        None
+    };
+}
+
+// 実行時刻になったスケジュールをジョブとして開始するループを起動
+pub fn start(app: tauri::AppHandle) {
+    tauri::async_runtime::spawn(async move {
+        loop {
+            let scheduler = app.state::<Scheduler>();
+            let wait = scheduler.sleep_duration();
+            if !wait.is_zero() {
+                tokio::select! {
+                    _ = tokio::time::sleep(wait) => {}
+                    _ = scheduler.changed.notified() => {}
+                }
+            }
+
+            for schedule in scheduler.take_due() {
+                log::info!(
+                    "Starting scheduled job: {} ({})",
+                    schedule.name,
+                    schedule.id
+                );
+                let _ = app.emit("schedule-updated", &schedule);
+                let app = app.clone();
+                tauri::async_runtime::spawn(async move {
+                    let result = run_schedule(&app, &schedule).await;
+                    let result = ScheduleResult {
+                        finished_at: crate::history::now_rfc3339(),
+                        success: result.as_ref().is_ok_and(|success| *success),
+                        error: result.err(),
+                    };
+                    if let Some(updated) =
+                        app.state::<Scheduler>().record_result(&schedule.id, result)
+                    {
+                        let _ = app.emit("schedule-updated", &updated);
+                    }
+                });
+            }
+        }
+    });
+}
+
+// スケジュールのコマンドラインを通常のダウンロードと同じ処理で実行
+async fn run_schedule(app: &tauri::AppHandle, schedule: &Schedule) -> Result<bool, String> {
+    let args = shlex::split(&schedule.command_line)
+        .ok_or_else(|| "Invalid command line syntax - failed to parse arguments".to_string())?;
+    // ウィンドウを閉じている・隠している間も実行できるようにアプリに送信する
+    let context = crate::engine::Context::from_app(app);
+    let retry_policy = context.settings.get().retry_policy;
+    let job_id = crate::history::new_entry_id();
+    let job = crate::engine::execute_job(
+        &context,
+        job_id.clone(),
+        args,
+        schedule.profile.clone(),
+        retry_policy,
+    );
+    let report = tokio::select! {
+        report = job => report?,
+        _ = enforce_window(app, schedule, &job_id) => unreachable!("window check never finishes"),
+    };
+    Ok(report.success)
+}
+
+// 時間枠のスケジュールは、枠の終わりでジョブを一時停止し、次の枠の始まりで再開する（終了しない）
+// ユーザーが一時停止・再開した場合はそちらを優先する
+async fn enforce_window(app: &tauri::AppHandle, schedule: &Schedule, job_id: &str) {
+    let scheduler = app.state::<Scheduler>();
+    if schedule.in_window(scheduler.clock.now()).is_none() {
+        return std::future::pending().await;
+    }
+    let jobs = app.state::<JobManager>();
+    let mut paused = false;
+    loop {
+        let inside = schedule.in_window(scheduler.clock.now()).unwrap_or(true);
+        if !inside && !paused && jobs.pause_mode(job_id).is_none() {
+            // ジョブの登録前や終了後は失敗するため、次の確認でやり直す
+            if jobs.pause(job_id, PauseMode::Restart).is_ok() {
+                log::info!(
+                    "Daily window of schedule {} ended, pausing job {}",
+                    schedule.name,
+                    job_id
+                );
+                paused = true;
+            }
+        } else if inside && paused {
+            log::info!(
+                "Daily window of schedule {} started, resuming job {}",
+                schedule.name,
+                job_id
+            );
+            let _ = jobs.resume(job_id);
+            paused = false;
+        }
+        tokio::time::sleep(StdDuration::from_secs(WINDOW_CHECK_SECS)).await;
+    }
+}
+
+// 一時ファイルに書き込んでから置き換え、書き込み途中の破損を防ぐ
+fn write_schedules(path: &Path, schedules: &[Schedule]) -> Result<(), String> {
+    let json = serde_json::to_string_pretty(&ScheduleFile {
+        schema_version: SCHEMA_VERSION,
+        schedules: schedules.to_vec(),
+    })
+    .map_err(|e| {
+        log::error!("Failed to serialize schedules: {}", e);
+        format!("Failed to serialize schedules: {}", e)
+    })?;
+    let tmp_path = path.with_extension("json.tmp");
+    fs::write(&tmp_path, json).map_err(|e| {
+        log::error!("Failed to write schedules file: {}", e);
+        format!("Failed to write schedules file: {}", e)
+    })?;
+    fs::rename(&tmp_path, path).map_err(|e| {
+        log::error!("Failed to save schedules file: {}", e);
+        format!("Failed to save schedules file: {}", e)
+    })
+}
+
+fn parse_time(text: &str) -> Result<OffsetDateTime, String> {
+    OffsetDateTime::parse(text, &Rfc3339)
+        .map_err(|e| format!("Invalid date time {:?}: {}", text, e))
+}
+
+fn format_time(datetime: OffsetDateTime) -> String {
+    datetime.format(&Rfc3339).unwrap_or_default()
+}
+
+pub fn offset(minutes: i32) -> Result<UtcOffset, String> {
+    UtcOffset::from_whole_seconds(minutes * 60)
+        .map_err(|_| format!("Invalid UTC offset: {} minutes", minutes))
+}
+
+pub fn parse_hhmm(text: &str) -> Result<Time, String> {
+    let (hour, minute) = text
+        .split_once(':')
+        .and_then(|(hour, minute)| Some((hour.parse().ok()?, minute.parse().ok()?)))
+        .ok_or_else(|| format!("Invalid time {:?}: expected HH:MM", text))?;
+    Time::from_hms(hour, minute, 0).map_err(|_| format!("Invalid time {:?}: expected HH:MM", text))
+}
+
+// 時間枠の中なら今（その枠で未実行の場合）、そうでなければ次の枠の開始時刻
+// endがstartより前なら日付をまたぐ枠（例: 22:00〜06:00）
+fn next_in_window(
+    start: Time,
+    end: Time,
+    offset: UtcOffset,
+    now: OffsetDateTime,
+    last_run: Option<OffsetDateTime>,
+) -> Option<OffsetDateTime> {
+    if let Some((begin, _)) = current_window(start, end, offset, now) {
+        if last_run.is_none_or(|last_run| last_run < begin) {
+            return Some(now);
+        }
+    }
+    let today = now.to_offset(offset).date();
+    let (begin, _) = window_on(start, end, offset, today)?;
+    if begin > now {
+        Some(begin)
+    } else {
+        window_on(start, end, offset, today.next_day()?).map(|(begin, _)| begin)
+    }
+}
+
+// nowを含む時間枠の開始・終了（前日から続く枠も含む）
+fn current_window(
+    start: Time,
+    end: Time,
+    offset: UtcOffset,
+    now: OffsetDateTime,
+) -> Option<(OffsetDateTime, OffsetDateTime)> {
+    let today = now.to_offset(offset).date();
+    [today.previous_day()?, today]
+        .into_iter()
+        .filter_map(|date| window_on(start, end, offset, date))
+        .find(|(begin, finish)| *begin <= now && now < *finish)
+}
+
+// dateに始まる時間枠の開始・終了
+fn window_on(
+    start: Time,
+    end: Time,
+    offset: UtcOffset,
+    date: Date,
+) -> Option<(OffsetDateTime, OffsetDateTime)> {
+    let begin = date.with_time(start).assume_offset(offset);
+    let end_date = if end > start { date } else { date.next_day()? };
+    Some((begin, end_date.with_time(end).assume_offset(offset)))
+}
+
+// cron形式の実行時刻（各フィールドで一致する値をビットで持つ）
+struct CronSchedule {
+    minutes: u64,
+    hours: u64,
+    days: u64,
+    months: u64,
+    weekdays: u64,
+    // 日と曜日の両方が指定された場合は、どちらかが一致すれば実行（cronと同じ）
+    days_restricted: bool,
+    weekdays_restricted: bool,
+}
+
+impl CronSchedule {
+    fn parse(expression: &str) -> Result<CronSchedule, String> {
+        let expression = match expression.trim() {
+            "@hourly" => "0 * * * *",
+            "@daily" | "@midnight" => "0 0 * * *",
+            "@weekly" => "0 0 * * 0",
+            "@monthly" => "0 0 1 * *",
+            "@yearly" | "@annually" => "0 0 1 1 *",
+            other => other,
+        };
+        let fields: Vec<&str> = expression.split_whitespace().collect();
+        let [minute, hour, day, month, weekday] = fields.as_slice() else {
+            return Err(format!(
+                "Invalid cron expression {:?}: expected 5 fields (minute hour day month weekday)",
+                expression
+            ));
+        };
+        // 曜日の7は日曜日（0）として扱う
+        // 「*/2」のように「*」で始まる日・曜日は、cronと同じく指定なしとして扱う
+        let mut weekdays = parse_cron_field(weekday, 0, 7)?;
+        if weekdays & (1 << 7) != 0 {
+            weekdays |= 1;
+        }
+        Ok(CronSchedule {
+            minutes: parse_cron_field(minute, 0, 59)?,
+            hours: parse_cron_field(hour, 0, 23)?,
+            days: parse_cron_field(day, 1, 31)?,
+            months: parse_cron_field(month, 1, 12)?,
+            weekdays,
+            days_restricted: !day.starts_with('*'),
+            weekdays_restricted: !weekday.starts_with('*'),
+        })
+    }
+
+    fn matches_date(&self, date: Date) -> bool {
+        if self.months & (1 << u8::from(date.month())) == 0 {
+            return false;
+        }
+        let day = self.days & (1 << date.day()) != 0;
+        let weekday = self.weekdays & (1 << date.weekday().number_days_from_sunday()) != 0;
+        match (self.days_restricted, self.weekdays_restricted) {
+            (true, true) => day || weekday,
+            _ => day && weekday,
+        }
+    }
+
+    // afterより後（分単位）で最初に一致する時刻
+    fn next_after(&self, after: OffsetDateTime) -> Option<OffsetDateTime> {
+        let start =
+            after.replace_second(0).ok()?.replace_nanosecond(0).ok()? + Duration::minutes(1);
+        let mut date = start.date();
+        for _ in 0..CRON_SEARCH_DAYS {
+            if self.matches_date(date) {
+                let first_day = date == start.date();
+                let from_hour = if first_day { start.hour() } else { 0 };
+                for hour in from_hour..24 {
+                    if self.hours & (1 << hour) == 0 {
+                        continue;
+                    }
+                    let from_minute = if first_day && hour == from_hour {
+                        start.minute()
+                    } else {
+                        0
+                    };
+                    if let Some(minute) = (from_minute..60).find(|m| self.minutes & (1 << m) != 0) {
+                        let time = Time::from_hms(hour, minute, 0).ok()?;
+                        return Some(date.with_time(time).assume_offset(after.offset()));
+                    }
+                }
+            }
+            date = date.next_day()?;
+        }
+        None
+    }
+}
+
+// 「*」「5」「1-5」「*/15」「1-30/5」とそのカンマ区切りに対応
+fn parse_cron_field(field: &str, min: u32, max: u32) -> Result<u64, String> {
+    let invalid = || format!("Invalid cron field {:?}", field);
+    let mut bits = 0u64;
+    for part in field.split(',') {
+        let (range, step) = match part.split_once('/') {
+            Some((range, step)) => (range, step.parse::<u32>().map_err(|_| invalid())?),
+            None => (part, 1),
+        };
+        let (from, to) = match range {
+            "*" => (min, max),
+            _ => match range.split_once('-') {
+                Some((from, to)) => (
+                    from.parse().map_err(|_| invalid())?,
+                    to.parse().map_err(|_| invalid())?,
+                ),
+                None => {
+                    let value = range.parse().map_err(|_| invalid())?;
+                    // 「5/15」は5から最大値まで
+                    (value, if step > 1 { max } else { value })
+                }
+            },
+        };
+        if step == 0 || from < min || to > max || from > to {
+            return Err(invalid());
+        }
+        for value in (from..=to).step_by(step as usize) {
+            bits |= 1 << value;
+        }
+    }
+    Ok(bits)
+}
+
+// 登録済みのスケジュール一覧
+#[tauri::command]
+pub async fn list_schedules(
+    scheduler: tauri::State<'_, Scheduler>,
+) -> Result<Vec<Schedule>, String> {
+    Ok(scheduler.list())
+}
+
+// スケジュールを追加・更新し、次の実行時刻を含めた内容をリターン
+#[tauri::command]
+pub async fn save_schedule(
+    schedule: Schedule,
+    scheduler: tauri::State<'_, Scheduler>,
+) -> Result<Schedule, String> {
+    log::info!(
+        "Invoked save_schedule with id: {:?}, name: {:?}, trigger: {:?}",
+        schedule.id,
+        schedule.name,
+        schedule.trigger
+    );
+    scheduler.save(schedule)
+}
+
+#[tauri::command]
+pub async fn delete_schedule(
+    id: String,
+    scheduler: tauri::State<'_, Scheduler>,
+) -> Result<bool, String> {
+    log::info!("Invoked delete_schedule with id: {:?}", id);
+    scheduler.delete(&id)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use time::Month;
+
+    // 任意の時刻を返し、テスト中に進められる時計
+    struct FakeClock(Mutex<OffsetDateTime>);
+
+    impl FakeClock {
+        fn set(&self, now: OffsetDateTime) {
+            *self.0.lock().unwrap() = now;
+        }
+    }
+
+    impl Clock for FakeClock {
+        fn now(&self) -> OffsetDateTime {
+            *self.0.lock().unwrap()
+        }
+    }
+
+    // 2026年1月の日時（UTC）。1月5日は月曜日
+    fn at(day: u8, hour: u8, minute: u8) -> OffsetDateTime {
+        Date::from_calendar_date(2026, Month::January, day)
+            .unwrap()
+            .with_hms(hour, minute, 0)
+            .unwrap()
+            .assume_utc()
+    }
+
+    fn schedule(trigger: Trigger) -> Schedule {
+        Schedule {
+            id: String::new(),
+            name: "test".to_string(),
+            command_line: "https://example.com/video".to_string(),
+            profile: None,
+            trigger,
+            enabled: true,
+            next_run_at: None,
+            last_run_at: None,
+            last_result: None,
+        }
+    }
+
+    fn cron(expression: &str) -> Schedule {
+        schedule(Trigger::Cron {
+            expression: expression.to_string(),
+            utc_offset_minutes: 0,
+        })
+    }
+
+    fn daily(start: &str, end: &str, utc_offset_minutes: i32) -> Schedule {
+        schedule(Trigger::DailyWindow {
+            start: start.to_string(),
+            end: end.to_string(),
+            utc_offset_minutes,
+        })
+    }
+
+    #[test]
+    fn cron_steps_and_ranges() {
+        assert_eq!(
+            cron("*/15 * * * *").compute_next_run(at(5, 10, 7)),
+            Some(at(5, 10, 15))
+        );
+        // 一致する時刻ちょうどなら次の時刻
+        assert_eq!(
+            cron("*/15 * * * *").compute_next_run(at(5, 10, 15)),
+            Some(at(5, 10, 30))
+        );
+        assert_eq!(
+            cron("30 8-18/2 * * *").compute_next_run(at(5, 9, 0)),
+            Some(at(5, 10, 30))
+        );
+        // 金曜日の後は月曜日
+        assert_eq!(
+            cron("0 9 * * 1-5").compute_next_run(at(9, 10, 0)),
+            Some(at(12, 9, 0))
+        );
+        // 7は日曜日
+        assert_eq!(
+            cron("0 12 * * 7").compute_next_run(at(5, 0, 0)),
+            Some(at(11, 12, 0))
+        );
+        assert_eq!(
+            cron("@daily").compute_next_run(at(5, 23, 59)),
+            Some(at(6, 0, 0))
+        );
+    }
+
+    #[test]
+    fn cron_day_and_weekday() {
+        // 日と曜日の両方を指定したらどちらかが一致する日
+        assert_eq!(
+            cron("0 0 1 * 1").compute_next_run(at(2, 0, 0)),
+            Some(at(5, 0, 0))
+        );
+        // 「*/2」は指定なしとして扱い、両方が一致する日（奇数日の月曜日）
+        assert_eq!(
+            cron("0 0 */2 * 1").compute_next_run(at(6, 0, 0)),
+            Some(at(19, 0, 0))
+        );
+        // 曜日の「*/2」（日・火・木・土）も指定なしとして扱い、日だけで決まる
+        assert_eq!(
+            cron("0 0 10 * */2").compute_next_run(at(2, 0, 0)),
+            Some(at(10, 0, 0))
+        );
+    }
+
+    #[test]
+    fn cron_uses_local_time() {
+        // UTC+9の9:00はUTCの0:00
+        let schedule = schedule(Trigger::Cron {
+            expression: "0 9 * * *".to_string(),
+            utc_offset_minutes: 540,
+        });
+        assert_eq!(schedule.compute_next_run(at(5, 0, 30)), Some(at(6, 0, 0)));
+    }
+
+    #[test]
+    fn daily_window_across_midnight() {
+        let window = daily("22:00", "06:00", 0);
+        // 枠の外なら次の枠の開始
+        assert_eq!(window.compute_next_run(at(5, 12, 0)), Some(at(5, 22, 0)));
+        assert_eq!(window.compute_next_run(at(5, 6, 0)), Some(at(5, 22, 0)));
+        // 枠の中で未実行ならすぐ（前日から続く枠も含む）
+        assert_eq!(window.compute_next_run(at(5, 23, 0)), Some(at(5, 23, 0)));
+        assert_eq!(window.compute_next_run(at(5, 3, 0)), Some(at(5, 3, 0)));
+
+        // その枠で実行済みなら次の枠
+        let mut ran = window.clone();
+        ran.last_run_at = Some(format_time(at(4, 22, 30)));
+        assert_eq!(ran.compute_next_run(at(5, 3, 0)), Some(at(5, 22, 0)));
+        ran.last_run_at = Some(format_time(at(5, 22, 0)));
+        assert_eq!(ran.compute_next_run(at(5, 23, 0)), Some(at(6, 22, 0)));
+    }
+
+    #[test]
+    fn daily_window_uses_local_time() {
+        // UTC+9の22:00〜翌6:00はUTCの13:00〜21:00
+        let window = daily("22:00", "06:00", 540);
+        assert_eq!(window.compute_next_run(at(5, 12, 0)), Some(at(5, 13, 0)));
+        assert_eq!(window.compute_next_run(at(5, 20, 0)), Some(at(5, 20, 0)));
+        assert_eq!(window.compute_next_run(at(5, 21, 0)), Some(at(6, 13, 0)));
+    }
+
+    #[test]
+    fn once_in_the_past_runs_once() {
+        let clock = Arc::new(FakeClock(Mutex::new(at(10, 12, 0))));
+        let dir = std::env::temp_dir().join(format!(
+            "takumi-vid-dl-scheduler-once-{}",
+            std::process::id()
+        ));
+        fs::create_dir_all(&dir).unwrap();
+        let scheduler = Scheduler::load(&dir, clock.clone());
+
+        // 起動していなかった間に過ぎた日時はすぐに実行する
+        let saved = scheduler
+            .save(schedule(Trigger::Once {
+                at: format_time(at(5, 9, 0)),
+            }))
+            .unwrap();
+        assert_eq!(saved.next_run_at, Some(format_time(at(5, 9, 0))));
+        assert_eq!(scheduler.sleep_duration(), StdDuration::ZERO);
+
+        let due = scheduler.take_due();
+        assert_eq!(due.len(), 1);
+        assert_eq!(due[0].next_run_at, None);
+        assert_eq!(due[0].last_run_at, Some(format_time(at(10, 12, 0))));
+
+        // 実行済みなら読み込み直しても再び実行しない
+        clock.set(at(11, 12, 0));
+        assert!(scheduler.take_due().is_empty());
+        let reloaded = Scheduler::load(&dir, clock.clone());
+        assert!(reloaded.take_due().is_empty());
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn cron_advances_with_the_clock() {
+        let clock = Arc::new(FakeClock(Mutex::new(at(5, 10, 30))));
+        let dir = std::env::temp_dir().join(format!(
+            "takumi-vid-dl-scheduler-cron-{}",
+            std::process::id()
+        ));
+        fs::create_dir_all(&dir).unwrap();
+        let scheduler = Scheduler::load(&dir, clock.clone());
+
+        let saved = scheduler.save(cron("0 * * * *")).unwrap();
+        assert_eq!(saved.next_run_at, Some(format_time(at(5, 11, 0))));
+        assert!(scheduler.take_due().is_empty());
+
+        clock.set(at(5, 11, 0));
+        let due = scheduler.take_due();
+        assert_eq!(due.len(), 1);
+        assert_eq!(due[0].next_run_at, Some(format_time(at(5, 12, 0))));
+        assert!(scheduler.take_due().is_empty());
+
+        // 起動していなかった間の実行は取り戻さない
+        clock.set(at(5, 15, 30));
+        let reloaded = Scheduler::load(&dir, clock.clone());
+        assert!(reloaded.take_due().is_empty());
+        assert_eq!(
+            reloaded.list()[0].next_run_at,
+            Some(format_time(at(5, 16, 0)))
+        );
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn missed_runs_after_suspend_are_skipped() {
+        let clock = Arc::new(FakeClock(Mutex::new(at(5, 0, 30))));
+        let dir = std::env::temp_dir().join(format!(
+            "takumi-vid-dl-scheduler-missed-{}",
+            std::process::id()
+        ));
+        fs::create_dir_all(&dir).unwrap();
+        let scheduler = Scheduler::load(&dir, clock.clone());
+
+        let window = scheduler.save(daily("01:00", "03:00", 0)).unwrap();
+        let hourly = scheduler.save(cron("0 * * * *")).unwrap();
+        assert_eq!(window.next_run_at, Some(format_time(at(5, 1, 0))));
+        assert_eq!(hourly.next_run_at, Some(format_time(at(5, 1, 0))));
+
+        // 1:00の実行時刻をスリープで逃し、時間枠の外の9:00に復帰
+        clock.set(at(5, 9, 0));
+        assert!(scheduler.take_due().is_empty());
+        let schedules = scheduler.list();
+        assert_eq!(schedules[0].next_run_at, Some(format_time(at(6, 1, 0))));
+        assert_eq!(schedules[0].last_run_at, None);
+        assert_eq!(schedules[1].next_run_at, Some(format_time(at(5, 10, 0))));
+
+        // 遅れが少なければcronの実行はそのまま行う
+        clock.set(at(5, 10, 0) + Duration::seconds(20));
+        let due = scheduler.take_due();
+        assert_eq!(due.len(), 1);
+        assert_eq!(due[0].id, hourly.id);
+
+        // 前回の実行が終わっていなければ重ねて実行しない
+        clock.set(at(5, 11, 0));
+        assert!(scheduler.take_due().is_empty());
+        assert_eq!(
+            scheduler.list()[1].next_run_at,
+            Some(format_time(at(5, 12, 0)))
+        );
+        scheduler.record_result(
+            &hourly.id,
+            ScheduleResult {
+                finished_at: format_time(at(5, 11, 30)),
+                success: true,
+                error: None,
+            },
+        );
+        clock.set(at(5, 12, 0));
+        assert_eq!(scheduler.take_due().len(), 1);
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn window_membership_follows_the_clock() {
+        let clock = FakeClock(Mutex::new(at(5, 21, 0)));
+        let window = daily("22:00", "06:00", 0);
+        assert_eq!(window.in_window(clock.now()), Some(false));
+        clock.set(at(5, 22, 0));
+        assert_eq!(window.in_window(clock.now()), Some(true));
+        clock.set(at(6, 5, 59));
+        assert_eq!(window.in_window(clock.now()), Some(true));
+        // 枠の終わりで一時停止する
+        clock.set(at(6, 6, 0));
+        assert_eq!(window.in_window(clock.now()), Some(false));
+        assert_eq!(cron("0 * * * *").in_window(clock.now()), None);
+
+        // 時間枠の外で期限を過ぎていても実行しない
+        assert!(!window.is_current(at(5, 22, 0), at(6, 9, 0)));
+        assert!(window.is_current(at(5, 22, 0), at(6, 1, 0)));
+    }
+}
@@ -0,0 +1,410 @@
+use crate::api::ApiConfig;
+use crate::bandwidth::BandwidthConfig;
+use crate::clipboard::ClipboardConfig;
+use crate::disk::DiskSpaceConfig;
+use crate::hooks::HookConfig;
+use crate::retry::RetryPolicy;
+use crate::throttle::ThrottleConfig;
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+// 現在の設定ファイルのスキーマバージョン
+pub const SCHEMA_VERSION: u32 = 1;
+
+// 古いバージョンの設定を1つ新しいバージョンに変換する処理（MIGRATIONS[n - 1]がnをn + 1に変換）
+// フィールドの追加だけなら#[serde(default)]で読み込めるため、名前や形式を変えたときに追加する
+type Migration = fn(&mut Map<String, Value>);
+const MIGRATIONS: &[Migration] = &[];
+
+// yt-dlpのオプションの指定方法
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum OptionMode {
+    #[default]
+    Auto,
+    Custom,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct Settings {
+    pub schema_version: u32,
+    pub output_dir: String,
+    pub ffmpeg_dir: String,
+    pub option_mode: OptionMode,
+    pub custom_options: String,
+    pub retry_policy: RetryPolicy,
+    pub throttle: ThrottleConfig,
+    pub disk_space: DiskSpaceConfig,
+    pub clipboard: ClipboardConfig,
+    pub api: ApiConfig,
+    pub hooks: HookConfig,
+    pub bandwidth: BandwidthConfig,
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Settings {
+            schema_version: SCHEMA_VERSION,
+            output_dir: String::new(),
+            ffmpeg_dir: String::new(),
+            option_mode: OptionMode::default(),
+            custom_options: String::new(),
+            retry_policy: RetryPolicy::default(),
+            throttle: ThrottleConfig::default(),
+            disk_space: DiskSpaceConfig::default(),
+            clipboard: ClipboardConfig::default(),
+            api: ApiConfig::default(),
+            hooks: HookConfig::default(),
+            bandwidth: BandwidthConfig::default(),
+        }
+    }
+}
+
+impl Settings {
+    // 変更されたフィールドだけを検証（previousは変更前の設定）
+    // 接続されていないドライブなど、変更していないフィールドの問題で他の設定を保存できなくならないようにする
+    pub fn validate_changes(&self, previous: &Settings) -> Result<(), String> {
+        if self.output_dir != previous.output_dir {
+            validate_dir("outputDir", &self.output_dir)?;
+        }
+        if self.ffmpeg_dir != previous.ffmpeg_dir {
+            validate_dir("ffmpegDir", &self.ffmpeg_dir)?;
+        }
+        if self.custom_options != previous.custom_options
+            && shlex::split(&self.custom_options).is_none()
+        {
+            return Err("customOptions has invalid command line syntax".to_string());
+        }
+        if self.retry_policy != previous.retry_policy {
+            self.retry_policy.validate()?;
+        }
+        if self.throttle != previous.throttle {
+            self.throttle.validate()?;
+        }
+        if self.disk_space != previous.disk_space {
+            self.disk_space.validate()?;
+        }
+        if self.clipboard != previous.clipboard {
+            self.clipboard.validate()?;
+        }
+        if self.api != previous.api {
+            self.api.validate()?;
+        }
+        if self.hooks != previous.hooks {
+            self.hooks.validate()?;
+        }
+        if self.bandwidth != previous.bandwidth {
+            self.bandwidth.validate()?;
+        }
+        Ok(())
+    }
+}
+
+// まだないフォルダはyt-dlpが作成するため、ファイルを指定した場合だけエラーにする
+fn validate_dir(name: &str, dir: &str) -> Result<(), String> {
+    if !dir.is_empty() && Path::new(dir).is_file() {
+        return Err(format!("{} is a file, not a directory: {}", name, dir));
+    }
+    Ok(())
+}
+
+// 設定ファイルの場所と現在の設定
+pub struct SettingsStore {
+    path: PathBuf,
+    settings: Mutex<Settings>,
+}
+
+impl SettingsStore {
+    // 設定ファイルを読み込む（なければ既定値、壊れていれば退避して既定値）
+    pub fn load(config_dir: &Path) -> SettingsStore {
+        let path = config_dir.join("settings.json");
+        let settings = match fs::read_to_string(&path) {
+            Ok(content) => parse_settings(&content, &path).unwrap_or_else(|e| {
+                log::error!("Failed to load settings, using defaults: {}", e);
+                let backup = path.with_extension("json.bak");
+                if let Err(e) = fs::rename(&path, &backup) {
+                    log::warn!("Failed to back up broken settings file: {}", e);
+                }
+                Settings::default()
+            }),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Settings::default(),
+            Err(e) => {
+                log::error!("Could not read settings file, using defaults: {}", e);
+                Settings::default()
+            }
+        };
+        log::info!("Loaded settings from {:?}", path);
+
+        SettingsStore {
+            path,
+            settings: Mutex::new(settings),
+        }
+    }
+
+    pub fn get(&self) -> Settings {
+        self.settings.lock().unwrap().clone()
+    }
+
+    // 設定を変更し、検証してから保存
+    pub fn update<F>(&self, f: F) -> Result<Settings, String>
+    where
+        F: FnOnce(&mut Settings),
+    {
+        self.try_update(|settings| {
+            f(settings);
+            Ok(())
+        })
+    }
+
+    // 指定されたフィールドだけを置き換える（他の画面やコマンドで変更されたフィールドは保つ）
+    pub fn patch(&self, patch: Map<String, Value>) -> Result<Settings, String> {
+        self.try_update(|settings| {
+            let mut value = serde_json::to_value(&*settings).map_err(|e| e.to_string())?;
+            if let Some(object) = value.as_object_mut() {
+                object.extend(patch);
+            }
+            *settings =
+                serde_json::from_value(value).map_err(|e| format!("Invalid settings: {}", e))?;
+            Ok(())
+        })
+    }
+
+    fn try_update<F>(&self, f: F) -> Result<Settings, String>
+    where
+        F: FnOnce(&mut Settings) -> Result<(), String>,
+    {
+        let mut current = self.settings.lock().unwrap();
+        let mut updated = current.clone();
+        f(&mut updated)?;
+        updated.schema_version = SCHEMA_VERSION;
+        updated.validate_changes(&current)?;
+
+        self.save(&updated)?;
+        *current = updated.clone();
+        Ok(updated)
+    }
+
+    // 一時ファイルに書き込んでから置き換え、書き込み途中の破損を防ぐ
+    fn save(&self, settings: &Settings) -> Result<(), String> {
+        if let Some(dir) = self.path.parent() {
+            fs::create_dir_all(dir).map_err(|e| {
+                log::error!("Could not create config directory: {}", e);
+                format!("Could not create config directory: {}", e)
+            })?;
+        }
+        let json = serde_json::to_string_pretty(settings).map_err(|e| {
+            log::error!("Failed to serialize settings: {}", e);
+            format!("Failed to serialize settings: {}", e)
+        })?;
+        let tmp_path = self.path.with_extension("json.tmp");
+        fs::write(&tmp_path, json).map_err(|e| {
+            log::error!("Failed to write settings file: {}", e);
+            format!("Failed to write settings file: {}", e)
+        })?;
+        fs::rename(&tmp_path, &self.path).map_err(|e| {
+            log::error!("Failed to save settings file: {}", e);
+            format!("Failed to save settings file: {}", e)
+        })
+    }
+}
+
+// スキーマバージョンに応じて移行してから読み込む
+// 新しいバージョンのアプリが書いた設定は、保存で知らないフィールドが失われる前に退避する
+fn parse_settings(content: &str, path: &Path) -> Result<Settings, String> {
+    let mut value: Value = serde_json::from_str(content).map_err(|e| e.to_string())?;
+    let object = value
+        .as_object_mut()
+        .ok_or_else(|| "Settings file is not a JSON object".to_string())?;
+
+    let version = object
+        .get("schemaVersion")
+        .and_then(Value::as_u64)
+        .map(|version| version as u32)
+        .unwrap_or(1);
+    if version > SCHEMA_VERSION {
+        let backup = path.with_extension(format!("v{}.json.bak", version));
+        log::warn!(
+            "Settings file has a newer schema version ({} > {}), keeping a copy at {:?}",
+            version,
+            SCHEMA_VERSION,
+            backup
+        );
+        if !backup.exists() {
+            fs::copy(path, &backup).map_err(|e| {
+                log::error!("Failed to back up settings file: {}", e);
+                format!("Failed to back up settings file: {}", e)
+            })?;
+        }
+    }
+    migrate(object, version, MIGRATIONS);
+    object.insert("schemaVersion".to_string(), Value::from(SCHEMA_VERSION));
+
+    serde_json::from_value(value).map_err(|e| e.to_string())
+}
+
+// versionの設定を、migrationsを順に適用して最新のバージョンに変換
+fn migrate(object: &mut Map<String, Value>, version: u32, migrations: &[Migration]) {
+    let latest = migrations.len() as u32 + 1;
+    for from in version.max(1)..latest {
+        log::info!("Migrating settings from version {} to {}", from, from + 1);
+        migrations[from as usize - 1](object);
+    }
+}
+
+#[tauri::command]
+pub async fn get_settings(store: tauri::State<'_, SettingsStore>) -> Result<Settings, String> {
+    Ok(store.get())
+}
+
+// 指定されたフィールドを検証して保存し、流量制御・帯域の上限・クリップボードの監視・ローカルAPIの設定を反映
+#[tauri::command]
+pub async fn update_settings(
+    patch: Map<String, Value>,
+    app: tauri::AppHandle,
+    store: tauri::State<'_, SettingsStore>,
+    jobs: tauri::State<'_, crate::jobs::JobManager>,
+    watcher: tauri::State<'_, crate::clipboard::ClipboardWatcher>,
+    api: tauri::State<'_, crate::api::ApiServer>,
+) -> Result<Settings, String> {
+    // WebhookのURLなどの値はログに出さない
+    log::info!(
+        "Invoked update_settings with fields: {:?}",
+        patch.keys().collect::<Vec<_>>()
+    );
+    let previous = store.get();
+    let updated = store.patch(patch)?;
+    jobs.throttle.set_config(updated.throttle.clone());
+    jobs.bandwidth.set_config(updated.bandwidth.clone());
+    if updated.clipboard != previous.clipboard {
+        watcher.apply(&app, &updated.clipboard);
+    }
+    if updated.api != previous.api {
+        api.apply(&app, &updated.api);
+    }
+    Ok(updated)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!(
+            "takumi-vid-dl-settings-{}-{}",
+            name,
+            std::process::id()
+        ));
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    #[test]
+    fn migrations_run_in_order_from_the_file_version() {
+        fn rename_output(object: &mut Map<String, Value>) {
+            if let Some(value) = object.remove("outputPath") {
+                object.insert("outputDir".to_string(), value);
+            }
+        }
+        fn add_suffix(object: &mut Map<String, Value>) {
+            if let Some(Value::String(dir)) = object.get_mut("outputDir") {
+                dir.push_str("/videos");
+            }
+        }
+        let migrations: &[Migration] = &[rename_output, add_suffix];
+
+        let mut object = serde_json::json!({ "outputPath": "/home" })
+            .as_object()
+            .unwrap()
+            .clone();
+        migrate(&mut object, 1, migrations);
+        assert_eq!(object["outputDir"], "/home/videos");
+
+        // 途中のバージョンからはそれ以降の移行だけを適用
+        let mut object = serde_json::json!({ "outputDir": "/home" })
+            .as_object()
+            .unwrap()
+            .clone();
+        migrate(&mut object, 2, migrations);
+        assert_eq!(object["outputDir"], "/home/videos");
+    }
+
+    #[test]
+    fn old_files_without_version_are_read() {
+        let dir = temp_dir("old");
+        let path = dir.join("settings.json");
+        let settings =
+            parse_settings(r#"{ "outputDir": "/videos", "unknown": 1 }"#, &path).unwrap();
+        assert_eq!(settings.schema_version, SCHEMA_VERSION);
+        assert_eq!(settings.output_dir, "/videos");
+        assert_eq!(settings.retry_policy, RetryPolicy::default());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn newer_files_are_backed_up() {
+        let dir = temp_dir("newer");
+        let path = dir.join("settings.json");
+        let content = format!(
+            r#"{{ "schemaVersion": {}, "outputDir": "/videos", "newField": true }}"#,
+            SCHEMA_VERSION + 1
+        );
+        fs::write(&path, &content).unwrap();
+
+        let settings = parse_settings(&content, &path).unwrap();
+        assert_eq!(settings.output_dir, "/videos");
+        let backup = path.with_extension(format!("v{}.json.bak", SCHEMA_VERSION + 1));
+        assert_eq!(fs::read_to_string(&backup).unwrap(), content);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn corrupt_files_are_moved_aside() {
+        let dir = temp_dir("corrupt");
+        let path = dir.join("settings.json");
+        fs::write(&path, "{ not json").unwrap();
+
+        let store = SettingsStore::load(&dir);
+        assert_eq!(store.get().output_dir, "");
+        assert!(!path.exists());
+        assert_eq!(
+            fs::read_to_string(path.with_extension("json.bak")).unwrap(),
+            "{ not json"
+        );
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn only_changed_fields_are_validated() {
+        let dir = temp_dir("patch");
+        let file = dir.join("file.txt");
+        fs::write(&file, "").unwrap();
+
+        // 保存済みの設定に問題があっても、他のフィールドは保存できる
+        let previous = Settings {
+            output_dir: file.to_string_lossy().to_string(),
+            custom_options: "'unclosed".to_string(),
+            ..Settings::default()
+        };
+        let mut updated = previous.clone();
+        updated.ffmpeg_dir = dir.join("missing").to_string_lossy().to_string();
+        assert!(updated.validate_changes(&previous).is_ok());
+
+        updated.custom_options = "-f 'best".to_string();
+        assert!(updated.validate_changes(&previous).is_err());
+
+        // まだないフォルダは作成されるため保存できる、ファイルは保存できない
+        let mut updated = Settings {
+            output_dir: dir.join("new").to_string_lossy().to_string(),
+            ..Settings::default()
+        };
+        assert!(updated.validate_changes(&Settings::default()).is_ok());
+        updated.output_dir = file.to_string_lossy().to_string();
+        assert!(updated.validate_changes(&Settings::default()).is_err());
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
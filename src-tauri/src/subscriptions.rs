@@ -0,0 +1,478 @@
+use crate::archive::{self, ArchiveEntry};
+use crate::scheduler::Clock;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::HashSet;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::Duration as StdDuration;
+use tauri::{Emitter, Manager};
+use time::{format_description::well_known::Rfc3339, Duration, OffsetDateTime};
+
+// サブスクリプションファイルのスキーマバージョン
+const SCHEMA_VERSION: u32 = 1;
+// 同期が必要なサブスクリプションを確認する間隔
+const CHECK_INTERVAL_SECS: u64 = 60;
+// サブスクリプションごとに残すエラーの件数
+const MAX_ERRORS: usize = 20;
+
+// 最後に成功した同期の結果
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SyncState {
+    pub synced_at: String,
+    // チャンネル・プレイリストのエントリ数
+    pub total_entries: usize,
+    // アーカイブになくダウンロードを開始したエントリ数
+    pub new_entries: usize,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SyncError {
+    pub at: String,
+    pub message: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Subscription {
+    // 新規作成時は空（保存時に採番）
+    #[serde(default)]
+    pub id: String,
+    pub name: String,
+    // チャンネルまたはプレイリストのURL
+    pub url: String,
+    // ダウンロードに使うプロファイル（アーカイブもプロファイルごと）
+    #[serde(default)]
+    pub profile: Option<String>,
+    #[serde(default = "default_interval_hours")]
+    pub interval_hours: u32,
+    #[serde(default = "default_enabled")]
+    pub enabled: bool,
+    // 以下は同期処理が管理する
+    #[serde(default)]
+    pub last_checked_at: Option<String>,
+    #[serde(default)]
+    pub last_sync: Option<SyncState>,
+    // 新しい順
+    #[serde(default)]
+    pub errors: Vec<SyncError>,
+}
+
+fn default_interval_hours() -> u32 {
+    24
+}
+
+fn default_enabled() -> bool {
+    true
+}
+
+impl Subscription {
+    pub fn validate(&self) -> Result<(), String> {
+        if self.name.trim().is_empty() {
+            return Err("Subscription name must not be empty".to_string());
+        }
+        match url::Url::parse(self.url.trim()) {
+            Ok(url) if matches!(url.scheme(), "http" | "https") => {}
+            _ => return Err(format!("Invalid subscription URL: {}", self.url)),
+        }
+        if self.interval_hours == 0 {
+            return Err("intervalHours must be at least 1".to_string());
+        }
+        Ok(())
+    }
+
+    // 前回の確認から間隔が過ぎているか（未確認なら同期する）
+    fn is_due(&self, now: OffsetDateTime) -> bool {
+        self.enabled
+            && self
+                .last_checked_at
+                .as_deref()
+                .and_then(|at| OffsetDateTime::parse(at, &Rfc3339).ok())
+                .is_none_or(|at| at + Duration::hours(self.interval_hours.into()) <= now)
+    }
+}
+
+// 同期の結果（sync_subscriptionで返す）
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SyncResult {
+    pub total_entries: usize,
+    pub new_urls: Vec<String>,
+}
+
+// ファイルに保存する形式
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct SubscriptionFile {
+    schema_version: u32,
+    subscriptions: Vec<Subscription>,
+}
+
+pub struct SubscriptionStore {
+    path: PathBuf,
+    clock: Arc<dyn Clock>,
+    subscriptions: Mutex<Vec<Subscription>>,
+    // 同期中、または同期で開始したダウンロードが実行中のサブスクリプション
+    busy: Mutex<HashSet<String>>,
+}
+
+impl SubscriptionStore {
+    pub fn load(data_dir: &Path, clock: Arc<dyn Clock>) -> SubscriptionStore {
+        let path = data_dir.join("subscriptions.json");
+        let subscriptions = match fs::read_to_string(&path) {
+            Ok(content) => match serde_json::from_str::<SubscriptionFile>(&content) {
+                Ok(file) => file.subscriptions,
+                Err(e) => {
+                    log::error!("Failed to load subscriptions: {}", e);
+                    let backup = path.with_extension("json.bak");
+                    if let Err(e) = fs::rename(&path, &backup) {
+                        log::warn!("Failed to back up broken subscriptions file: {}", e);
+                    }
+                    Vec::new()
+                }
+            },
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Vec::new(),
+            Err(e) => {
+                log::error!("Could not read subscriptions file: {}", e);
+                Vec::new()
+            }
+        };
+
+        SubscriptionStore {
+            path,
+            clock,
+            subscriptions: Mutex::new(subscriptions),
+            busy: Mutex::new(HashSet::new()),
+        }
+    }
+
+    pub fn list(&self) -> Vec<Subscription> {
+        self.subscriptions.lock().unwrap().clone()
+    }
+
+    fn get(&self, id: &str) -> Option<Subscription> {
+        self.subscriptions
+            .lock()
+            .unwrap()
+            .iter()
+            .find(|subscription| subscription.id == id)
+            .cloned()
+    }
+
+    // サブスクリプションを変更して保存（保存に失敗したら変更を戻す）
+    fn modify<T, F>(&self, f: F) -> Result<T, String>
+    where
+        F: FnOnce(&mut Vec<Subscription>) -> Result<T, String>,
+    {
+        let mut subscriptions = self.subscriptions.lock().unwrap();
+        let mut updated = subscriptions.clone();
+        let result = f(&mut updated)?;
+        write_subscriptions(&self.path, &updated)?;
+        *subscriptions = updated;
+        Ok(result)
+    }
+
+    // 追加または更新（同期の状態は保存済みのものを引き継ぐ）
+    pub fn save(&self, mut subscription: Subscription) -> Result<Subscription, String> {
+        subscription.url = subscription.url.trim().to_string();
+        subscription.validate()?;
+        self.modify(|subscriptions| {
+            if subscription.id.is_empty() {
+                subscription.id = crate::history::new_entry_id();
+            }
+            subscription.last_checked_at = None;
+            subscription.last_sync = None;
+            subscription.errors = Vec::new();
+            match subscriptions.iter_mut().find(|s| s.id == subscription.id) {
+                Some(existing) => {
+                    // URLを変えた場合は別のチャンネルとして次の確認で同期する
+                    if existing.url == subscription.url {
+                        subscription.last_checked_at = existing.last_checked_at.clone();
+                        subscription.last_sync = existing.last_sync.clone();
+                    }
+                    subscription.errors = existing.errors.clone();
+                    *existing = subscription.clone();
+                }
+                None => subscriptions.push(subscription.clone()),
+            }
+            Ok(subscription)
+        })
+    }
+
+    pub fn delete(&self, id: &str) -> Result<bool, String> {
+        self.modify(|subscriptions| {
+            let before = subscriptions.len();
+            subscriptions.retain(|subscription| subscription.id != id);
+            Ok(subscriptions.len() != before)
+        })
+    }
+
+    fn update<F>(&self, id: &str, f: F) -> Option<Subscription>
+    where
+        F: FnOnce(&mut Subscription),
+    {
+        self.modify(|subscriptions| {
+            Ok(subscriptions
+                .iter_mut()
+                .find(|subscription| subscription.id == id)
+                .map(|subscription| {
+                    f(subscription);
+                    subscription.clone()
+                }))
+        })
+        .unwrap_or_else(|e| {
+            log::warn!("Failed to save subscription state: {}", e);
+            None
+        })
+    }
+
+    fn record_error(&self, id: &str, message: &str) -> Option<Subscription> {
+        let at = format_time(self.clock.now());
+        self.update(id, |subscription| {
+            subscription.errors.insert(
+                0,
+                SyncError {
+                    at,
+                    message: message.to_string(),
+                },
+            );
+            subscription.errors.truncate(MAX_ERRORS);
+        })
+    }
+
+    // 同期中でなければ同期中にする
+    fn try_begin(&self, id: &str) -> bool {
+        self.busy.lock().unwrap().insert(id.to_string())
+    }
+
+    fn finish(&self, id: &str) {
+        self.busy.lock().unwrap().remove(id);
+    }
+}
+
+// 同期が必要なサブスクリプションを定期的に確認するループを起動
+pub fn start(app: tauri::AppHandle) {
+    tauri::async_runtime::spawn(async move {
+        loop {
+            let store = app.state::<SubscriptionStore>();
+            let now = store.clock.now();
+            let due: Vec<String> = store
+                .list()
+                .into_iter()
+                .filter(|subscription| subscription.is_due(now))
+                .map(|subscription| subscription.id)
+                .collect();
+            for id in due {
+                let app = app.clone();
+                tauri::async_runtime::spawn(async move {
+                    if let Err(e) = sync(&app, &id).await {
+                        log::warn!("Failed to sync subscription {}: {}", id, e);
+                    }
+                });
+            }
+            tokio::time::sleep(StdDuration::from_secs(CHECK_INTERVAL_SECS)).await;
+        }
+    });
+}
+
+// チャンネル・プレイリストのエントリを取得し、アーカイブにないものをダウンロード
+// 前回の同期で開始したダウンロードが終わっていなければ何もしない
+pub async fn sync(app: &tauri::AppHandle, id: &str) -> Result<SyncResult, String> {
+    let store = app.state::<SubscriptionStore>();
+    let subscription = store
+        .get(id)
+        .ok_or_else(|| format!("Subscription not found: {}", id))?;
+    if !store.try_begin(id) {
+        log::info!("Subscription {} is already syncing", subscription.name);
+        return Err(format!(
+            "Subscription {} is already syncing",
+            subscription.name
+        ));
+    }
+
+    // 一覧の取得はyt-dlpの終了まで待つため、非同期ランタイムのスレッドを塞がないよう別スレッドで実行
+    let probed = subscription.clone();
+    let result = tauri::async_runtime::spawn_blocking(move || sync_entries(&probed))
+        .await
+        .map_err(|e| format!("Failed to sync subscription: {}", e))
+        .and_then(|result| result);
+    if !result
+        .as_ref()
+        .is_ok_and(|result| !result.new_urls.is_empty())
+    {
+        store.finish(id);
+    }
+    let updated = match &result {
+        Ok(result) => {
+            log::info!(
+                "Synced subscription {}: {} new of {} entries",
+                subscription.name,
+                result.new_urls.len(),
+                result.total_entries
+            );
+            let now = format_time(store.clock.now());
+            store.update(id, |subscription| {
+                subscription.last_checked_at = Some(now.clone());
+                subscription.last_sync = Some(SyncState {
+                    synced_at: now,
+                    total_entries: result.total_entries,
+                    new_entries: result.new_urls.len(),
+                });
+            })
+        }
+        Err(e) => {
+            log::error!("Failed to sync subscription {}: {}", subscription.name, e);
+            // 失敗しても次の確認は間隔を空けて行う
+            let now = format_time(store.clock.now());
+            store.update(id, |subscription| subscription.last_checked_at = Some(now));
+            store.record_error(id, e)
+        }
+    };
+    if let Some(updated) = updated {
+        let _ = app.emit("subscription-updated", &updated);
+    }
+    let result = result?;
+
+    // 新しいエントリをプロファイルの設定でダウンロード（終わるまで次の同期はしない）
+    if !result.new_urls.is_empty() {
+        let app = app.clone();
+        let id = id.to_string();
+        let profile = subscription.profile.clone();
+        let args = result.new_urls.clone();
+        tauri::async_runtime::spawn(async move {
+            let store = app.state::<SubscriptionStore>();
+            // ウィンドウを閉じている・隠している間も実行できるようにアプリに送信する
+            let context = crate::engine::Context::from_app(&app);
+            let retry_policy = context.settings.get().retry_policy;
+            let outcome =
+                crate::engine::execute_yt_dlp(&context, args, profile, retry_policy).await;
+            store.finish(&id);
+            let message = match outcome {
+                Ok(report) if report.success => None,
+                Ok(report) => Some(format!(
+                    "Download finished with {} failed of {} items",
+                    report.summary.failed, report.summary.total
+                )),
+                Err(e) => Some(e),
+            };
+            if let Some(message) = message {
+                if let Some(updated) = store.record_error(&id, &message) {
+                    let _ = app.emit("subscription-updated", &updated);
+                }
+            }
+        });
+    }
+    Ok(result)
+}
+
+// --flat-playlistでエントリの一覧を取得し、アーカイブと照合
+fn sync_entries(subscription: &Subscription) -> Result<SyncResult, String> {
+    let info = crate::probe::probe(&subscription.url, &["--flat-playlist"])?;
+    let entries = info
+        .get("entries")
+        .and_then(Value::as_array)
+        .ok_or_else(|| format!("{} is not a channel or playlist", subscription.url))?;
+    let archived = archive::archived_entries(subscription.profile.as_deref())?;
+
+    let mut new_urls = Vec::new();
+    for entry in entries {
+        let Some(url) = entry_url(entry) else {
+            continue;
+        };
+        // アーカイブと同じ「エクストラクター ID」で照合（判別できなければyt-dlp側のアーカイブ確認に任せる）
+        let archive_entry = match (
+            entry.get("ie_key").and_then(Value::as_str),
+            entry.get("id").and_then(Value::as_str),
+        ) {
+            (Some(extractor), Some(id)) => Some(ArchiveEntry {
+                extractor: extractor.to_lowercase(),
+                id: id.to_string(),
+            }),
+            _ => archive::archive_entry_for_url(&url),
+        };
+        if archive_entry.is_some_and(|entry| archived.contains(&entry)) {
+            continue;
+        }
+        if !new_urls.contains(&url) {
+            new_urls.push(url);
+        }
+    }
+
+    Ok(SyncResult {
+        total_entries: entries.len(),
+        new_urls,
+    })
+}
+
+fn entry_url(entry: &Value) -> Option<String> {
+    ["url", "webpage_url"]
+        .iter()
+        .filter_map(|key| entry.get(key).and_then(Value::as_str))
+        .find(|url| url.starts_with("http://") || url.starts_with("https://"))
+        .map(str::to_string)
+}
+
+// 一時ファイルに書き込んでから置き換え、書き込み途中の破損を防ぐ
+fn write_subscriptions(path: &Path, subscriptions: &[Subscription]) -> Result<(), String> {
+    let json = serde_json::to_string_pretty(&SubscriptionFile {
+        schema_version: SCHEMA_VERSION,
+        subscriptions: subscriptions.to_vec(),
+    })
+    .map_err(|e| {
+        log::error!("Failed to serialize subscriptions: {}", e);
+        format!("Failed to serialize subscriptions: {}", e)
+    })?;
+    let tmp_path = path.with_extension("json.tmp");
+    fs::write(&tmp_path, json).map_err(|e| {
+        log::error!("Failed to write subscriptions file: {}", e);
+        format!("Failed to write subscriptions file: {}", e)
+    })?;
+    fs::rename(&tmp_path, path).map_err(|e| {
+        log::error!("Failed to save subscriptions file: {}", e);
+        format!("Failed to save subscriptions file: {}", e)
+    })
+}
+
+fn format_time(datetime: OffsetDateTime) -> String {
+    datetime.format(&Rfc3339).unwrap_or_default()
+}
+
+#[tauri::command]
+pub async fn list_subscriptions(
+    store: tauri::State<'_, SubscriptionStore>,
+) -> Result<Vec<Subscription>, String> {
+    Ok(store.list())
+}
+
+#[tauri::command]
+pub async fn save_subscription(
+    subscription: Subscription,
+    store: tauri::State<'_, SubscriptionStore>,
+) -> Result<Subscription, String> {
+    log::info!(
+        "Invoked save_subscription with id: {:?}, url: {:?}, profile: {:?}",
+        subscription.id,
+        subscription.url,
+        subscription.profile
+    );
+    store.save(subscription)
+}
+
+#[tauri::command]
+pub async fn delete_subscription(
+    id: String,
+    store: tauri::State<'_, SubscriptionStore>,
+) -> Result<bool, String> {
+    log::info!("Invoked delete_subscription with id: {:?}", id);
+    store.delete(&id)
+}
+
+// 間隔を待たずに今すぐ同期
+#[tauri::command]
+pub async fn sync_subscription(id: String, app: tauri::AppHandle) -> Result<SyncResult, String> {
+    log::info!("Invoked sync_subscription with id: {:?}", id);
+    sync(&app, &id).await
+}
@@ -0,0 +1,1040 @@
+use crate::diagnostics::Severity;
+use serde::Serialize;
+use serde_json::{json, Value};
+use std::path::Path;
+use time::{Date, Month, OffsetDateTime};
+
+// 値がないときにyt-dlpが出力する文字列（--output-na-placeholderの既定値）
+const NA_PLACEHOLDER: &str = "NA";
+// 多くのファイルシステムでのファイル名の上限（バイト）
+const MAX_COMPONENT_BYTES: usize = 255;
+// Windowsで長いパスを有効にしていない場合の上限（文字）
+const MAX_WINDOWS_PATH_CHARS: usize = 260;
+
+// 変換の種類（printf形式とyt-dlp独自の形式）
+const CONVERSIONS: &str = "diouxXeEfFgGcrsBjhlqDSU";
+// Windowsでファイル名に使えない文字
+const WINDOWS_ILLEGAL_CHARS: &[char] = &['<', '>', ':', '"', '|', '?', '*'];
+const WINDOWS_RESERVED_NAMES: &[&str] = &[
+    "CON", "PRN", "AUX", "NUL", "COM1", "COM2", "COM3", "COM4", "COM5", "COM6", "COM7", "COM8",
+    "COM9", "LPT1", "LPT2", "LPT3", "LPT4", "LPT5", "LPT6", "LPT7", "LPT8", "LPT9",
+];
+// -oの値の先頭に付けて出力ファイルの種類を指定する接頭辞
+const OUTPUT_TYPES: &[&str] = &[
+    "subtitle",
+    "thumbnail",
+    "description",
+    "annotation",
+    "infojson",
+    "link",
+    "pl_thumbnail",
+    "pl_description",
+    "pl_infojson",
+    "chapter",
+    "pl_video",
+];
+
+// よく使われるフィールド（これ以外はエクストラクター固有の可能性があるため警告のみ）
+const KNOWN_FIELDS: &[&str] = &[
+    "id",
+    "title",
+    "fulltitle",
+    "alt_title",
+    "ext",
+    "description",
+    "display_id",
+    "uploader",
+    "uploader_id",
+    "uploader_url",
+    "channel",
+    "channel_id",
+    "channel_url",
+    "channel_follower_count",
+    "creator",
+    "creators",
+    "timestamp",
+    "upload_date",
+    "release_date",
+    "release_timestamp",
+    "release_year",
+    "modified_date",
+    "modified_timestamp",
+    "duration",
+    "duration_string",
+    "view_count",
+    "concurrent_view_count",
+    "like_count",
+    "dislike_count",
+    "repost_count",
+    "comment_count",
+    "average_rating",
+    "age_limit",
+    "live_status",
+    "is_live",
+    "was_live",
+    "availability",
+    "playable_in_embed",
+    "location",
+    "categories",
+    "tags",
+    "thumbnail",
+    "language",
+    "license",
+    "playlist",
+    "playlist_id",
+    "playlist_title",
+    "playlist_index",
+    "playlist_count",
+    "playlist_autonumber",
+    "playlist_uploader",
+    "playlist_uploader_id",
+    "playlist_channel",
+    "playlist_channel_id",
+    "n_entries",
+    "chapter",
+    "chapter_number",
+    "chapter_id",
+    "chapters",
+    "section_title",
+    "section_number",
+    "section_start",
+    "section_end",
+    "series",
+    "series_id",
+    "season",
+    "season_number",
+    "season_id",
+    "episode",
+    "episode_number",
+    "episode_id",
+    "track",
+    "track_number",
+    "track_id",
+    "artist",
+    "artists",
+    "album",
+    "album_type",
+    "album_artist",
+    "album_artists",
+    "disc_number",
+    "genre",
+    "genres",
+    "composer",
+    "composers",
+    "extractor",
+    "extractor_key",
+    "webpage_url",
+    "webpage_url_basename",
+    "webpage_url_domain",
+    "original_url",
+    "format",
+    "format_id",
+    "format_note",
+    "formats",
+    "requested_formats",
+    "width",
+    "height",
+    "resolution",
+    "aspect_ratio",
+    "fps",
+    "dynamic_range",
+    "vcodec",
+    "acodec",
+    "abr",
+    "vbr",
+    "tbr",
+    "asr",
+    "audio_channels",
+    "filesize",
+    "filesize_approx",
+    "protocol",
+    "container",
+    "autonumber",
+    "video_autonumber",
+    "epoch",
+    "now",
+];
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TemplateIssue {
+    pub severity: Severity,
+    pub message: String,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TemplateCheck {
+    pub valid: bool,
+    // テンプレートで参照しているフィールド名
+    pub fields: Vec<String>,
+    pub issues: Vec<TemplateIssue>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TemplatePreview {
+    pub valid: bool,
+    // テンプレートを展開したファイル名（出力ディレクトリからの相対パス）
+    pub rendered: String,
+    pub full_path: String,
+    pub issues: Vec<TemplateIssue>,
+}
+
+enum Segment {
+    Literal(String),
+    Field(Field),
+}
+
+// %(...)形式のフィールド
+struct Field {
+    // 「,」区切りの候補（最初に値があるものを使う）
+    alternatives: Vec<Expression>,
+    // 「>」以降の日付の書式
+    date_format: Option<String>,
+    // 「&」以降の置換文字列（値があるときに使う）
+    replacement: Option<String>,
+    // 「|」以降の既定値（値がないときに使う）
+    default: Option<String>,
+    flags: String,
+    width: Option<usize>,
+    precision: Option<usize>,
+    conversion: char,
+}
+
+// フィールド名と加減算（例: playlist_index-1）
+struct Expression {
+    path: Vec<String>,
+    operations: Vec<(char, Operand)>,
+}
+
+enum Operand {
+    Number(f64),
+    Field(Vec<String>),
+}
+
+// テンプレートを解析して問題点を返す
+pub fn check(template: &str) -> TemplateCheck {
+    let mut issues = Vec::new();
+    let segments = parse(template, &mut issues);
+    check_segments(template, &segments, &mut issues);
+
+    let mut fields: Vec<String> = segments
+        .iter()
+        .filter_map(|segment| match segment {
+            Segment::Field(field) => Some(field),
+            Segment::Literal(_) => None,
+        })
+        .flat_map(|field| field.alternatives.iter())
+        .filter_map(|expression| expression.path.first().cloned())
+        .collect();
+    fields.sort();
+    fields.dedup();
+
+    TemplateCheck {
+        valid: !has_errors(&issues),
+        fields,
+        issues,
+    }
+}
+
+// メタデータでテンプレートを展開し、出力先のパスの問題点も合わせて返す
+pub fn preview(template: &str, output_dir: Option<&str>, metadata: &Value) -> TemplatePreview {
+    let mut issues = Vec::new();
+    let segments = parse(template, &mut issues);
+    check_segments(template, &segments, &mut issues);
+
+    let rendered: String = segments
+        .iter()
+        .map(|segment| match segment {
+            Segment::Literal(text) => text.clone(),
+            Segment::Field(field) => sanitize_value(&render_field(field, metadata)),
+        })
+        .collect();
+    let full_path = match output_dir.filter(|dir| !dir.trim().is_empty()) {
+        Some(dir) => Path::new(dir).join(&rendered).to_string_lossy().to_string(),
+        None => rendered.clone(),
+    };
+    check_path(&rendered, &full_path, &mut issues);
+
+    TemplatePreview {
+        valid: !has_errors(&issues),
+        rendered,
+        full_path,
+        issues,
+    }
+}
+
+// 引数から出力テンプレート（最後に指定された-o/--output）を取り出す
+pub fn output_template(args: &[String]) -> Option<&str> {
+    let mut template = None;
+    let mut iter = args.iter();
+    while let Some(arg) = iter.next() {
+        let value = match arg.as_str() {
+            "-o" | "--output" => iter.next().map(String::as_str),
+            _ => arg.strip_prefix("--output="),
+        };
+        let Some(value) = value else {
+            continue;
+        };
+        // 種類の指定（thumbnail:など）がついたものは動画のファイル名ではない
+        let typed = value
+            .split_once(':')
+            .is_some_and(|(prefix, _)| OUTPUT_TYPES.contains(&prefix));
+        if !typed {
+            template = Some(value);
+        }
+    }
+    template
+}
+
+// プレビュー用のサンプルのメタデータ
+pub fn sample_metadata() -> Value {
+    json!({
+        "id": "dQw4w9WgXcQ",
+        "title": "Sample Video Title",
+        "fulltitle": "Sample Video Title",
+        "ext": "mp4",
+        "uploader": "Sample Channel",
+        "uploader_id": "@samplechannel",
+        "channel": "Sample Channel",
+        "channel_id": "UCuAXFkgsw1L7xaCfnd5JJOw",
+        "upload_date": "20240131",
+        "timestamp": 1706700000,
+        "duration": 212,
+        "duration_string": "3:32",
+        "view_count": 123456,
+        "like_count": 7890,
+        "extractor": "youtube",
+        "extractor_key": "Youtube",
+        "webpage_url": "https://www.youtube.com/watch?v=dQw4w9WgXcQ",
+        "webpage_url_domain": "youtube.com",
+        "format_id": "137+140",
+        "resolution": "1920x1080",
+        "width": 1920,
+        "height": 1080,
+        "fps": 30,
+        "vcodec": "avc1.640028",
+        "acodec": "mp4a.40.2",
+        "playlist_index": 1,
+        "playlist_count": 10,
+        "playlist_title": "Sample Playlist",
+        "autonumber": 1,
+        "tags": ["sample", "video"],
+    })
+}
+
+fn has_errors(issues: &[TemplateIssue]) -> bool {
+    issues.iter().any(|issue| issue.severity == Severity::Error)
+}
+
+fn issue(issues: &mut Vec<TemplateIssue>, severity: Severity, message: String) {
+    issues.push(TemplateIssue { severity, message });
+}
+
+fn parse(template: &str, issues: &mut Vec<TemplateIssue>) -> Vec<Segment> {
+    let mut segments = Vec::new();
+    let mut literal = String::new();
+    let mut rest = template;
+
+    while let Some(pos) = rest.find('%') {
+        literal.push_str(&rest[..pos]);
+        rest = &rest[pos..];
+        if let Some(after) = rest.strip_prefix("%%") {
+            literal.push('%');
+            rest = after;
+            continue;
+        }
+        // yt-dlpは「%(」以外の%をそのまま出力するため、エラーにはしない
+        if !rest.starts_with("%(") {
+            issue(
+                issues,
+                Severity::Warning,
+                "Stray '%' is output as is: use '%%' to make the literal percent sign explicit"
+                    .to_string(),
+            );
+            literal.push('%');
+            rest = &rest[1..];
+            continue;
+        }
+
+        // 括弧の対応を見てフィールドの終わりを探す（先頭の%の次から）
+        let mut depth = 0;
+        let close = rest.char_indices().skip(1).find_map(|(i, ch)| {
+            match ch {
+                '(' => depth += 1,
+                ')' => depth -= 1,
+                _ => {}
+            }
+            (depth == 0).then_some(i)
+        });
+        let Some(close) = close else {
+            issue(issues, Severity::Error, format!("Unclosed field: {}", rest));
+            literal.push_str(rest);
+            rest = "";
+            break;
+        };
+
+        let inner = &rest[2..close];
+        let after = &rest[close + 1..];
+        let (spec, spec_len) = parse_conversion(after);
+        let raw = &rest[..close + 1 + spec_len];
+        match spec {
+            Some((flags, width, precision, conversion)) => {
+                if let Some(field) =
+                    parse_field(inner, flags, width, precision, conversion, raw, issues)
+                {
+                    if !literal.is_empty() {
+                        segments.push(Segment::Literal(std::mem::take(&mut literal)));
+                    }
+                    segments.push(Segment::Field(field));
+                }
+            }
+            None => issue(
+                issues,
+                Severity::Error,
+                format!(
+                    "Field {} has no conversion type (for example %({})s)",
+                    raw, inner
+                ),
+            ),
+        }
+        rest = &rest[close + 1 + spec_len..];
+    }
+    literal.push_str(rest);
+    if !literal.is_empty() {
+        segments.push(Segment::Literal(literal));
+    }
+    segments
+}
+
+// 「)」の後の書式指定（フラグ、幅、精度、変換の種類）
+type Conversion = (String, Option<usize>, Option<usize>, char);
+
+fn parse_conversion(text: &str) -> (Option<Conversion>, usize) {
+    let flags_len = text
+        .find(|ch: char| !"#0-+ ".contains(ch))
+        .unwrap_or(text.len());
+    let flags = text[..flags_len].to_string();
+    let mut pos = flags_len;
+
+    let digits = |s: &str| s.find(|ch: char| !ch.is_ascii_digit()).unwrap_or(s.len());
+    let width_len = digits(&text[pos..]);
+    let width = text[pos..pos + width_len].parse().ok();
+    pos += width_len;
+
+    let mut precision = None;
+    if text[pos..].starts_with('.') {
+        let precision_len = digits(&text[pos + 1..]);
+        precision = Some(text[pos + 1..pos + 1 + precision_len].parse().unwrap_or(0));
+        pos += 1 + precision_len;
+    }
+
+    match text[pos..].chars().next() {
+        Some(conversion) if CONVERSIONS.contains(conversion) => (
+            Some((flags, width, precision, conversion)),
+            pos + conversion.len_utf8(),
+        ),
+        _ => (None, 0),
+    }
+}
+
+fn parse_field(
+    inner: &str,
+    flags: String,
+    width: Option<usize>,
+    precision: Option<usize>,
+    conversion: char,
+    raw: &str,
+    issues: &mut Vec<TemplateIssue>,
+) -> Option<Field> {
+    let (inner, default) = match inner.split_once('|') {
+        Some((inner, default)) => (inner, Some(default.to_string())),
+        None => (inner, None),
+    };
+    let (inner, replacement) = match inner.split_once('&') {
+        Some((inner, replacement)) => (inner, Some(replacement.to_string())),
+        None => (inner, None),
+    };
+    let (inner, date_format) = match inner.split_once('>') {
+        Some((inner, date_format)) => (inner, Some(date_format.to_string())),
+        None => (inner, None),
+    };
+
+    // フィールド名が空なら既定値だけを出力する（例: %(|default)s）
+    let mut alternatives = Vec::new();
+    if !inner.is_empty() {
+        for alternative in inner.split(',') {
+            match parse_expression(alternative) {
+                Ok(expression) => alternatives.push(expression),
+                Err(message) => {
+                    issue(issues, Severity::Error, format!("{} in {}", message, raw));
+                    return None;
+                }
+            }
+        }
+    }
+
+    for expression in &alternatives {
+        let name = &expression.path[0];
+        if !KNOWN_FIELDS.contains(&name.as_str()) {
+            issue(
+                issues,
+                Severity::Warning,
+                format!(
+                    "Unknown field '{}' in {} (it may be specific to some sites)",
+                    name, raw
+                ),
+            );
+        }
+    }
+
+    Some(Field {
+        alternatives,
+        date_format,
+        replacement,
+        default,
+        flags,
+        width,
+        precision,
+        conversion,
+    })
+}
+
+fn parse_expression(text: &str) -> Result<Expression, String> {
+    // 「.」の直後の「-」は負のインデックスなので演算子として扱わない
+    let mut tokens = vec![(None, String::new())];
+    let mut previous = None;
+    for ch in text.chars() {
+        if (ch == '+' || ch == '-') && previous.is_some_and(|p| p != '.' && p != ':') {
+            tokens.push((Some(ch), String::new()));
+        } else {
+            tokens.last_mut().unwrap().1.push(ch);
+        }
+        previous = Some(ch);
+    }
+
+    let mut tokens = tokens.into_iter();
+    let (_, first) = tokens.next().unwrap();
+    let path = parse_path(&first)?;
+    let mut operations = Vec::new();
+    for (operator, operand) in tokens {
+        let operand = match operand.parse::<f64>() {
+            Ok(number) => Operand::Number(number),
+            Err(_) => Operand::Field(parse_path(&operand)?),
+        };
+        operations.push((operator.unwrap_or('+'), operand));
+    }
+    Ok(Expression { path, operations })
+}
+
+fn parse_path(text: &str) -> Result<Vec<String>, String> {
+    let path: Vec<String> = text.split('.').map(str::to_string).collect();
+    let name = &path[0];
+    let valid_name = name
+        .chars()
+        .next()
+        .is_some_and(|ch| ch.is_ascii_alphabetic() || ch == '_')
+        && name
+            .chars()
+            .all(|ch| ch.is_ascii_alphanumeric() || ch == '_');
+    if !valid_name {
+        return Err(format!("Invalid field name '{}'", text));
+    }
+    if path[1..].iter().any(|key| key.is_empty()) {
+        return Err(format!("Empty key in field '{}'", text));
+    }
+    Ok(path)
+}
+
+// リテラル部分の文字とテンプレート全体の構成を確認
+fn check_segments(template: &str, segments: &[Segment], issues: &mut Vec<TemplateIssue>) {
+    if template.trim().is_empty() {
+        issue(issues, Severity::Error, "Template is empty".to_string());
+        return;
+    }
+
+    let literal: String = segments
+        .iter()
+        .filter_map(|segment| match segment {
+            Segment::Literal(text) => Some(text.as_str()),
+            Segment::Field(_) => None,
+        })
+        .collect();
+    if literal.chars().any(|ch| ch.is_control()) {
+        issue(
+            issues,
+            Severity::Error,
+            "Template contains control characters".to_string(),
+        );
+    }
+    // ドライブ名の「:」（C:\など）は除いて確認
+    let without_drive = match literal.get(1..2) {
+        Some(":") if literal.starts_with(|ch: char| ch.is_ascii_alphabetic()) => &literal[2..],
+        _ => literal.as_str(),
+    };
+    let mut illegal: Vec<char> = without_drive
+        .chars()
+        .filter(|ch| WINDOWS_ILLEGAL_CHARS.contains(ch))
+        .collect();
+    illegal.sort();
+    illegal.dedup();
+    if !illegal.is_empty() {
+        let severity = if cfg!(windows) {
+            Severity::Error
+        } else {
+            Severity::Warning
+        };
+        issue(
+            issues,
+            severity,
+            format!(
+                "Characters not allowed in Windows file names: {}",
+                illegal.iter().collect::<String>()
+            ),
+        );
+    }
+
+    let fields: Vec<&Field> = segments
+        .iter()
+        .filter_map(|segment| match segment {
+            Segment::Field(field) => Some(field),
+            Segment::Literal(_) => None,
+        })
+        .collect();
+    let uses = |name: &str| {
+        fields.iter().any(|field| {
+            field
+                .alternatives
+                .iter()
+                .any(|expression| expression.path.len() == 1 && expression.path[0] == name)
+        })
+    };
+    if !uses("ext") {
+        issue(
+            issues,
+            Severity::Warning,
+            "Template does not contain %(ext)s, so files will have no extension".to_string(),
+        );
+    }
+    if !uses("id") {
+        issue(
+            issues,
+            Severity::Warning,
+            "Template does not contain %(id)s, so videos with the same title may overwrite each other"
+                .to_string(),
+        );
+    }
+    if fields.iter().any(|field| {
+        field.conversion == 's'
+            && field.precision.is_none()
+            && field.alternatives.iter().any(|expression| {
+                matches!(
+                    expression.path[0].as_str(),
+                    "title" | "fulltitle" | "description" | "playlist_title"
+                )
+            })
+    }) {
+        issue(
+            issues,
+            Severity::Warning,
+            "Long titles may exceed file name limits; consider a byte limit such as %(title).200B"
+                .to_string(),
+        );
+    }
+}
+
+// 展開後のパスの長さと、Windowsで使えない名前を確認
+fn check_path(rendered: &str, full_path: &str, issues: &mut Vec<TemplateIssue>) {
+    let separators: &[char] = if cfg!(windows) { &['/', '\\'] } else { &['/'] };
+    for component in rendered.split(separators) {
+        if component.is_empty() {
+            continue;
+        }
+        if component.len() > MAX_COMPONENT_BYTES {
+            issue(
+                issues,
+                Severity::Error,
+                format!(
+                    "File name is {} bytes, exceeding the {}-byte limit: {}",
+                    component.len(),
+                    MAX_COMPONENT_BYTES,
+                    component
+                ),
+            );
+        }
+        let stem = component.split('.').next().unwrap_or_default();
+        if WINDOWS_RESERVED_NAMES
+            .iter()
+            .any(|name| name.eq_ignore_ascii_case(stem))
+        {
+            issue(
+                issues,
+                Severity::Warning,
+                format!("'{}' is a reserved file name on Windows", component),
+            );
+        }
+        if component.ends_with(['.', ' ']) {
+            issue(
+                issues,
+                Severity::Warning,
+                format!(
+                    "'{}' ends with a dot or space, which Windows removes",
+                    component
+                ),
+            );
+        }
+    }
+
+    let length = full_path.chars().count();
+    if length > MAX_WINDOWS_PATH_CHARS {
+        let severity = if cfg!(windows) {
+            Severity::Error
+        } else {
+            Severity::Warning
+        };
+        issue(
+            issues,
+            severity,
+            format!(
+                "Full path is {} characters, exceeding the Windows limit of {}",
+                length, MAX_WINDOWS_PATH_CHARS
+            ),
+        );
+    }
+}
+
+fn lookup<'a>(metadata: &'a Value, path: &[String]) -> Option<&'a Value> {
+    let mut value = metadata;
+    for key in path {
+        value = match value {
+            Value::Object(map) => map.get(key)?,
+            Value::Array(items) => {
+                let index: i64 = key.parse().ok()?;
+                let index = if index < 0 {
+                    items.len().checked_sub(index.unsigned_abs() as usize)?
+                } else {
+                    index as usize
+                };
+                items.get(index)?
+            }
+            _ => return None,
+        };
+    }
+    (!value.is_null()).then_some(value)
+}
+
+fn evaluate(expression: &Expression, metadata: &Value) -> Option<Value> {
+    let value = lookup(metadata, &expression.path)?.clone();
+    if expression.operations.is_empty() {
+        return Some(value);
+    }
+
+    let mut result = value.as_f64()?;
+    for (operator, operand) in &expression.operations {
+        let operand = match operand {
+            Operand::Number(number) => *number,
+            Operand::Field(path) => lookup(metadata, path)?.as_f64()?,
+        };
+        result = if *operator == '-' {
+            result - operand
+        } else {
+            result + operand
+        };
+    }
+    Some(json!(result))
+}
+
+fn render_field(field: &Field, metadata: &Value) -> String {
+    let value = field
+        .alternatives
+        .iter()
+        .find_map(|expression| evaluate(expression, metadata));
+    let value = match (value, &field.date_format) {
+        (Some(value), Some(date_format)) => format_date(&value, date_format).map(Value::String),
+        (value, _) => value,
+    };
+
+    let text = match (&value, &field.replacement) {
+        (Some(value), Some(replacement)) => replacement.replace("{}", &value_to_string(value)),
+        (Some(value), None) => convert(value, field),
+        (None, _) => {
+            return field
+                .default
+                .clone()
+                .unwrap_or_else(|| NA_PLACEHOLDER.to_string())
+        }
+    };
+    pad(&text, field)
+}
+
+fn value_to_string(value: &Value) -> String {
+    match value {
+        Value::String(text) => text.clone(),
+        Value::Number(number) => match number.as_f64() {
+            Some(float) if float.fract() == 0.0 && float.abs() < 1e15 => {
+                format!("{}", float as i64)
+            }
+            _ => number.to_string(),
+        },
+        Value::Array(items) => items
+            .iter()
+            .map(value_to_string)
+            .collect::<Vec<_>>()
+            .join(", "),
+        other => other.to_string(),
+    }
+}
+
+fn convert(value: &Value, field: &Field) -> String {
+    match field.conversion {
+        'd' | 'i' | 'u' => value
+            .as_f64()
+            .map(|number| format!("{}", number.trunc() as i64))
+            .unwrap_or_else(|| NA_PLACEHOLDER.to_string()),
+        'f' | 'F' | 'e' | 'E' | 'g' | 'G' => value
+            .as_f64()
+            .map(|number| format!("{:.*}", field.precision.unwrap_or(6), number))
+            .unwrap_or_else(|| NA_PLACEHOLDER.to_string()),
+        'x' | 'X' | 'o' => match value.as_f64().map(|number| number.trunc() as i64) {
+            Some(number) if field.conversion == 'x' => format!("{:x}", number),
+            Some(number) if field.conversion == 'X' => format!("{:X}", number),
+            Some(number) => format!("{:o}", number),
+            None => NA_PLACEHOLDER.to_string(),
+        },
+        'j' => value.to_string(),
+        'q' => shlex::try_quote(&value_to_string(value))
+            .map(|quoted| quoted.to_string())
+            .unwrap_or_default(),
+        'D' => value
+            .as_f64()
+            .map(decimal_suffix)
+            .unwrap_or_else(|| NA_PLACEHOLDER.to_string()),
+        'B' => {
+            let text = value_to_string(value);
+            match field.precision {
+                Some(limit) => truncate_bytes(&text, limit).to_string(),
+                None => text,
+            }
+        }
+        _ => {
+            let text = value_to_string(value);
+            match field.precision {
+                Some(limit) => text.chars().take(limit).collect(),
+                None => text,
+            }
+        }
+    }
+}
+
+// 文字の途中で切らないようにバイト数で切り詰める
+fn truncate_bytes(text: &str, limit: usize) -> &str {
+    if text.len() <= limit {
+        return text;
+    }
+    let mut end = limit;
+    while !text.is_char_boundary(end) {
+        end -= 1;
+    }
+    &text[..end]
+}
+
+fn decimal_suffix(number: f64) -> String {
+    const SUFFIXES: &[&str] = &["", "k", "M", "G", "T", "P"];
+    let mut value = number;
+    let mut index = 0;
+    while value.abs() >= 1000.0 && index < SUFFIXES.len() - 1 {
+        value /= 1000.0;
+        index += 1;
+    }
+    if index == 0 {
+        format!("{}", value)
+    } else {
+        format!("{:.1}{}", value, SUFFIXES[index])
+    }
+}
+
+fn pad(text: &str, field: &Field) -> String {
+    let Some(width) = field.width else {
+        return text.to_string();
+    };
+    let length = text.chars().count();
+    if length >= width {
+        return text.to_string();
+    }
+    let fill = width - length;
+    if field.flags.contains('-') {
+        format!("{}{}", text, " ".repeat(fill))
+    } else if field.flags.contains('0') && text.parse::<f64>().is_ok() {
+        match text.strip_prefix('-') {
+            Some(digits) => format!("-{}{}", "0".repeat(fill), digits),
+            None => format!("{}{}", "0".repeat(fill), text),
+        }
+    } else {
+        format!("{}{}", " ".repeat(fill), text)
+    }
+}
+
+// YYYYMMDD形式の日付またはUNIX時間をstrftime形式の書式で整形
+fn format_date(value: &Value, date_format: &str) -> Option<String> {
+    let datetime = match value {
+        Value::String(text) if text.len() == 8 => {
+            let year = text[0..4].parse().ok()?;
+            let month = Month::try_from(text[4..6].parse::<u8>().ok()?).ok()?;
+            let day = text[6..8].parse().ok()?;
+            Date::from_calendar_date(year, month, day)
+                .ok()?
+                .midnight()
+                .assume_utc()
+        }
+        _ => OffsetDateTime::from_unix_timestamp(value.as_f64()? as i64).ok()?,
+    };
+
+    let mut output = String::new();
+    let mut chars = date_format.chars();
+    while let Some(ch) = chars.next() {
+        if ch != '%' {
+            output.push(ch);
+            continue;
+        }
+        match chars.next()? {
+            'Y' => output.push_str(&format!("{:04}", datetime.year())),
+            'y' => output.push_str(&format!("{:02}", datetime.year() % 100)),
+            'm' => output.push_str(&format!("{:02}", u8::from(datetime.month()))),
+            'd' => output.push_str(&format!("{:02}", datetime.day())),
+            'H' => output.push_str(&format!("{:02}", datetime.hour())),
+            'M' => output.push_str(&format!("{:02}", datetime.minute())),
+            'S' => output.push_str(&format!("{:02}", datetime.second())),
+            'j' => output.push_str(&format!("{:03}", datetime.ordinal())),
+            '%' => output.push('%'),
+            other => {
+                output.push('%');
+                output.push(other);
+            }
+        }
+    }
+    Some(output)
+}
+
+// フィールドの値に含まれるパス区切りなどの文字をyt-dlpと同じく全角の文字に置き換える
+fn sanitize_value(text: &str) -> String {
+    text.chars()
+        .map(|ch| match ch {
+            '/' => '\u{29F8}',
+            '\\' if cfg!(windows) => '\u{29F9}',
+            ':' if cfg!(windows) => '\u{FF1A}',
+            '*' if cfg!(windows) => '\u{FF0A}',
+            '?' if cfg!(windows) => '\u{FF1F}',
+            '"' if cfg!(windows) => '\u{FF02}',
+            '<' if cfg!(windows) => '\u{FF1C}',
+            '>' if cfg!(windows) => '\u{FF1E}',
+            '|' if cfg!(windows) => '\u{FF5C}',
+            ch if ch.is_control() => ' ',
+            ch => ch,
+        })
+        .collect()
+}
+
+// 出力テンプレートの構文とフィールド名を検証
+#[tauri::command]
+pub async fn validate_output_template(template: String) -> Result<TemplateCheck, String> {
+    Ok(check(&template))
+}
+
+// 出力テンプレートを展開したファイル名のプレビュー
+// メタデータの指定がなければURLの情報を取得し、URLもなければサンプルの値を使う
+#[tauri::command]
+pub async fn preview_output_template(
+    template: String,
+    output_dir: Option<String>,
+    url: Option<String>,
+    metadata: Option<Value>,
+) -> Result<TemplatePreview, String> {
+    log::info!(
+        "Invoked preview_output_template with template: {:?}, url: {:?}",
+        template,
+        url
+    );
+    let metadata = match (metadata, url.filter(|url| !url.trim().is_empty())) {
+        (Some(metadata), _) => metadata,
+        (None, Some(url)) => crate::probe::probe(url.trim(), &["--no-playlist"])?,
+        (None, None) => sample_metadata(),
+    };
+    Ok(preview(&template, output_dir.as_deref(), &metadata))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn messages(issues: &[TemplateIssue], severity: Severity) -> Vec<&str> {
+        issues
+            .iter()
+            .filter(|issue| issue.severity == severity)
+            .map(|issue| issue.message.as_str())
+            .collect()
+    }
+
+    fn render(template: &str) -> String {
+        preview(template, None, &sample_metadata()).rendered
+    }
+
+    #[test]
+    fn fields_are_parsed() {
+        let result = check("%(title)s.%(ext)s");
+        assert!(result.valid, "{:?}", result.issues);
+        assert_eq!(result.fields, vec!["ext", "title"]);
+        assert_eq!(render("%(title)s.%(ext)s"), "Sample Video Title.mp4");
+
+        let result = check("%(title).200B [%(id)s].%(ext)s");
+        assert!(result.valid, "{:?}", result.issues);
+        assert_eq!(result.fields, vec!["ext", "id", "title"]);
+        assert_eq!(
+            render("%(title).200B [%(id)s].%(ext)s"),
+            "Sample Video Title [dQw4w9WgXcQ].mp4"
+        );
+        assert_eq!(render("%(playlist_index)03d"), "001");
+    }
+
+    #[test]
+    fn nested_parentheses_stay_in_the_field() {
+        let result = check("%(uploader|(unknown))s/%(title)s.%(ext)s");
+        assert!(result.valid, "{:?}", result.issues);
+        assert_eq!(result.fields, vec!["ext", "title", "uploader"]);
+        assert_eq!(render("%(missing_field|(none))s"), "(none)");
+    }
+
+    #[test]
+    fn double_percent_is_a_literal() {
+        let result = check("100%% %(title).200B [%(id)s].%(ext)s");
+        assert!(result.valid, "{:?}", result.issues);
+        assert!(result.issues.is_empty(), "{:?}", result.issues);
+        assert_eq!(render("100%% %(id)s"), "100% dQw4w9WgXcQ");
+    }
+
+    #[test]
+    fn stray_percent_is_a_warning() {
+        let result = check("100% %(title).200B [%(id)s].%(ext)s");
+        assert!(result.valid);
+        assert_eq!(messages(&result.issues, Severity::Warning).len(), 1);
+        assert_eq!(render("100% %(id)s"), "100% dQw4w9WgXcQ");
+    }
+
+    #[test]
+    fn unclosed_field_is_an_error() {
+        let result = check("%(title.%(ext)s");
+        assert!(!result.valid);
+        assert!(messages(&result.issues, Severity::Error)
+            .iter()
+            .any(|message| message.starts_with("Unclosed field")));
+    }
+
+    #[test]
+    fn missing_conversion_is_an_error() {
+        let result = check("%(title).%(ext)s");
+        assert!(!result.valid);
+        assert!(messages(&result.issues, Severity::Error)
+            .iter()
+            .any(|message| message.contains("has no conversion type")));
+    }
+}
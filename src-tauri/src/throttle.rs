@@ -0,0 +1,424 @@
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tokio::sync::Notify;
+use url::Url;
+
+// リクエスト間隔とクールダウンの上限（秒）
+const MAX_REQUEST_SPACING_SECS: f64 = 3600.0;
+const MAX_COOLDOWN_SECS: u64 = 24 * 60 * 60;
+
+// ドメインごとの制限
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct DomainLimits {
+    // 全ジョブを通じた同時実行プロセス数
+    pub max_concurrent: usize,
+    // リクエスト間隔（秒）。プロセスの開始間隔と、実行中のプロセス数を掛けた--sleep-requestsに使う
+    pub request_spacing_secs: f64,
+    // 429を検出したときの待機時間（秒）
+    pub cooldown_secs: u64,
+}
+
+impl Default for DomainLimits {
+    fn default() -> Self {
+        DomainLimits {
+            max_concurrent: 1,
+            request_spacing_secs: 2.0,
+            cooldown_secs: 300,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct ThrottleConfig {
+    pub default_limits: DomainLimits,
+    // ドメイン名（example.com形式）ごとの上書き設定
+    pub overrides: HashMap<String, DomainLimits>,
+}
+
+impl ThrottleConfig {
+    pub fn validate(&self) -> Result<(), String> {
+        for (domain, limits) in std::iter::once(("default", &self.default_limits)).chain(
+            self.overrides
+                .iter()
+                .map(|(domain, limits)| (domain.as_str(), limits)),
+        ) {
+            if limits.max_concurrent == 0 {
+                return Err(format!("maxConcurrent for {} must be at least 1", domain));
+            }
+            if !(0.0..=MAX_REQUEST_SPACING_SECS).contains(&limits.request_spacing_secs) {
+                return Err(format!(
+                    "requestSpacingSecs for {} must be between 0 and {}",
+                    domain, MAX_REQUEST_SPACING_SECS
+                ));
+            }
+            if limits.cooldown_secs > MAX_COOLDOWN_SECS {
+                return Err(format!(
+                    "cooldownSecs for {} must not exceed {}",
+                    domain, MAX_COOLDOWN_SECS
+                ));
+            }
+        }
+        Ok(())
+    }
+
+    pub fn limits_for(&self, domain: &str) -> &DomainLimits {
+        self.overrides.get(domain).unwrap_or(&self.default_limits)
+    }
+}
+
+// ドメインの現在の状態（get_throttle_statusで返す）
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DomainStatus {
+    pub domain: String,
+    pub active: usize,
+    pub max_concurrent: usize,
+    pub cooldown_remaining_secs: u64,
+}
+
+#[derive(Default)]
+struct DomainState {
+    active: usize,
+    last_start: Option<Instant>,
+    cooldown_until: Option<Instant>,
+}
+
+// 全ジョブで共有するドメインごとの流量制御
+#[derive(Default)]
+pub struct DomainThrottle {
+    config: Mutex<ThrottleConfig>,
+    domains: Mutex<HashMap<String, DomainState>>,
+    notify: Notify,
+}
+
+// 取得中の実行枠（dropで解放）
+pub struct DomainPermit {
+    throttle: Arc<DomainThrottle>,
+    domain: String,
+}
+
+impl Drop for DomainPermit {
+    fn drop(&mut self) {
+        if let Some(state) = self.throttle.domains.lock().unwrap().get_mut(&self.domain) {
+            state.active = state.active.saturating_sub(1);
+        }
+        self.throttle.notify.notify_waiters();
+    }
+}
+
+// 実行枠を取得できなかった理由
+pub enum Wait {
+    // 同時実行数の上限（他のプロセスの終了待ち）
+    Busy,
+    // 開始間隔またはクールダウンの待ち時間
+    Until(Duration),
+}
+
+impl DomainThrottle {
+    pub fn config(&self) -> ThrottleConfig {
+        self.config.lock().unwrap().clone()
+    }
+
+    pub fn set_config(&self, config: ThrottleConfig) {
+        *self.config.lock().unwrap() = config;
+        self.notify.notify_waiters();
+    }
+
+    pub fn limits_for(&self, domain: &str) -> DomainLimits {
+        self.config.lock().unwrap().limits_for(domain).clone()
+    }
+
+    // すぐに実行できれば実行枠を取得
+    pub fn try_acquire(self: &Arc<Self>, domain: &str) -> Result<DomainPermit, Wait> {
+        let limits = self.limits_for(domain);
+        let mut domains = self.domains.lock().unwrap();
+        let state = domains.entry(domain.to_string()).or_default();
+
+        let now = Instant::now();
+        // 検証前の設定ファイルの値でもパニックしないよう上限で切り詰める
+        let spacing =
+            Duration::try_from_secs_f64(limits.request_spacing_secs.min(MAX_REQUEST_SPACING_SECS))
+                .unwrap_or_default();
+        let ready_at = [
+            state.cooldown_until,
+            state.last_start.map(|last| last + spacing),
+        ]
+        .into_iter()
+        .flatten()
+        .fold(now, Instant::max);
+
+        if state.active >= limits.max_concurrent {
+            return Err(Wait::Busy);
+        }
+        if ready_at > now {
+            return Err(Wait::Until(ready_at - now));
+        }
+
+        state.active += 1;
+        state.last_start = Some(now);
+        Ok(DomainPermit {
+            throttle: self.clone(),
+            domain: domain.to_string(),
+        })
+    }
+
+    // 実行枠が空くまで待って取得
+    pub async fn acquire(self: &Arc<Self>, domain: &str) -> DomainPermit {
+        loop {
+            // 判定前に通知を受け取る準備をして、取りこぼしを防ぐ
+            let notified = self.notify.notified();
+            match self.try_acquire(domain) {
+                Ok(permit) => return permit,
+                Err(Wait::Busy) => notified.await,
+                Err(Wait::Until(delay)) => {
+                    tokio::select! {
+                        _ = tokio::time::sleep(delay) => {}
+                        _ = notified => {}
+                    }
+                }
+            }
+        }
+    }
+
+    // ドメインで実行中のプロセス数
+    pub fn active(&self, domain: &str) -> usize {
+        self.domains
+            .lock()
+            .unwrap()
+            .get(domain)
+            .map_or(0, |state| state.active)
+    }
+
+    // クールダウンの残り時間（クールダウン中でなければNone）
+    pub fn cooldown_remaining(&self, domain: &str) -> Option<Duration> {
+        let domains = self.domains.lock().unwrap();
+        let until = domains.get(domain)?.cooldown_until?;
+        let remaining = until.saturating_duration_since(Instant::now());
+        (!remaining.is_zero()).then_some(remaining)
+    }
+
+    // 429を検出したドメインをクールダウンさせる
+    pub fn cool_down(&self, domain: &str) {
+        let cooldown =
+            Duration::from_secs(self.limits_for(domain).cooldown_secs.min(MAX_COOLDOWN_SECS));
+        let until = Instant::now() + cooldown;
+        let mut domains = self.domains.lock().unwrap();
+        let state = domains.entry(domain.to_string()).or_default();
+        if state.cooldown_until.is_none_or(|current| current < until) {
+            log::warn!(
+                "Rate limit detected for {}, cooling down for {:?}",
+                domain,
+                cooldown
+            );
+            state.cooldown_until = Some(until);
+        }
+    }
+
+    pub fn status(&self) -> Vec<DomainStatus> {
+        let config = self.config();
+        let now = Instant::now();
+        let domains = self.domains.lock().unwrap();
+        let mut status: Vec<DomainStatus> = domains
+            .iter()
+            .map(|(domain, state)| DomainStatus {
+                domain: domain.clone(),
+                active: state.active,
+                max_concurrent: config.limits_for(domain).max_concurrent,
+                cooldown_remaining_secs: state
+                    .cooldown_until
+                    .map(|until| until.saturating_duration_since(now).as_secs())
+                    .unwrap_or(0),
+            })
+            .collect();
+        status.sort_by(|a, b| a.domain.cmp(&b.domain));
+        status
+    }
+}
+
+// 国別トップレベルドメインの下で使われる2階層目のラベル
+const SECOND_LEVEL_LABELS: &[&str] = &[
+    "co", "com", "ne", "or", "ac", "go", "net", "org", "gov", "edu",
+];
+
+// 同じサイトをまとめて扱うためのドメイン名（サブドメインと短縮URLを正規化）
+pub fn domain_of(url: &str) -> String {
+    let host = match Url::parse(url.trim())
+        .ok()
+        .and_then(|u| u.host_str().map(str::to_lowercase))
+    {
+        Some(host) => host,
+        None => return "unknown".to_string(),
+    };
+
+    match host.as_str() {
+        "youtu.be" => return "youtube.com".to_string(),
+        "nico.ms" => return "nicovideo.jp".to_string(),
+        _ => {}
+    }
+
+    // co.jpやco.ukのような2階層のトップレベルドメインを考慮して末尾を取り出す
+    let labels: Vec<&str> = host.split('.').collect();
+    let take = match labels.as_slice() {
+        [.., _, second, tld] if tld.len() == 2 && SECOND_LEVEL_LABELS.contains(second) => 3,
+        _ => 2,
+    };
+    labels[labels.len().saturating_sub(take)..].join(".")
+}
+
+// 現在の制限設定とドメインごとの状態
+#[tauri::command]
+pub async fn get_throttle_status(
+    jobs: tauri::State<'_, crate::jobs::JobManager>,
+) -> Result<Vec<DomainStatus>, String> {
+    Ok(jobs.throttle.status())
+}
+
+#[tauri::command]
+pub async fn get_throttle_config(
+    jobs: tauri::State<'_, crate::jobs::JobManager>,
+) -> Result<ThrottleConfig, String> {
+    Ok(jobs.throttle.config())
+}
+
+#[tauri::command]
+pub async fn set_throttle_config(
+    config: ThrottleConfig,
+    jobs: tauri::State<'_, crate::jobs::JobManager>,
+    settings: tauri::State<'_, crate::settings::SettingsStore>,
+) -> Result<(), String> {
+    log::info!("Invoked set_throttle_config with config: {:?}", config);
+    config.validate()?;
+    // 再起動後も同じ制限になるよう設定にも保存
+    settings.update(|settings| settings.throttle = config.clone())?;
+    jobs.throttle.set_config(config);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn throttle(limits: DomainLimits) -> Arc<DomainThrottle> {
+        let throttle = Arc::new(DomainThrottle::default());
+        throttle.set_config(ThrottleConfig {
+            default_limits: limits,
+            overrides: HashMap::new(),
+        });
+        throttle
+    }
+
+    #[test]
+    fn domains_group_subdomains_and_short_urls() {
+        assert_eq!(
+            domain_of("https://www.youtube.com/watch?v=1"),
+            "youtube.com"
+        );
+        assert_eq!(domain_of("https://m.youtube.com/watch?v=1"), "youtube.com");
+        assert_eq!(domain_of("https://youtu.be/abc"), "youtube.com");
+        assert_eq!(domain_of("https://nico.ms/sm9"), "nicovideo.jp");
+        assert_eq!(domain_of("https://www.bbc.co.uk/iplayer"), "bbc.co.uk");
+        assert_eq!(domain_of("https://news.yahoo.co.jp/"), "yahoo.co.jp");
+        assert_eq!(domain_of("https://vimeo.com/76979871"), "vimeo.com");
+        assert_eq!(domain_of("HTTPS://Example.COM/"), "example.com");
+        assert_eq!(domain_of("not a url"), "unknown");
+    }
+
+    #[test]
+    fn permits_are_limited_per_domain() {
+        let throttle = throttle(DomainLimits {
+            max_concurrent: 2,
+            request_spacing_secs: 0.0,
+            cooldown_secs: 60,
+        });
+        let first = throttle.try_acquire("example.com").ok().unwrap();
+        let _second = throttle.try_acquire("example.com").ok().unwrap();
+        assert!(matches!(
+            throttle.try_acquire("example.com"),
+            Err(Wait::Busy)
+        ));
+        // 他のドメインは別に数える
+        assert!(throttle.try_acquire("example.org").is_ok());
+
+        drop(first);
+        assert_eq!(throttle.active("example.com"), 1);
+        assert!(throttle.try_acquire("example.com").is_ok());
+    }
+
+    #[test]
+    fn starts_are_spaced() {
+        let throttle = throttle(DomainLimits {
+            max_concurrent: 2,
+            request_spacing_secs: 30.0,
+            cooldown_secs: 60,
+        });
+        let _permit = throttle.try_acquire("example.com").ok().unwrap();
+        match throttle.try_acquire("example.com") {
+            Err(Wait::Until(delay)) => assert!(delay > Duration::from_secs(29)),
+            _ => panic!("expected to wait for the request spacing"),
+        }
+    }
+
+    #[test]
+    fn cooldown_blocks_until_it_expires() {
+        let throttle = throttle(DomainLimits {
+            max_concurrent: 1,
+            request_spacing_secs: 0.0,
+            cooldown_secs: 60,
+        });
+        throttle.cool_down("example.com");
+        let remaining = throttle.cooldown_remaining("example.com").unwrap();
+        assert!(remaining > Duration::from_secs(59));
+        assert!(matches!(
+            throttle.try_acquire("example.com"),
+            Err(Wait::Until(_))
+        ));
+        assert!((59..=60).contains(&throttle.status()[0].cooldown_remaining_secs));
+
+        // クールダウンの終了時刻を過ぎたら実行できる
+        throttle
+            .domains
+            .lock()
+            .unwrap()
+            .get_mut("example.com")
+            .unwrap()
+            .cooldown_until = Some(Instant::now());
+        assert!(throttle.cooldown_remaining("example.com").is_none());
+        assert!(throttle.try_acquire("example.com").is_ok());
+    }
+
+    #[test]
+    fn huge_limits_are_rejected_and_do_not_panic() {
+        let limits = DomainLimits {
+            max_concurrent: 1,
+            request_spacing_secs: 1e300,
+            cooldown_secs: u64::MAX,
+        };
+        let config = ThrottleConfig {
+            default_limits: limits.clone(),
+            overrides: HashMap::new(),
+        };
+        assert!(config.validate().is_err());
+        assert!(ThrottleConfig {
+            default_limits: DomainLimits {
+                request_spacing_secs: f64::NAN,
+                ..DomainLimits::default()
+            },
+            overrides: HashMap::new(),
+        }
+        .validate()
+        .is_err());
+
+        let throttle = throttle(limits);
+        let permit = throttle.try_acquire("example.com").ok().unwrap();
+        drop(permit);
+        assert!(matches!(
+            throttle.try_acquire("example.com"),
+            Err(Wait::Until(_))
+        ));
+        throttle.cool_down("example.com");
+    }
+}
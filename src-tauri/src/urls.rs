@@ -0,0 +1,430 @@
+use regex::Regex;
+use serde::Serialize;
+use std::collections::HashMap;
+use std::sync::LazyLock;
+use url::Url;
+
+// 全てのサイトで削除するトラッキング用のパラメーター（utm_で始まるものも削除）
+const TRACKING_PARAMS: &[&str] = &[
+    "fbclid", "gclid", "dclid", "gbraid", "wbraid", "yclid", "msclkid", "mc_cid", "mc_eid",
+    "igshid", "igsh", "_ga", "_gl", "ref_src", "ref_url", "spm",
+];
+
+// サイトごとに削除する共有用のパラメーター
+const SITE_TRACKING_PARAMS: &[(&str, &[&str])] = &[
+    (
+        "youtube.com",
+        &["si", "feature", "pp", "ab_channel", "embeds_referring_euri"],
+    ),
+    ("twitter.com", &["s", "t"]),
+    ("x.com", &["s", "t"]),
+    ("instagram.com", &["img_index"]),
+    (
+        "tiktok.com",
+        &["is_from_webapp", "sender_device", "_r", "_t"],
+    ),
+    ("nicovideo.jp", &["ref", "cmnhd_ref"]),
+];
+
+// 文章中のURL（スキームなしのwww.から始まるものも含む）
+// HTMLの属性やMarkdownのリンクの区切りになる文字で終わる
+// 「_」は単語の区切りにならないため、Markdownの強調の「_」が直前にあれば含めて一致させる
+static URL_PATTERN: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r#"(?i)(?:\b|_+)(?:https?://|www\.)[^\s<>"'`\\\]\[{}|^]+"#).unwrap()
+});
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct InvalidLine {
+    // 1から
+    pub line: usize,
+    pub text: String,
+    pub reason: String,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DuplicateUrl {
+    pub line: usize,
+    pub url: String,
+    // 先に出現した同じ動画のURL（正規化後）
+    pub duplicate_of: String,
+}
+
+#[derive(Debug, Clone, Default, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct UrlListResult {
+    // 正規化・重複除去後のURL（出現順）
+    pub urls: Vec<String>,
+    pub duplicates: Vec<DuplicateUrl>,
+    pub invalid: Vec<InvalidLine>,
+    // URLを含まない文章の行（チャットのメッセージなど）の数
+    pub skipped_lines: usize,
+}
+
+// 貼り付けられたテキスト（URLの一覧、HTML、Markdown、チャットのログなど）からURLを取り出す
+pub fn parse_url_list(text: &str) -> UrlListResult {
+    let mut result = UrlListResult::default();
+    // 重複判定のキーと最初のURL
+    let mut seen: HashMap<String, String> = HashMap::new();
+
+    for (i, line) in text.lines().enumerate() {
+        let line_number = i + 1;
+        let trimmed = line.trim();
+        // 空行とyt-dlpのバッチファイルのコメント行は無視
+        if trimmed.is_empty() || trimmed.starts_with(['#', ';', ']']) {
+            continue;
+        }
+
+        let candidates: Vec<&str> = URL_PATTERN
+            .find_iter(line)
+            .map(|m| {
+                let candidate = m.as_str().trim_start_matches('_');
+                trim_trailing(candidate, &line[..m.end() - candidate.len()])
+            })
+            .collect();
+        if candidates.is_empty() {
+            // 1語だけの行はURLの入力ミスとみなし、文章は無視する
+            if trimmed.split_whitespace().count() == 1 {
+                result.invalid.push(InvalidLine {
+                    line: line_number,
+                    text: trimmed.to_string(),
+                    reason: "No URL found".to_string(),
+                });
+            } else {
+                result.skipped_lines += 1;
+            }
+            continue;
+        }
+
+        for candidate in candidates {
+            let url = match normalize(candidate) {
+                Ok(url) => url,
+                Err(reason) => {
+                    result.invalid.push(InvalidLine {
+                        line: line_number,
+                        text: candidate.to_string(),
+                        reason,
+                    });
+                    continue;
+                }
+            };
+            let key = dedupe_key(&url);
+            match seen.get(&key) {
+                Some(first) => result.duplicates.push(DuplicateUrl {
+                    line: line_number,
+                    url,
+                    duplicate_of: first.clone(),
+                }),
+                None => {
+                    seen.insert(key, url.clone());
+                    result.urls.push(url);
+                }
+            }
+        }
+    }
+    result
+}
+
+// 同じ動画かどうかの判定に使うキー（アーカイブと同じ「エクストラクター ID」、判別できなければURL）
+pub fn dedupe_key(url: &str) -> String {
+    match crate::archive::archive_entry_for_url(url) {
+        Some(entry) => format!("{} {}", entry.extractor, entry.id),
+        None => url.to_string(),
+    }
+}
+
+// 文末の句読点や、URLの外側の閉じ括弧・Markdownの強調の記号を除く
+// 「_」「*」「~」はURLの末尾にも使われるため（YouTubeの動画IDなど）、直前の開きの記号と対応する場合だけ除く
+fn trim_trailing<'a>(candidate: &'a str, before: &str) -> &'a str {
+    let opening = &before[before.trim_end_matches(['*', '_', '~']).len()..];
+    let closing: String = opening.chars().rev().collect();
+    let mut emphasis_closed = closing.is_empty();
+    let mut url = candidate;
+    loop {
+        let mut trimmed = url.trim_end_matches(['.', ',', ';', ':', '!', '?']);
+        if !emphasis_closed {
+            if let Some(inner) = trimmed.strip_suffix(closing.as_str()) {
+                trimmed = inner;
+                emphasis_closed = true;
+            }
+        }
+        // Markdownのリンクや括弧書きの「)」は、URL内の括弧と対応しない場合だけ除く
+        let trimmed = match trimmed.strip_suffix(')') {
+            Some(inner) if inner.matches('(').count() < trimmed.matches(')').count() => inner,
+            _ => trimmed,
+        };
+        if trimmed == url {
+            return url;
+        }
+        url = trimmed;
+    }
+}
+
+// URLを正規化（スキームの補完、HTMLの&amp;の復元、短縮URLの展開、トラッキング用パラメーターの削除）
+pub fn normalize(candidate: &str) -> Result<String, String> {
+    let candidate = candidate.replace("&amp;", "&");
+    let candidate = if candidate.to_lowercase().starts_with("www.") {
+        format!("https://{}", candidate)
+    } else {
+        candidate
+    };
+    let mut url = Url::parse(&candidate).map_err(|e| format!("Invalid URL: {}", e))?;
+    let host = url
+        .host_str()
+        .filter(|host| host.contains('.') || *host == "localhost")
+        .ok_or_else(|| "URL has no valid host".to_string())?
+        .to_lowercase();
+    let path_segments: Vec<String> = url
+        .path_segments()
+        .map(|segments| {
+            segments
+                .filter(|segment| !segment.is_empty())
+                .map(str::to_string)
+                .collect()
+        })
+        .unwrap_or_default();
+
+    // 短縮URLやモバイル版のURLを通常のURLにする
+    match host.as_str() {
+        "youtu.be" => {
+            if let Some(id) = path_segments.first() {
+                let query: Vec<(String, String)> = url.query_pairs().into_owned().collect();
+                url = Url::parse("https://www.youtube.com/watch").unwrap();
+                url.query_pairs_mut()
+                    .append_pair("v", id)
+                    .extend_pairs(query.iter().filter(|(key, _)| key != "v"));
+            }
+        }
+        "youtube.com" | "m.youtube.com" => {
+            let _ = url.set_host(Some("www.youtube.com"));
+        }
+        "nico.ms" => {
+            if let Some(id) = path_segments.first() {
+                url = Url::parse(&format!("https://www.nicovideo.jp/watch/{}", id))
+                    .map_err(|e| format!("Invalid URL: {}", e))?;
+            }
+        }
+        "sp.nicovideo.jp" => {
+            let _ = url.set_host(Some("www.nicovideo.jp"));
+        }
+        "mobile.twitter.com" => {
+            let _ = url.set_host(Some("twitter.com"));
+        }
+        _ => {}
+    }
+
+    strip_tracking_params(&mut url);
+    if url.fragment() == Some("") {
+        url.set_fragment(None);
+    }
+    Ok(url.to_string())
+}
+
+fn strip_tracking_params(url: &mut Url) {
+    if url.query().is_none() {
+        return;
+    }
+    let host = url
+        .host_str()
+        .unwrap_or_default()
+        .trim_start_matches("www.")
+        .to_lowercase();
+    let site_params: &[&str] = SITE_TRACKING_PARAMS
+        .iter()
+        .find(|(site, _)| host == *site || host.ends_with(&format!(".{}", site)))
+        .map(|(_, params)| *params)
+        .unwrap_or_default();
+
+    let kept: Vec<(String, String)> = url
+        .query_pairs()
+        .into_owned()
+        .filter(|(key, _)| {
+            let key = key.to_lowercase();
+            !key.starts_with("utm_")
+                && !TRACKING_PARAMS.contains(&key.as_str())
+                && !site_params.contains(&key.as_str())
+        })
+        .collect();
+    if kept.is_empty() {
+        url.set_query(None);
+    } else {
+        url.query_pairs_mut().clear().extend_pairs(kept);
+    }
+}
+
+// 貼り付けられたテキストを解析（書き込み前の確認用）
+#[tauri::command]
+pub async fn parse_urls(text: String) -> Result<UrlListResult, String> {
+    let result = parse_url_list(&text);
+    log::info!(
+        "Parsed URL list: {} URLs, {} duplicates, {} invalid lines",
+        result.urls.len(),
+        result.duplicates.len(),
+        result.invalid.len()
+    );
+    Ok(result)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn urls(text: &str) -> Vec<String> {
+        parse_url_list(text).urls
+    }
+
+    #[test]
+    fn short_and_mobile_urls_are_expanded() {
+        assert_eq!(
+            normalize("https://youtu.be/dQw4w9WgXcQ?si=AbCdEf&t=42").unwrap(),
+            "https://www.youtube.com/watch?v=dQw4w9WgXcQ&t=42"
+        );
+        assert_eq!(
+            normalize("https://m.youtube.com/watch?v=dQw4w9WgXcQ&feature=share").unwrap(),
+            "https://www.youtube.com/watch?v=dQw4w9WgXcQ"
+        );
+        // ショートはyt-dlpがそのまま扱えるため、URLは変えずに重複判定で同じ動画とみなす
+        assert_eq!(
+            normalize("https://youtube.com/shorts/aqz-KE-bpKQ?feature=share").unwrap(),
+            "https://www.youtube.com/shorts/aqz-KE-bpKQ"
+        );
+        assert_eq!(
+            normalize("https://nico.ms/sm9").unwrap(),
+            "https://www.nicovideo.jp/watch/sm9"
+        );
+        assert_eq!(
+            normalize("www.example.com/video").unwrap(),
+            "https://www.example.com/video"
+        );
+    }
+
+    #[test]
+    fn tracking_params_are_removed() {
+        assert_eq!(
+            normalize(
+                "https://www.youtube.com/watch?v=dQw4w9WgXcQ&list=PL1&utm_source=x&fbclid=1&pp=ygU"
+            )
+            .unwrap(),
+            "https://www.youtube.com/watch?v=dQw4w9WgXcQ&list=PL1"
+        );
+        // サイトごとのパラメーターは、そのサイトでだけ削除する
+        assert_eq!(
+            normalize("https://x.com/user/status/123?s=20&t=AbC").unwrap(),
+            "https://x.com/user/status/123"
+        );
+        assert_eq!(
+            normalize("https://example.com/watch?t=30&s=2").unwrap(),
+            "https://example.com/watch?t=30&s=2"
+        );
+        assert_eq!(
+            normalize("https://example.com/video?utm_medium=social#").unwrap(),
+            "https://example.com/video"
+        );
+    }
+
+    #[test]
+    fn html_entities_are_decoded() {
+        assert_eq!(
+            urls(
+                r#"<a href="https://www.youtube.com/watch?v=dQw4w9WgXcQ&amp;list=PL1&amp;index=2">video</a>"#
+            ),
+            vec!["https://www.youtube.com/watch?v=dQw4w9WgXcQ&list=PL1&index=2"]
+        );
+    }
+
+    #[test]
+    fn surrounding_punctuation_is_trimmed() {
+        assert_eq!(
+            urls("[video](https://youtu.be/dQw4w9WgXcQ)"),
+            vec!["https://www.youtube.com/watch?v=dQw4w9WgXcQ"]
+        );
+        assert_eq!(
+            urls("see https://en.wikipedia.org/wiki/Rust_(programming_language)."),
+            vec!["https://en.wikipedia.org/wiki/Rust_(programming_language)"]
+        );
+        assert_eq!(
+            urls("(https://example.com/a_(b)), next"),
+            vec!["https://example.com/a_(b)"]
+        );
+        assert_eq!(
+            urls("**https://example.com/video** and _https://example.com/clip_"),
+            vec!["https://example.com/video", "https://example.com/clip"]
+        );
+        // 強調でなければ末尾の「_」「*」はURLの一部
+        assert_eq!(
+            urls("https://youtu.be/abcdefghij_ https://example.com/a*"),
+            vec![
+                "https://www.youtube.com/watch?v=abcdefghij_",
+                "https://example.com/a*"
+            ]
+        );
+    }
+
+    #[test]
+    fn duplicates_use_the_video_key() {
+        assert_eq!(
+            dedupe_key("https://www.youtube.com/shorts/aqz-KE-bpKQ"),
+            "youtube aqz-KE-bpKQ"
+        );
+        assert_eq!(
+            dedupe_key("https://example.com/video"),
+            "https://example.com/video"
+        );
+
+        let result = parse_url_list(
+            "https://www.youtube.com/watch?v=aqz-KE-bpKQ\n\
+             https://youtu.be/aqz-KE-bpKQ?si=123\n\
+             https://youtube.com/shorts/aqz-KE-bpKQ\n\
+             https://example.com/video?utm_source=a\n\
+             https://example.com/video",
+        );
+        assert_eq!(
+            result.urls,
+            vec![
+                "https://www.youtube.com/watch?v=aqz-KE-bpKQ",
+                "https://example.com/video"
+            ]
+        );
+        let duplicates: Vec<(usize, &str)> = result
+            .duplicates
+            .iter()
+            .map(|duplicate| (duplicate.line, duplicate.duplicate_of.as_str()))
+            .collect();
+        assert_eq!(
+            duplicates,
+            vec![
+                (2, "https://www.youtube.com/watch?v=aqz-KE-bpKQ"),
+                (3, "https://www.youtube.com/watch?v=aqz-KE-bpKQ"),
+                (5, "https://example.com/video"),
+            ]
+        );
+    }
+
+    #[test]
+    fn invalid_lines_keep_their_line_numbers() {
+        let result = parse_url_list(
+            "https://example.com/a\n\
+             \n\
+             # comment https://example.com/ignored\n\
+             example.com/no-scheme\n\
+             check this one later\n\
+             http://intranet/video https://example.com/b",
+        );
+        assert_eq!(
+            result.urls,
+            vec!["https://example.com/a", "https://example.com/b"]
+        );
+        let invalid: Vec<(usize, &str)> = result
+            .invalid
+            .iter()
+            .map(|invalid| (invalid.line, invalid.text.as_str()))
+            .collect();
+        assert_eq!(
+            invalid,
+            vec![(4, "example.com/no-scheme"), (6, "http://intranet/video")]
+        );
+        assert_eq!(result.invalid[1].reason, "URL has no valid host");
+        assert_eq!(result.skipped_lines, 1);
+    }
+}
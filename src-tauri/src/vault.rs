@@ -0,0 +1,455 @@
+use argon2::{Algorithm, Argon2, Params, Version};
+use base64::{engine::general_purpose::STANDARD, Engine};
+use chacha20poly1305::aead::{rand_core::RngCore, Aead, AeadCore, KeyInit, OsRng};
+use chacha20poly1305::{Key, XChaCha20Poly1305, XNonce};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+const VAULT_VERSION: u32 = 1;
+const MIN_PASSPHRASE_LEN: usize = 8;
+const SALT_LEN: usize = 16;
+
+// 鍵導出（Argon2id）のパラメータ
+const KDF_MEMORY_KIB: u32 = 64 * 1024;
+const KDF_ITERATIONS: u32 = 3;
+const KDF_PARALLELISM: u32 = 1;
+
+// ユーザー名・パスワードを取るオプション（指定済みなら資格情報ストアの値は使わない）
+const LOGIN_OPTIONS: &[&str] = &["-u", "--username", "-p", "--password", "-n", "--netrc"];
+
+// サイトごとの資格情報（暗号化して保存し、フロントエンドには返さない）
+#[derive(Clone, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct Credential {
+    pub username: Option<String>,
+    pub password: Option<String>,
+    pub video_password: Option<String>,
+}
+
+// list_credentialsで返す資格情報の概要（パスワードは含めない）
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CredentialSummary {
+    pub site: String,
+    pub username: Option<String>,
+    pub has_password: bool,
+    pub has_video_password: bool,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct VaultStatus {
+    pub exists: bool,
+    pub unlocked: bool,
+}
+
+// vault.jsonの内容（資格情報はパスフレーズから導出した鍵で暗号化）
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct VaultFile {
+    version: u32,
+    salt: String,
+    memory_kib: u32,
+    iterations: u32,
+    parallelism: u32,
+    nonce: String,
+    ciphertext: String,
+}
+
+struct Unlocked {
+    key: [u8; 32],
+    salt: Vec<u8>,
+    // 保存時も開いたときと同じ鍵導出パラメータを書き込む
+    memory_kib: u32,
+    iterations: u32,
+    parallelism: u32,
+    credentials: BTreeMap<String, Credential>,
+}
+
+impl Drop for Unlocked {
+    fn drop(&mut self) {
+        self.key.fill(0);
+    }
+}
+
+// 資格情報ストア（ロック解除中だけ鍵と資格情報をメモリに保持）
+#[derive(Default)]
+pub struct Vault {
+    unlocked: Mutex<Option<Unlocked>>,
+}
+
+impl Vault {
+    // ドメインに対応する資格情報をyt-dlpの引数にする（ユーザー指定のオプションがあればそちらを優先）
+    // コマンドラインに渡すと他のプロセスから見えるため、write_credential_configで設定ファイルにして渡す
+    pub fn credential_args(&self, domain: &str, options: &[String]) -> Vec<String> {
+        let unlocked = self.unlocked.lock().unwrap();
+        let Some(credential) = unlocked
+            .as_ref()
+            .and_then(|unlocked| unlocked.credentials.get(domain))
+        else {
+            return Vec::new();
+        };
+
+        let mut args = Vec::new();
+        if !has_option(options, LOGIN_OPTIONS) {
+            if let Some(username) = &credential.username {
+                args.push("--username".to_string());
+                args.push(username.clone());
+                if let Some(password) = &credential.password {
+                    args.push("--password".to_string());
+                    args.push(password.clone());
+                }
+            }
+        }
+        if !has_option(options, &["--video-password"]) {
+            if let Some(video_password) = &credential.video_password {
+                args.push("--video-password".to_string());
+                args.push(video_password.clone());
+            }
+        }
+        args
+    }
+}
+
+// 資格情報の引数を所有者のみ読める設定ファイルに書き込む（yt-dlpの--config-locationsで読み込ませる）
+pub fn write_credential_config(path: &Path, args: &[String]) -> Result<(), String> {
+    let mut lines = Vec::new();
+    for pair in args.chunks(2) {
+        let quoted: Result<Vec<_>, _> = pair.iter().map(|arg| shlex::try_quote(arg)).collect();
+        let quoted = quoted.map_err(|e| format!("Invalid credential value: {}", e))?;
+        lines.push(quoted.join(" "));
+    }
+
+    let mut file = crate::cookies::create_private_file(path)?;
+    writeln!(file, "{}", lines.join("\n")).map_err(|e| {
+        log::error!("Failed to write credential config: {}", e);
+        format!("Failed to write credential config: {}", e)
+    })
+}
+
+// 資格情報ストアのロックを解除（ストアがなければこのパスフレーズで新規作成）
+#[tauri::command]
+pub async fn unlock_vault(
+    passphrase: String,
+    vault: tauri::State<'_, Vault>,
+) -> Result<VaultStatus, String> {
+    log::info!("Invoked unlock_vault");
+    let path = vault_path()?;
+
+    let unlocked = if path.exists() {
+        let content = fs::read_to_string(&path).map_err(|e| {
+            log::error!("Could not read credential vault: {}", e);
+            format!("Could not read credential vault: {}", e)
+        })?;
+        let file: VaultFile = serde_json::from_str(&content).map_err(|e| {
+            log::error!("Failed to parse credential vault: {}", e);
+            format!("Failed to parse credential vault: {}", e)
+        })?;
+        open_vault(&file, &passphrase)?
+    } else {
+        if passphrase.chars().count() < MIN_PASSPHRASE_LEN {
+            return Err(format!(
+                "Passphrase must be at least {} characters",
+                MIN_PASSPHRASE_LEN
+            ));
+        }
+        let mut salt = vec![0u8; SALT_LEN];
+        OsRng.fill_bytes(&mut salt);
+        let unlocked = Unlocked {
+            key: derive_key(
+                &passphrase,
+                &salt,
+                KDF_MEMORY_KIB,
+                KDF_ITERATIONS,
+                KDF_PARALLELISM,
+            )?,
+            salt,
+            memory_kib: KDF_MEMORY_KIB,
+            iterations: KDF_ITERATIONS,
+            parallelism: KDF_PARALLELISM,
+            credentials: BTreeMap::new(),
+        };
+        save_vault(&unlocked)?;
+        log::info!("Created credential vault: {:?}", path);
+        unlocked
+    };
+
+    for credential in unlocked.credentials.values() {
+        register_secrets(credential);
+    }
+    *vault.unlocked.lock().unwrap() = Some(unlocked);
+    Ok(VaultStatus {
+        exists: true,
+        unlocked: true,
+    })
+}
+
+#[tauri::command]
+pub async fn lock_vault(vault: tauri::State<'_, Vault>) -> Result<(), String> {
+    log::info!("Invoked lock_vault");
+    *vault.unlocked.lock().unwrap() = None;
+    Ok(())
+}
+
+#[tauri::command]
+pub async fn get_vault_status(vault: tauri::State<'_, Vault>) -> Result<VaultStatus, String> {
+    Ok(VaultStatus {
+        exists: vault_path()?.exists(),
+        unlocked: vault.unlocked.lock().unwrap().is_some(),
+    })
+}
+
+// 保存済みの資格情報一覧（ロック解除中のみ）
+#[tauri::command]
+pub async fn list_credentials(
+    vault: tauri::State<'_, Vault>,
+) -> Result<Vec<CredentialSummary>, String> {
+    let unlocked = vault.unlocked.lock().unwrap();
+    let unlocked = unlocked.as_ref().ok_or_else(locked_error)?;
+    Ok(unlocked
+        .credentials
+        .iter()
+        .map(|(site, credential)| CredentialSummary {
+            site: site.clone(),
+            username: credential.username.clone(),
+            has_password: credential.password.is_some(),
+            has_video_password: credential.video_password.is_some(),
+        })
+        .collect())
+}
+
+#[tauri::command]
+pub async fn set_credential(
+    site: String,
+    credential: Credential,
+    vault: tauri::State<'_, Vault>,
+) -> Result<(), String> {
+    log::info!("Invoked set_credential with site: {:?}", site);
+    let site = crate::cookies::normalize_site(&site);
+    if site == "unknown" {
+        return Err("Invalid site name".to_string());
+    }
+    if credential.password.is_some() && credential.username.is_none() {
+        return Err("A password requires a username".to_string());
+    }
+
+    let mut unlocked = vault.unlocked.lock().unwrap();
+    let unlocked = unlocked.as_mut().ok_or_else(locked_error)?;
+    register_secrets(&credential);
+    unlocked.credentials.insert(site, credential);
+    save_vault(unlocked)
+}
+
+#[tauri::command]
+pub async fn delete_credential(site: String, vault: tauri::State<'_, Vault>) -> Result<(), String> {
+    log::info!("Invoked delete_credential with site: {:?}", site);
+    let site = crate::cookies::normalize_site(&site);
+    let mut unlocked = vault.unlocked.lock().unwrap();
+    let unlocked = unlocked.as_mut().ok_or_else(locked_error)?;
+    if unlocked.credentials.remove(&site).is_none() {
+        return Err(format!("No credential stored for {}", site));
+    }
+    save_vault(unlocked)
+}
+
+fn vault_path() -> Result<PathBuf, String> {
+    Ok(crate::paths::data_dir()?.join("vault.json"))
+}
+
+fn locked_error() -> String {
+    "Credential vault is locked".to_string()
+}
+
+fn has_option(options: &[String], names: &[&str]) -> bool {
+    options.iter().any(|arg| {
+        names
+            .iter()
+            .any(|name| arg == name || arg.starts_with(&format!("{}=", name)))
+    })
+}
+
+// ログとイベントに出ないようにパスワードを伏せ字の対象に登録
+fn register_secrets(credential: &Credential) {
+    for secret in [&credential.password, &credential.video_password]
+        .into_iter()
+        .flatten()
+    {
+        crate::redact::register_secret(secret);
+    }
+}
+
+fn derive_key(
+    passphrase: &str,
+    salt: &[u8],
+    memory_kib: u32,
+    iterations: u32,
+    parallelism: u32,
+) -> Result<[u8; 32], String> {
+    let params = Params::new(memory_kib, iterations, parallelism, Some(32)).map_err(|e| {
+        log::error!("Invalid key derivation parameters: {}", e);
+        format!("Invalid key derivation parameters: {}", e)
+    })?;
+    let mut key = [0u8; 32];
+    Argon2::new(Algorithm::Argon2id, Version::V0x13, params)
+        .hash_password_into(passphrase.as_bytes(), salt, &mut key)
+        .map_err(|e| {
+            log::error!("Failed to derive vault key: {}", e);
+            format!("Failed to derive vault key: {}", e)
+        })?;
+    Ok(key)
+}
+
+// パスフレーズで復号（認証タグの検証に失敗したらパスフレーズ違い）
+fn open_vault(file: &VaultFile, passphrase: &str) -> Result<Unlocked, String> {
+    if file.version != VAULT_VERSION {
+        return Err(format!(
+            "Unsupported credential vault version: {}",
+            file.version
+        ));
+    }
+    let decode = |value: &str| {
+        STANDARD.decode(value).map_err(|e| {
+            log::error!("Credential vault is corrupted: {}", e);
+            format!("Credential vault is corrupted: {}", e)
+        })
+    };
+    let salt = decode(&file.salt)?;
+    let nonce = decode(&file.nonce)?;
+    let ciphertext = decode(&file.ciphertext)?;
+    if nonce.len() != 24 {
+        return Err("Credential vault is corrupted: invalid nonce".to_string());
+    }
+
+    let key = derive_key(
+        passphrase,
+        &salt,
+        file.memory_kib,
+        file.iterations,
+        file.parallelism,
+    )?;
+    let plaintext = XChaCha20Poly1305::new(Key::from_slice(&key))
+        .decrypt(XNonce::from_slice(&nonce), ciphertext.as_slice())
+        .map_err(|_| {
+            log::warn!("Failed to unlock credential vault: incorrect passphrase");
+            "Incorrect passphrase".to_string()
+        })?;
+    let credentials = serde_json::from_slice(&plaintext).map_err(|e| {
+        log::error!("Failed to parse decrypted credentials: {}", e);
+        format!("Failed to parse decrypted credentials: {}", e)
+    })?;
+
+    let mut unlocked = Unlocked {
+        key,
+        salt,
+        memory_kib: file.memory_kib,
+        iterations: file.iterations,
+        parallelism: file.parallelism,
+        credentials,
+    };
+    // 既定より弱い鍵導出パラメータで保存されていれば、既定値以上に引き上げて保存し直す
+    if strengthen_params(&mut unlocked, passphrase)? {
+        log::info!("Upgraded key derivation parameters of credential vault");
+        save_vault(&unlocked)?;
+    }
+    Ok(unlocked)
+}
+
+// 鍵導出パラメータを既定値以上にして、新しいソルトで鍵を導出し直す（変更した場合はtrue）
+fn strengthen_params(unlocked: &mut Unlocked, passphrase: &str) -> Result<bool, String> {
+    let memory_kib = unlocked.memory_kib.max(KDF_MEMORY_KIB);
+    let iterations = unlocked.iterations.max(KDF_ITERATIONS);
+    let parallelism = unlocked.parallelism.max(KDF_PARALLELISM);
+    if (memory_kib, iterations, parallelism)
+        == (
+            unlocked.memory_kib,
+            unlocked.iterations,
+            unlocked.parallelism,
+        )
+    {
+        return Ok(false);
+    }
+
+    let mut salt = vec![0u8; SALT_LEN];
+    OsRng.fill_bytes(&mut salt);
+    unlocked.key = derive_key(passphrase, &salt, memory_kib, iterations, parallelism)?;
+    unlocked.salt = salt;
+    unlocked.memory_kib = memory_kib;
+    unlocked.iterations = iterations;
+    unlocked.parallelism = parallelism;
+    Ok(true)
+}
+
+// 資格情報を新しいnonceで暗号化して保存
+fn save_vault(unlocked: &Unlocked) -> Result<(), String> {
+    let plaintext = serde_json::to_vec(&unlocked.credentials).map_err(|e| {
+        log::error!("Failed to serialize credentials: {}", e);
+        format!("Failed to serialize credentials: {}", e)
+    })?;
+    let nonce = XChaCha20Poly1305::generate_nonce(&mut OsRng);
+    let ciphertext = XChaCha20Poly1305::new(Key::from_slice(&unlocked.key))
+        .encrypt(&nonce, plaintext.as_slice())
+        .map_err(|e| {
+            log::error!("Failed to encrypt credentials: {}", e);
+            format!("Failed to encrypt credentials: {}", e)
+        })?;
+
+    let file = VaultFile {
+        version: VAULT_VERSION,
+        salt: STANDARD.encode(&unlocked.salt),
+        memory_kib: unlocked.memory_kib,
+        iterations: unlocked.iterations,
+        parallelism: unlocked.parallelism,
+        nonce: STANDARD.encode(nonce),
+        ciphertext: STANDARD.encode(ciphertext),
+    };
+    let json = serde_json::to_string_pretty(&file).map_err(|e| {
+        log::error!("Failed to serialize credential vault: {}", e);
+        format!("Failed to serialize credential vault: {}", e)
+    })?;
+
+    // 一時ファイルに書き込んでから置き換え、書き込み途中の破損を防ぐ
+    let path = vault_path()?;
+    let tmp_path = path.with_extension("json.tmp");
+    fs::write(&tmp_path, json).map_err(|e| {
+        log::error!("Failed to write credential vault: {}", e);
+        format!("Failed to write credential vault: {}", e)
+    })?;
+    crate::cookies::restrict_permissions(&tmp_path);
+    fs::rename(&tmp_path, &path).map_err(|e| {
+        log::error!("Failed to save credential vault: {}", e);
+        format!("Failed to save credential vault: {}", e)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn credential_config_quotes_values() {
+        let path = std::env::temp_dir().join(format!(
+            "takumi-vid-dl-credentials-{}.conf",
+            std::process::id()
+        ));
+        let args: Vec<String> = ["--username", "user name", "--password", "p'a\"s#s"]
+            .iter()
+            .map(|arg| arg.to_string())
+            .collect();
+        write_credential_config(&path, &args).unwrap();
+        let content = fs::read_to_string(&path).unwrap();
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            let mode = fs::metadata(&path).unwrap().permissions().mode();
+            assert_eq!(mode & 0o777, 0o600);
+        }
+        let _ = fs::remove_file(&path);
+
+        assert_eq!(content.lines().count(), 2);
+        assert_eq!(shlex::split(&content).unwrap(), args);
+    }
+}
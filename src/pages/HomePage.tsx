@@ -54,6 +54,35 @@ interface ToolStatus {
     loading: boolean;
 }
 
+interface UrlResult {
+    url: string;
+    videoId: string | null;
+    outcome: 'success' | 'alreadyArchived' | 'unavailable' | 'geoBlocked' | 'loginRequired' | 'private' | 'otherError';
+    message: string | null;
+}
+
+interface BatchReport {
+    success: boolean;
+    exitCode: number | null;
+    summary: { total: number; succeeded: number; alreadyArchived: number; failed: number };
+    results: UrlResult[];
+}
+
+interface Settings {
+    schemaVersion: number;
+    outputDir: string;
+    ffmpegDir: string;
+    optionMode: 'auto' | 'custom';
+    customOptions: string;
+    retryPolicy: unknown;
+    throttle: unknown;
+    diskSpace: unknown;
+    clipboard: unknown;
+    api: unknown;
+    hooks: unknown;
+    bandwidth: unknown;
+}
+
 interface LogViewProps {
     log: string[];
     isProcessing: boolean;
@@ -63,7 +92,7 @@ interface LogViewProps {
 // --- Command Presets ---
 const YTDLP_COMMANDS: string[] = [
     // Format Selection
-    `-f "bv*+ba/b" -o "%(title).200B [%(id)s].%(ext)s" --no-continue --sleep-requests 2 --sleep-interval 3 --max-sleep-interval 8 --remux-video mp4/mkv --embed-metadata --embed-thumbnail --convert-thumbnails png`,
+    `-f "bv*+ba/b" -o "%(title).200B [%(id)s].%(ext)s" --no-continue --remux-video mp4/mkv --embed-metadata --embed-thumbnail --convert-thumbnails png`,
     `-f "bestvideo[ext=mp4]+bestaudio[ext=m4a]/best[ext=mp4]/best"`,
     `-f "bestvideo[ext=webm]+bestaudio[ext=webm]/best[ext=webm]/best"`,
     `-f "bestvideo+bestaudio"`,
@@ -115,6 +144,9 @@ const HomePage: React.FC = () => {
     const [isProcessing, setIsProcessing] = useState(false);
     const [isModalOpen, setIsModalOpen] = useState(false);
     const [confirmationChecked, setConfirmationChecked] = useState(false);
+    const [settings, setSettings] = useState<Settings | null>(null);
+    const [settingsError, setSettingsError] = useState<string | null>(null);
+    const [customOptionsError, setCustomOptionsError] = useState<string | null>(null);
 
     // Menu Anchors
     const [ytdlpMenuAnchor, setYtdlpMenuAnchor] = useState<null | HTMLElement>(null);
@@ -264,7 +296,10 @@ const HomePage: React.FC = () => {
                 updateLogWithLimit(errorLine, data.overwrite);
             }),
 
-            listen('yt-dlp-started', () => {
+            listen('yt-dlp-started', (event) => {
+                // パスワードなどはバックエンドで伏せ字にしたコマンドを表示
+                const command = event.payload as string;
+                addLogWithLimit(`[INFO] Executing command: yt-dlp ${command}`);
                 addLogWithLimit('[INFO] yt-dlp process started...');
             }),
 
@@ -311,10 +346,6 @@ const HomePage: React.FC = () => {
             commandParts.push('--batch-file');
             commandParts.push(`"${urlsFilePath}"`);
 
-            // 一時ディレクトリを指定（中間ファイル用）
-            commandParts.push('--paths');
-            commandParts.push('"temp:./tmp"');
-
             // 出力ディレクトリを指定
             if (outputPath) {
                 commandParts.push('--paths');
@@ -332,18 +363,27 @@ const HomePage: React.FC = () => {
             // カスタムオプションを追加
             if (ytdlpOption === 'custom' && ytdlpCustom.trim()) {
                 commandParts.push(ytdlpCustom.trim());
-                addLogWithLimit(`[INFO] Custom options: ${ytdlpCustom}`);
             }
 
             const fullCommand = commandParts.join(' ');
-            addLogWithLimit(`[INFO] Executing command: yt-dlp ${fullCommand}`);
 
             // 3. yt-dlpを実行（リアルタイム出力はイベントリスナーで処理）
-            await invoke<string>('run_yt_dlp', {
+            const report = await invoke<BatchReport>('run_yt_dlp', {
                 commandLine: fullCommand
             });
 
-            addLogWithLimit('[SUCCESS] All processes have been completed.');
+            // 4. URLごとの結果を表示
+            const { total, succeeded, alreadyArchived, failed } = report.summary;
+            addLogWithLimit(`[INFO] Results: ${succeeded} succeeded, ${alreadyArchived} already archived, ${failed} failed (total ${total})`);
+            report.results
+                .filter((result) => result.outcome !== 'success' && result.outcome !== 'alreadyArchived')
+                .forEach((result) => addLogWithLimit(`[ERROR] ${result.url} (${result.outcome}): ${result.message ?? ''}`));
+
+            if (report.success) {
+                addLogWithLimit('[SUCCESS] All processes have been completed.');
+            } else {
+                addLogWithLimit(`[ERROR] yt-dlp exited with status ${report.exitCode ?? 'unknown'}.`);
+            }
 
         } catch (error) {
             console.error("Error during download process:", error);
@@ -357,6 +397,54 @@ const HomePage: React.FC = () => {
         if (activeStep === 2) checkTools();
     }, [activeStep, checkTools]);
 
+    // 保存済みの設定を読み込み
+    useEffect(() => {
+        invoke<Settings>('get_settings')
+            .then((loaded) => {
+                setOutputPath(loaded.outputDir);
+                setFfmpegPath(loaded.ffmpegDir);
+                setYtdlpOption(loaded.optionMode);
+                setYtdlpCustom(loaded.customOptions);
+                setSettings(loaded);
+            })
+            .catch((error) => console.error("Failed to load settings:", error));
+    }, []);
+
+    // 設定の変更を保存（入力中の連続した変更はまとめる）
+    // この画面で編集するフィールドだけを送り、他の画面での変更を上書きしない
+    useEffect(() => {
+        if (!settings) return;
+        const timer = setTimeout(() => {
+            invoke<Settings>('update_settings', {
+                patch: {
+                    outputDir: outputPath,
+                    ffmpegDir: ffmpegPath,
+                    optionMode: ytdlpOption,
+                },
+            })
+                .then(() => setSettingsError(null))
+                .catch((error) => {
+                    console.error("Failed to save settings:", error);
+                    setSettingsError(String(error));
+                });
+        }, 500);
+        return () => clearTimeout(timer);
+    }, [settings, outputPath, ffmpegPath, ytdlpOption]);
+
+    // 入力途中の引用符などで保存できなくても他の設定は保存されるよう、カスタムオプションは別に保存
+    useEffect(() => {
+        if (!settings) return;
+        const timer = setTimeout(() => {
+            invoke<Settings>('update_settings', { patch: { customOptions: ytdlpCustom } })
+                .then(() => setCustomOptionsError(null))
+                .catch((error) => {
+                    console.error("Failed to save custom options:", error);
+                    setCustomOptionsError(String(error));
+                });
+        }, 500);
+        return () => clearTimeout(timer);
+    }, [settings, ytdlpCustom]);
+
     const getStepContent = (step: number) => {
         switch (step) {
             case 0:
@@ -403,6 +491,8 @@ const HomePage: React.FC = () => {
                                     margin="normal"
                                     value={ytdlpCustom}
                                     onChange={(e) => setYtdlpCustom(e.target.value)}
+                                    error={Boolean(customOptionsError)}
+                                    helperText={customOptionsError ? `Not saved: ${customOptionsError}` : undefined}
                                     sx={{ fontFamily: 'monospace' }}
                                     InputProps={{
                                         endAdornment: (
@@ -559,6 +649,12 @@ const HomePage: React.FC = () => {
                             </Stepper>
                         </Box>
                         <Box sx={{ flexGrow: 1, overflowY: "auto" }}>
+                            {settingsError && (
+                                <Alert severity="error" sx={{ m: 2 }} onClose={() => setSettingsError(null)}>
+                                    <AlertTitle>Failed to save settings</AlertTitle>
+                                    {settingsError}
+                                </Alert>
+                            )}
                             {getStepContent(activeStep)}
                         </Box>
                         <Box sx={{ borderTop: 1, borderColor: "divider", p: 2, display: "flex", justifyContent: "space-between" }}>